@@ -1,55 +1,339 @@
 from __future__ import annotations
-from typing import Any, Dict, Optional, Tuple, List
+from typing import Any, Dict, Optional, Tuple, List, TypeVar
 from . import rust_accel as rust_accel
 
+_T = TypeVar("_T")
+
 # Public functions exposed by the native extension
 
-def load_schema(path: str) -> bool: ...
+# jsonc=True (implied for .jsonc/.json5 files) allows comments and trailing commas
+def load_schema(path: str, jsonc: bool = False) -> bool: ...
+
+# Same, from JSON text in memory; get_schema_status() then reports source "string"
+def load_schema_json(config_json: str, jsonc: bool = False) -> bool: ...
+
+# Load a schema under `name`, kept alongside others; the single-schema API uses "default"
+def load_schema_named(name: str, schema_path: str, jsonc: bool = False) -> bool: ...
+
+# Background reload of the schema file whenever its mtime changes
+def enable_schema_autoreload(schema_path: str, interval_ms: int = 1000) -> bool: ...
+def disable_schema_autoreload() -> bool: ...
 
 # Parse using a previously loaded schema
 # Returns a dict mapping field names to values (str or None)
-def parse_kv(line: str) -> Dict[str, Any]: ...
+# With extra_key (e.g. "_extra"), fields beyond the schema are kept as a list under that key
+# skip_empty drops fields whose value is "", skip_null drops fields missing from a short line
+def parse_kv(
+    line: str, extra_key: Optional[str] = None, skip_empty: bool = False, skip_null: bool = False
+) -> Dict[str, Any]: ...
+
+# Raised by parse_kv_strict; offset/field_index locate malformed lines (None on count mismatch)
+class ParseError(ValueError):
+    offset: Optional[int]
+    field_index: Optional[int]
+    message: str
+
+# Like parse_kv, but raises ParseError if the line's field count differs from the schema,
+# the line ends before the log type, or a quoted field is never closed
+def parse_kv_strict(line: str) -> Dict[str, Any]: ...
+
+# Like parse_kv with declared field types; datetime fields also get "<name>_epoch_ms"
+# (omitted when the timestamp does not match the field's format)
+def parse_kv_typed(line: str) -> Dict[str, Any]: ...
+
+# Parse a raw bytes line; strict_utf8=True raises ValueError (with byte offset) on invalid UTF-8
+def parse_kv_bytes(
+    line: bytes, strict_utf8: bool = False, skip_empty: bool = False, skip_null: bool = False
+) -> Dict[str, Any]: ...
 
 # Parse using a schema path provided for this call (does not persist)
-def parse_kv_with_schema(line: str, schema_path: str) -> Dict[str, Any]: ...
+def parse_kv_with_schema(
+    line: str,
+    schema_path: str,
+    extra_key: Optional[str] = None,
+    skip_empty: bool = False,
+    skip_null: bool = False,
+) -> Dict[str, Any]: ...
+
+# Parse against an inline {type_value: [field names]} schema; no global state changes
+def parse_line_with_schema_dict(line: str, schema_dict: Dict[str, List[str]]) -> Dict[str, Any]: ...
+
+# Like parse_kv, against the schema loaded under `name` with load_schema_named
+def parse_kv_named(
+    name: str,
+    line: str,
+    extra_key: Optional[str] = None,
+    skip_empty: bool = False,
+    skip_null: bool = False,
+) -> Dict[str, Any]: ...
+
+# Reusable parser for tight loops; snapshots the default (or `name`d) schema when created
+class Parser:
+    def __init__(self, name: Optional[str] = None) -> None: ...
+    def parse(self, line: str) -> Dict[str, Any]: ...
+
+# Parse straight into a new instance of `cls` (dataclass, slotted or plain class)
+# without building a dict; __init__ is not called. Declared attributes must match
+# the schema's sanitized field names or ValueError is raised.
+def parse_kv_into(line: str, cls: type[_T]) -> _T: ...
 
 # Introspection of the schema loader state
 # Example keys: {"loaded": bool, "path": Optional[str], "source": Optional[str], "mtime_epoch_ms": Optional[int]}
+# source is "file" or "string"; path and mtime_epoch_ms are None for string-loaded schemas
+# "schemas" maps every loaded schema name to {"path", "source", "types"}
 
 def get_schema_status() -> Dict[str, Any]: ...
 
+# Hot-patch a single log type of the loaded schema without a full reload.
+# Changes are in-memory only and are lost if the schema file is reloaded.
+def update_log_type(type_value: str, fields: List[str]) -> bool: ...
+def remove_log_type(type_value: str) -> bool: ...
+
+# Field names for one log type (None if unknown or no schema), and all type values
+def get_schema_fields(type_value: str) -> Optional[List[str]]: ...
+def list_schema_types() -> List[str]: ...
+
+# e.g. "Log type TRAFFIC: duplicate field name src_ip at index 1 renamed to src_ip_2"
+# or "Log type TRAFFIC: field_count is 72 but 71 fields are defined"
+def get_schema_warnings() -> List[str]: ...
+
 # CSV helpers
 
-def extract_field(line: str, index: int) -> Optional[str]: ...
+def extract_field(line: str, index: int, delimiter: str = ",") -> Optional[str]: ...
 
 def extract_type_subtype(line: str) -> Tuple[Optional[str], Optional[str]]: ...
 
-def split_csv(line: str) -> List[str]: ...
+# ("palo_alto" | "fortinet" | "cisco", confidence 0..1), or None if unrecognized
+def detect_source(line: str) -> Optional[Tuple[str, float]]: ...
+
+def split_csv(line: str, delimiter: str = ",") -> List[str]: ...
+
+# Inverse of split_csv: quotes fields that need it
+def join_csv(fields: List[str], delimiter: str = ",") -> str: ...
+
+# (value, was_quoted) per field
+def split_csv_with_flags(line: str) -> List[Tuple[str, bool]]: ...
+
+# (start, end, value) per field; byte offsets of the raw field text, quotes included
+def split_csv_spans(line: str) -> List[Tuple[int, int, str]]: ...
+
+# Same as len(split_csv(line)) without allocating the fields
+def count_fields(line: str) -> int: ...
+
+# Schema-less key=value line -> dict; quoted values may hold spaces, bare keys map to ""
+def parse_kv_pairs(line: str) -> Dict[str, str]: ...
 
 # Enriched parsing results
 # Returns a dict with keys like: {"parsed": Dict[str, Any], "raw_excerpt": str, "hash64": int, "runtime_ns": int}
+# raw_excerpt keeps the first excerpt_len bytes of the line (0 = full line)
+# include_type=True adds "type" and "subtype", as extract_type_subtype() returns them
+# record_id=True adds "record_id", a deterministic UUID string for the line and its type
+
+def parse_kv_enriched(
+    line: str, excerpt_len: int = 256, include_type: bool = False, record_id: bool = False
+) -> Dict[str, Any]: ...
+
+def parse_kv_enriched_with_schema(
+    line: str,
+    schema_path: str,
+    excerpt_len: int = 256,
+    include_type: bool = False,
+    record_id: bool = False,
+) -> Dict[str, Any]: ...
+
+# Parallel batch parsing; arena_chunk > 0 shares one field buffer per chunk
+# strict=False puts {"error": str, "line_index": int} in place of failed lines
+
+# parse_kv_enriched for a raw bytes line; hash64 covers the original bytes
+def parse_kv_enriched_bytes(line: bytes, strict_utf8: bool = False, excerpt_len: int = 256) -> Dict[str, Any]: ...
+
+# hash64 algorithm for enriched results, process-wide: "fnv1a" (default) or "xxhash64"
+def set_hash_algo(name: str) -> None: ...
+
+def get_hash_algo() -> str: ...
+
+# Dedicated thread pool for batch parsing; 0 uses Rayon's global pool
+def set_parse_threads(n: int) -> None: ...
 
-def parse_kv_enriched(line: str) -> Dict[str, Any]: ...
+def parse_kv_enriched_batch(
+    lines: List[str],
+    arena_chunk: int = 0,
+    excerpt_len: int = 256,
+    strict: bool = True,
+    sample_rate: float = 1.0,
+) -> List[Dict[str, Any]]: ...
 
-def parse_kv_enriched_with_schema(line: str, schema_path: str) -> Dict[str, Any]: ...
+# parse_kv_enriched_batch keeping the first record per hash64; returns (records, duplicates dropped)
+def parse_kv_enriched_batch_dedup(
+    lines: List[str], excerpt_len: int = 256
+) -> Tuple[List[Dict[str, Any]], int]: ...
+
+# One parse_kv dict per line; strict=False puts {"error", "line_index"} in bad lines' slots
+def parse_kv_batch(lines: List[str], strict: bool = True) -> List[Dict[str, Any]]: ...
+
+# Batch parsing into dictionary-encoded columns
+# Returns {"rows": int, "columns": {name: {"dictionary": List[str], "indices": List[Optional[int]]}}}
+
+def parse_batch_dict_encoded(lines: List[str]) -> Dict[str, Any]: ...
+
+# pyarrow.RecordBatch with one string column per field; only with the "arrow" feature
+def parse_to_arrow(lines: List[str]) -> Any: ...
+
+# pandas.DataFrame with one column per field, for one log type; only with the "pandas" feature
+def parse_to_dataframe(lines: List[str]) -> Any: ...
+
+# File parsing to NDJSON (enriched records, one per line); return the number written
+# provenance=True adds "line_no" and "byte_offset" to each record
+# ".gz" paths are gzip-compressed unless compressed= says otherwise
+# Returns {"records", "unknown", "long_lines"} counts; emit_unknown=True also writes
+# unknown-type lines as {"_unknown_type", "raw_excerpt", "hash64"}
+# Lines over max_line_bytes (default 16 MiB) are skipped and counted as long_lines
+
+def parse_file_to_ndjson(
+    input_path: str,
+    output_path: str,
+    provenance: bool = False,
+    compressed: Optional[bool] = None,
+    excerpt_len: int = 256,
+    emit_unknown: bool = False,
+    record_id: bool = False,
+    append: bool = False,
+    skip_lines: int = 0,
+    max_line_bytes: Optional[int] = None,
+) -> Dict[str, int]: ...
+
+# parse_file_to_ndjson from the process's stdin to its stdout; returns records written
+def parse_stdin_to_stdout() -> int: ...
+
+# Length-prefixed (4-byte big-endian) MessagePack frames shaped like the enriched JSON;
+# only with the "msgpack" feature
+def parse_file_to_msgpack(
+    input_path: str, output_path: str, compressed: Optional[bool] = None, excerpt_len: int = 256
+) -> int: ...
+
+# Lazily yields enriched dicts (string values), skipping lines like parse_file_to_ndjson
+class ParsedFileIter:
+    def __iter__(self) -> "ParsedFileIter": ...
+    def __next__(self) -> Dict[str, Any]: ...
+
+def parse_file_iter(
+    input_path: str, compressed: Optional[bool] = None, excerpt_len: int = 256
+) -> ParsedFileIter: ...
+
+def parse_file_filtered(
+    input_path: str,
+    output_path: str,
+    types: List[str],
+    provenance: bool = False,
+    excerpt_len: int = 256,
+    compressed: Optional[bool] = None,
+) -> int: ...
+
+# Output formats
+
+def to_logfmt(parsed: Dict[str, Any]) -> str: ...
+
+# Returns the number of records written
+def parse_file_to_logfmt(input_path: str, output_path: str) -> int: ...
+def parse_to_cef(
+    line: str,
+    vendor: str = "logparse_rs",
+    product: str = "logparse_rs",
+    version: Optional[str] = None,
+    signature_id: Optional[str] = None,
+    name: Optional[str] = None,
+    severity: str = "Unknown",
+) -> str: ...
+def parse_to_leef(
+    line: str,
+    vendor: str = "logparse_rs",
+    product: str = "logparse_rs",
+    version: Optional[str] = None,
+    event_id: Optional[str] = None,
+    delimiter: str = "\t",
+) -> str: ...
+
+# File statistics
+# Keys: file_bytes, sampled_lines, avg_line_bytes, estimated_lines, sampled_to_eof, distinct_types
+
+def estimate_file(input_path: str, sample_n: int = 1000) -> Dict[str, Any]: ...
+
+# Maps fields-per-line to number of lines over the whole file
+def field_count_histogram(input_path: str) -> Dict[int, int]: ...
 
 # Anonymizer APIs
 
-def load_anonymizer(config_path: str) -> bool: ...
+def load_anonymizer(config_path: str, jsonc: bool = False, strict: bool = False) -> bool: ...
 
-def set_anonymizer_json(config_json: str) -> bool: ...
+def set_anonymizer_json(config_json: str, jsonc: bool = False, strict: bool = False) -> bool: ...
+
+# Load new rules (e.g. a new salt and key_version) keeping the current integrity table
+def rotate_anonymizer(config_path: str, jsonc: bool = False, strict: bool = False) -> bool: ...
 
 # Example keys: {"enabled": bool, "fields": int, "pairs": int}
 
 def get_anonymizer_status() -> Dict[str, Any]: ...
 
+# Flat snapshot of schema, anonymizer, and parse counters for metrics exporters
+# Keys: schema_loaded, schema_types, anonymizer_enabled, anonymizer_fields,
+# anonymizer_pairs, anonymizer_table_bytes, parse_lines_ok, parse_lines_err,
+# parse_lines_redacted
+
+def metrics_snapshot() -> Dict[str, Any]: ...
+
 # Mapping of field -> {original_value -> replacement}
 
 def export_integrity_table() -> Dict[str, Dict[str, str]]: ...
 
+# Clear all integrity mappings (config is kept); returns the number of pairs cleared
+def reset_anonymizer() -> int: ...
+
+# Clear one field's integrity mappings; returns the number of pairs cleared
+def clear_anonymizer_field(field: str) -> int: ...
+
+# Original value behind `token` in `field`, or None if unknown or ambiguous (e.g. a fixed replacement)
+def deanonymize(field: str, token: str) -> Optional[str]: ...
+
+# Replacement `value` would get in `field`, without recording it; None if it would pass through
+def preview_anonymize(field: str, value: str) -> Optional[str]: ...
+
+# Anonymize an already parsed dict (e.g. from parse_kv); returns a new dict
+# include_fields/exclude_fields restrict which fields are anonymized (exclude wins)
+def anonymize_dict(
+    d: Dict[str, Any],
+    include_fields: Optional[List[str]] = None,
+    exclude_fields: Optional[List[str]] = None,
+) -> Dict[str, Any]: ...
+
 # Enriched parsing with anonymization; includes additional timing and flags
-# Example keys include: _anonymized, parse_ns, anonymize_ns, runtime_ns_total
+# Example keys include: _anonymized, _anonymized_fields, parse_ns, anonymize_ns, runtime_ns_total
+
+def parse_kv_enriched_anon(
+    line: str,
+    excerpt_len: int = 256,
+    include_fields: Optional[List[str]] = None,
+    exclude_fields: Optional[List[str]] = None,
+) -> Dict[str, Any]: ...
+
+def parse_kv_enriched_with_schema_anon(
+    line: str,
+    schema_path: str,
+    excerpt_len: int = 256,
+    include_fields: Optional[List[str]] = None,
+    exclude_fields: Optional[List[str]] = None,
+) -> Dict[str, Any]: ...
 
-def parse_kv_enriched_anon(line: str) -> Dict[str, Any]: ...
+# Batch parse_kv_enriched_anon; lines are parsed and anonymized in parallel
+def parse_kv_enriched_anon_batch(
+    lines: List[str],
+    excerpt_len: int = 256,
+    include_fields: Optional[List[str]] = None,
+    exclude_fields: Optional[List[str]] = None,
+) -> List[Dict[str, Any]]: ...
 
-def parse_kv_enriched_with_schema_anon(line: str, schema_path: str) -> Dict[str, Any]: ...
+# Anonymized parsed dicts plus only the (field, original, replacement) mappings learned in this call
+def anonymize_batch_with_deltas(
+    lines: List[str],
+    include_fields: Optional[List[str]] = None,
+    exclude_fields: Optional[List[str]] = None,
+) -> Tuple[List[Dict[str, Any]], List[Tuple[str, str, str]]]: ...
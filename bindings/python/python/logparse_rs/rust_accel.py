@@ -578,7 +578,7 @@ def parse_file_to_ndjson(input_path: str, output_path: str, *, schema_path: Opti
         load_schema(schema_path)
     if _rust_enabled() and _rs_parse_file_to_ndjson is not None:
         try:
-            return int(_rs_parse_file_to_ndjson(input_path, output_path))  # type: ignore
+            return int(_rs_parse_file_to_ndjson(input_path, output_path)["records"])  # type: ignore
         except Exception:
             # fall through to Python fallback
             pass
@@ -2,85 +2,667 @@
 use once_cell::sync::Lazy;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyModule};
-use std::sync::RwLock;
+use pyo3::types::{PyDict, PyModule, PyString, PyType};
+use pyo3::IntoPyObjectExt;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 use std::time::Instant;
 
 use logparse_core as core;
 
 // Re-export a local schema cache that uses the core types
-use core::{LoadedSchema, SCHEMA_CACHE};
+use core::{Layout, LoadedSchema, DEFAULT_SCHEMA_NAME, SCHEMA_CACHE};
 
 // Parallel iterators for batch parsing
 use rayon::prelude::*;
 
+// Raised by parse_kv_strict; `offset`, `field_index` and `message` say where
+// a malformed line went wrong (None for field count mismatches)
+pyo3::create_exception!(logparse_rs, ParseError, PyValueError);
+
+fn parse_error_to_py(py: Python, e: core::ParseError) -> PyErr {
+    let err = ParseError::new_err(e.to_string());
+    let (offset, field_index, message) = match e {
+        core::ParseError::Malformed { offset, field_index, message } => {
+            (Some(offset), Some(field_index), message)
+        }
+        core::ParseError::InvalidUtf8 { offset } => (Some(offset), None, e.to_string()),
+        other => (None, None, other.to_string()),
+    };
+    let value = err.value(py);
+    let set = value
+        .setattr("offset", offset)
+        .and_then(|_| value.setattr("field_index", field_index))
+        .and_then(|_| value.setattr("message", message));
+    set.err().unwrap_or(err)
+}
+
+// -------- Thread pool for batch parsing (set_parse_threads) --------
+// None runs batches on Rayon's global pool. Callers clone the Arc so a
+// concurrent set_parse_threads never waits for a batch in progress.
+static PARSE_POOL: Lazy<RwLock<Option<Arc<rayon::ThreadPool>>>> = Lazy::new(|| RwLock::new(None));
+
+/// Run `f`, and the Rayon work it starts, on the set_parse_threads pool if one
+/// is set, else on the global pool.
+fn in_parse_pool<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    let pool = PARSE_POOL.read().unwrap().clone();
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+// -------- Parse statistics (process-wide counters for metrics_snapshot) --------
+static PARSE_LINES_OK: AtomicU64 = AtomicU64::new(0);
+static PARSE_LINES_ERR: AtomicU64 = AtomicU64::new(0);
+/// Unknown-type lines returned redacted (`redact_unknown`), counted apart
+/// from both successes and errors
+static PARSE_LINES_REDACTED: AtomicU64 = AtomicU64::new(0);
+
+fn record_parse(ok: u64, err: u64) {
+    // Relaxed is enough: the counters are independent and only read for reporting
+    PARSE_LINES_OK.fetch_add(ok, Ordering::Relaxed);
+    PARSE_LINES_ERR.fetch_add(err, Ordering::Relaxed);
+}
+
+fn record_redacted(n: u64) {
+    PARSE_LINES_REDACTED.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Set `key` to `raw`, or None when missing. With a declared type the value
+/// becomes a Python int/float/bool (empty -> None); values that fail coercion
+/// keep their raw string.
+fn set_field(
+    d: &Bound<PyDict>,
+    key: Bound<PyString>,
+    raw: Option<&str>,
+    ty: Option<core::FieldType>,
+) -> PyResult<()> {
+    d.set_item(key, field_value(d.py(), raw, ty)?)
+}
+
+/// Python value of one field: `raw` coerced to its declared type, the string
+/// itself when untyped or not coercible, None when missing or empty-and-typed.
+fn field_value<'py>(
+    py: Python<'py>,
+    raw: Option<&str>,
+    ty: Option<core::FieldType>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let Some(raw) = raw else {
+        return Ok(py.None().into_bound(py));
+    };
+    match ty.and_then(|ty| core::coerce_value(raw, ty)) {
+        Some(core::TypedValue::Int(v)) => v.into_bound_py_any(py),
+        Some(core::TypedValue::Float(v)) => v.into_bound_py_any(py),
+        Some(core::TypedValue::Bool(v)) => v.into_bound_py_any(py),
+        Some(core::TypedValue::Null) => Ok(py.None().into_bound(py)),
+        Some(core::TypedValue::Str(_)) | None => raw.into_bound_py_any(py),
+    }
+}
+
+/// Declared type of field `i`, if the layout has types.
+fn field_type(types: Option<&[core::FieldType]>, i: usize) -> Option<core::FieldType> {
+    types.map(|ts| ts.get(i).copied().unwrap_or_default())
+}
+
+/// `extra_key`: when set, values beyond the schema's field names are stored as a
+/// list under this key instead of being dropped. `skip` leaves out empty and/or
+/// missing fields.
 fn parse_line_to_dict<'py>(
     py: Python<'py>,
     line: &str,
     schema: &LoadedSchema,
+    extra_key: Option<&str>,
+    skip: core::SkipFields,
 ) -> PyResult<Bound<'py, PyDict>> {
     // Fast path: avoid building an intermediate HashMap. Instead, split the CSV
     // once and populate the Python dict directly using the schema's field names.
     // This eliminates per-line hashing and key String cloning.
-    let t = core::extract_field_internal(line, 3)
-        .ok_or_else(|| PyValueError::new_err("Could not extract log type at index 3"))?;
-    let names = schema
-        .type_to_fields
-        .get(&t)
-        .ok_or_else(|| PyValueError::new_err(format!("Unknown log type in schema: {}", t)))?;
-
-    let fields = core::split_csv_internal(line);
+    let (fields, (names, types)) = core::split_with_layout(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+
+    let defaults = record_defaults(schema, &fields);
+    let d = fields_to_pydict(py, &fields, (names, types), defaults, skip)?;
+    if let Some(k) = extra_key {
+        d.set_item(k, fields.get(names.len()..).unwrap_or_default())?;
+    }
+    Ok(d)
+}
+
+/// Schema defaults for the missing trailing fields of a split record.
+fn record_defaults<'s>(schema: &'s LoadedSchema, fields: &[String]) -> core::FieldDefaults<'s> {
+    let (t, _) = core::type_and_subtype(fields, schema);
+    schema.field_defaults(schema.resolve_type(t?), fields)
+}
+
+/// Dict of split `fields` keyed by the layout's names, in order, without the
+/// fields `skip` selects. Fields past the end of the line take their schema
+/// default, if `defaults` has one.
+fn fields_to_pydict<'py>(
+    py: Python<'py>,
+    fields: &[String],
+    (names, types): Layout,
+    defaults: core::FieldDefaults,
+    skip: core::SkipFields,
+) -> PyResult<Bound<'py, PyDict>> {
     let d = PyDict::new(py);
     for (i, name) in names.iter().enumerate() {
-        let key = pyo3::types::PyString::intern(py, name);
-        if i < fields.len() {
-            d.set_item(key, &fields[i])?;
-        } else {
-            d.set_item(key, py.None())?;
+        let default = || defaults.and_then(|d| d.get(i)?.as_deref());
+        let raw = fields.get(i).map(|s| s.as_str()).or_else(default);
+        if !skip.keeps(raw) {
+            continue;
         }
+        set_field(&d, PyString::intern(py, name), raw, field_type(types, i))?;
     }
     Ok(d)
 }
 
+/// Python form of an `EnrichedRecord`: {"parsed", "raw_excerpt", "hash64",
+/// "runtime_ns"}. `parsed` keys follow the layout's (schema) order, with values
+/// typed as in `parse_line_to_dict`.
+fn enriched_to_pydict(
+    py: Python,
+    rec: &core::EnrichedRecord,
+    (names, types): Layout,
+) -> PyResult<Py<PyDict>> {
+    let parsed = PyDict::new(py);
+    for (i, name) in names.iter().enumerate() {
+        let raw = rec.parsed.get(name).and_then(|v| v.as_deref());
+        set_field(&parsed, PyString::intern(py, name), raw, field_type(types, i))?;
+    }
+    let d = PyDict::new(py);
+    d.set_item("parsed", parsed)?;
+    d.set_item("raw_excerpt", &rec.raw_excerpt)?;
+    d.set_item("hash64", rec.hash64 as u128)?;
+    d.set_item("runtime_ns", rec.runtime_ns)?;
+    Ok(d.unbind())
+}
+
+/// Parse `line` into an `EnrichedRecord` along with its layout. With
+/// `excerpt_len` None the record's `raw_excerpt` is left empty, for callers
+/// that decide on the excerpt themselves.
+fn parse_enriched<'s>(
+    schema: &'s LoadedSchema,
+    line: &str,
+    excerpt_len: Option<usize>,
+) -> Result<(core::EnrichedRecord, Layout<'s>), String> {
+    let t0 = Instant::now();
+    let (fields, layout) = core::split_with_layout(line, schema)?;
+    let defaults = record_defaults(schema, &fields);
+    Ok((enriched_from_fields(line, fields, (layout, defaults), t0, excerpt_len), layout))
+}
+
+/// `EnrichedRecord` for the split `fields` of `line`, timed from `t0`; fields
+/// past the end of the line take their schema default. `excerpt_len` as for
+/// `parse_enriched`.
+fn enriched_from_fields(
+    line: &str,
+    fields: Vec<String>,
+    layout: (Layout, core::FieldDefaults),
+    t0: Instant,
+    excerpt_len: Option<usize>,
+) -> core::EnrichedRecord {
+    let parsed = field_map(fields, layout);
+    let runtime_ns = t0.elapsed().as_nanos();
+    match excerpt_len {
+        Some(n) => core::EnrichedRecord::new(line, parsed, runtime_ns, n),
+        None => core::EnrichedRecord {
+            parsed,
+            raw_excerpt: String::new(),
+            hash64: core::hash64(line.as_bytes()),
+            runtime_ns,
+        },
+    }
+}
+
+/// The split `fields` of a record keyed by its layout's names; names past the
+/// end of the line take their schema default.
+fn field_map(
+    fields: Vec<String>,
+    ((names, _), defaults): (Layout, core::FieldDefaults),
+) -> core::FieldMap {
+    let mut values = fields.into_iter();
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let default = || defaults.and_then(|d| d.get(i)?.clone());
+            (name.clone(), values.next().or_else(default))
+        })
+        .collect()
+}
+
 /// Load a schema from a JSON file path. Returns True on success.
-/// Raises ValueError if the file cannot be read or parsed.
+/// Raises ValueError if the file cannot be read or parsed. With `jsonc=True`
+/// (implied for .jsonc/.json5 files) comments and trailing commas are allowed.
 #[pyfunction]
-#[pyo3(text_signature = "(schema_path)")]
-fn load_schema(schema_path: &str) -> PyResult<bool> {
-    match core::load_schema_internal(schema_path) {
+#[pyo3(signature = (schema_path, jsonc = false), text_signature = "(schema_path, jsonc=False)")]
+fn load_schema(schema_path: &str, jsonc: bool) -> PyResult<bool> {
+    let loaded = if jsonc {
+        core::load_schema_jsonc(schema_path)
+    } else {
+        core::load_schema_internal(schema_path)
+    };
+    match loaded {
         Ok(loaded) => {
             let mut guard = SCHEMA_CACHE.write().unwrap();
-            *guard = Some(loaded);
+            guard.insert(DEFAULT_SCHEMA_NAME.to_string(), loaded);
             Ok(true)
         }
         Err(e) => Err(PyValueError::new_err(e)),
     }
 }
 
+/// Load a schema from a JSON string, e.g. generated at runtime or fetched from
+/// a config service. Returns True on success; raises ValueError if it cannot be
+/// parsed. With `jsonc=True` comments and trailing commas are allowed.
+#[pyfunction]
+#[pyo3(signature = (config_json, jsonc = false), text_signature = "(config_json, jsonc=False)")]
+fn load_schema_json(config_json: &str, jsonc: bool) -> PyResult<bool> {
+    let loaded = if jsonc {
+        core::load_schema_from_str(&core::strip_jsonc(config_json))
+    } else {
+        core::load_schema_from_str(config_json)
+    };
+    let loaded = loaded.map_err(PyValueError::new_err)?;
+    let mut guard = SCHEMA_CACHE.write().unwrap();
+    guard.insert(DEFAULT_SCHEMA_NAME.to_string(), loaded);
+    Ok(true)
+}
+
+/// Load a schema from a JSON file under `name`, alongside any other named
+/// schemas, for parse_kv_named(). The single-schema API (load_schema,
+/// parse_kv, ...) uses the name "default". Returns True on success; `jsonc` as
+/// for load_schema().
+#[pyfunction]
+#[pyo3(
+    signature = (name, schema_path, jsonc = false),
+    text_signature = "(name, schema_path, jsonc=False)"
+)]
+fn load_schema_named(name: &str, schema_path: &str, jsonc: bool) -> PyResult<bool> {
+    let loaded = if jsonc {
+        core::load_schema_jsonc(schema_path)
+    } else {
+        core::load_schema_internal(schema_path)
+    };
+    let loaded = loaded.map_err(PyValueError::new_err)?;
+    SCHEMA_CACHE.write().unwrap().insert(name.to_string(), loaded);
+    Ok(true)
+}
+
+/// Load the schema at `schema_path` and reload it in the background whenever
+/// the file's mtime changes, polling every `interval_ms`. Parses keep using the
+/// previous schema until the new one is fully loaded; a file that fails to
+/// parse is ignored until it changes again. Raises ValueError if the initial
+/// load fails.
+#[pyfunction]
+#[pyo3(
+    signature = (schema_path, interval_ms = 1000),
+    text_signature = "(schema_path, interval_ms=1000)"
+)]
+fn enable_schema_autoreload(schema_path: &str, interval_ms: u64) -> PyResult<bool> {
+    core::start_watcher(schema_path, std::time::Duration::from_millis(interval_ms))
+        .map_err(PyValueError::new_err)?;
+    Ok(true)
+}
+
+/// Stop the schema autoreload thread; the loaded schema stays in place.
+/// Returns False if autoreload was not enabled.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn disable_schema_autoreload() -> bool {
+    core::stop_watcher()
+}
+
 /// Parse a single CSV/KV log line using the previously loaded schema.
-/// Returns a dict mapping field names to values.
+/// Returns a dict mapping field names to values. With `extra_key`, fields beyond
+/// the schema are kept as a list under that key (e.g. "_extra"). `skip_empty`
+/// leaves out fields whose value is an empty string, `skip_null` the fields
+/// missing from a short line (otherwise None).
 #[pyfunction]
-#[pyo3(text_signature = "(line)")]
-fn parse_kv(py: Python, line: &str) -> PyResult<Py<PyDict>> {
+#[pyo3(
+    signature = (line, extra_key = None, skip_empty = false, skip_null = false),
+    text_signature = "(line, extra_key=None, skip_empty=False, skip_null=False)"
+)]
+fn parse_kv(
+    py: Python,
+    line: &str,
+    extra_key: Option<&str>,
+    skip_empty: bool,
+    skip_null: bool,
+) -> PyResult<Py<PyDict>> {
     let guard = SCHEMA_CACHE.read().unwrap();
-    let schema = guard.as_ref().ok_or_else(|| {
+    let schema = guard.get(DEFAULT_SCHEMA_NAME).ok_or_else(|| {
         PyValueError::new_err("No schema loaded. Call load_schema() or use parse_kv_with_schema().")
     })?;
-    let dict = parse_line_to_dict(py, line, schema)?;
+    let skip = core::SkipFields { empty: skip_empty, null: skip_null };
+    let dict = parse_line_to_dict(py, line, schema, extra_key, skip)?;
+    Ok(dict.unbind())
+}
+
+/// Like parse_kv, but raises ParseError (a ValueError) when the line has fewer
+/// or more fields than the schema declares for its log type (message includes
+/// the type and the expected and actual counts), ends before the log type, or
+/// leaves a quoted field open. The last two set the exception's `offset` and
+/// `field_index`.
+#[pyfunction]
+#[pyo3(text_signature = "(line)")]
+fn parse_kv_strict(py: Python, line: &str) -> PyResult<Py<PyDict>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let mut map = core::parse_line_to_map_strict(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        parse_error_to_py(py, e)
+    })?;
+    record_parse(1, 0);
+    // Emit keys in schema order, like parse_kv
+    let t = schema.log_type(line).unwrap_or_default();
+    let d = PyDict::new(py);
+    for name in core::field_names_for_line(line, schema, &t).into_iter().flatten() {
+        let key = PyString::intern(py, name);
+        d.set_item(key, map.remove(name).flatten())?;
+    }
+    Ok(d.unbind())
+}
+
+/// Parse a line with the loaded schema's declared field types. `datetime`
+/// fields keep their raw string and add `<name>_epoch_ms` right after it; the
+/// epoch key is omitted when the timestamp does not match the field's format.
+#[pyfunction]
+#[pyo3(text_signature = "(line)")]
+fn parse_kv_typed(py: Python, line: &str) -> PyResult<Py<PyDict>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let mut rec = core::parse_line_typed(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+    let t = schema.log_type(line).unwrap_or_default();
+    let d = PyDict::new(py);
+    for name in core::field_names_for_line(line, schema, &t).into_iter().flatten() {
+        let epoch_key = format!("{}_epoch_ms", name);
+        let values =
+            [(name.as_str(), rec.values.remove(name)), (&epoch_key, rec.values.remove(&epoch_key))];
+        for (key, value) in values {
+            let key = PyString::intern(py, key);
+            match value {
+                Some(core::TypedValue::Str(v)) => d.set_item(key, v)?,
+                Some(core::TypedValue::Int(v)) => d.set_item(key, v)?,
+                Some(core::TypedValue::Float(v)) => d.set_item(key, v)?,
+                Some(core::TypedValue::Bool(v)) => d.set_item(key, v)?,
+                Some(core::TypedValue::Null) => d.set_item(key, py.None())?,
+                None => {}
+            }
+        }
+    }
+    Ok(d.unbind())
+}
+
+/// Parse a raw `bytes` line with the loaded schema. With `strict_utf8=True`,
+/// invalid UTF-8 raises ValueError with the byte offset instead of being
+/// replaced with U+FFFD, so the line can be quarantined unaltered.
+/// `skip_empty` and `skip_null` as for parse_kv.
+#[pyfunction]
+#[pyo3(
+    signature = (line, strict_utf8 = false, skip_empty = false, skip_null = false),
+    text_signature = "(line, strict_utf8=False, skip_empty=False, skip_null=False)"
+)]
+fn parse_kv_bytes(
+    py: Python,
+    line: &[u8],
+    strict_utf8: bool,
+    skip_empty: bool,
+    skip_null: bool,
+) -> PyResult<Py<PyDict>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let text = core::decode_line(line, strict_utf8).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e.to_string())
+    })?;
+    let skip = core::SkipFields { empty: skip_empty, null: skip_null };
+    let dict = parse_line_to_dict(py, &text, schema, None, skip)?;
     Ok(dict.unbind())
 }
 
 /// Parse a single log line using the schema at the given path (temporary load).
 #[pyfunction]
-#[pyo3(text_signature = "(line, schema_path)")]
-fn parse_kv_with_schema(py: Python, line: &str, schema_path: &str) -> PyResult<Py<PyDict>> {
+#[pyo3(
+    signature = (line, schema_path, extra_key = None, skip_empty = false, skip_null = false),
+    text_signature = "(line, schema_path, extra_key=None, skip_empty=False, skip_null=False)"
+)]
+fn parse_kv_with_schema(
+    py: Python,
+    line: &str,
+    schema_path: &str,
+    extra_key: Option<&str>,
+    skip_empty: bool,
+    skip_null: bool,
+) -> PyResult<Py<PyDict>> {
     core::ensure_schema_loaded(schema_path).map_err(PyValueError::new_err)?;
     let guard = SCHEMA_CACHE.read().unwrap();
-    let schema = guard.as_ref().unwrap();
-    let dict = parse_line_to_dict(py, line, schema)?;
+    let schema = guard.get(DEFAULT_SCHEMA_NAME).unwrap();
+    let skip = core::SkipFields { empty: skip_empty, null: skip_null };
+    let dict = parse_line_to_dict(py, line, schema, extra_key, skip)?;
     Ok(dict.unbind())
 }
 
+/// Parse a single log line against a schema given as a dict of
+/// {type_value: [field names]}. The schema is built for this call only;
+/// neither the loaded schema nor the named schemas are touched, so tests
+/// using it do not depend on each other or on load order.
+#[pyfunction]
+#[pyo3(text_signature = "(line, schema_dict)")]
+fn parse_line_with_schema_dict(
+    py: Python,
+    line: &str,
+    schema_dict: std::collections::HashMap<String, Vec<String>>,
+) -> PyResult<Py<PyDict>> {
+    let mut schema = LoadedSchema::default();
+    for (type_value, fields) in &schema_dict {
+        schema.update_log_type(type_value, fields).map_err(PyValueError::new_err)?;
+    }
+    let dict = parse_line_to_dict(py, line, &schema, None, core::SkipFields::default())?;
+    Ok(dict.unbind())
+}
+
+/// Like parse_kv, against the schema loaded under `name` by load_schema_named().
+#[pyfunction]
+#[pyo3(
+    signature = (name, line, extra_key = None, skip_empty = false, skip_null = false),
+    text_signature = "(name, line, extra_key=None, skip_empty=False, skip_null=False)"
+)]
+fn parse_kv_named(
+    py: Python,
+    name: &str,
+    line: &str,
+    extra_key: Option<&str>,
+    skip_empty: bool,
+    skip_null: bool,
+) -> PyResult<Py<PyDict>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard.get(name).ok_or_else(|| {
+        PyValueError::new_err(format!(
+            "No schema named {:?} loaded. Call load_schema_named() first.",
+            name
+        ))
+    })?;
+    let skip = core::SkipFields { empty: skip_empty, null: skip_null };
+    let dict = parse_line_to_dict(py, line, schema, extra_key, skip)?;
+    Ok(dict.unbind())
+}
+
+/// Reusable parser for tight single-threaded loops: keeps its field buffer
+/// between lines instead of allocating per call. Holds a snapshot of the schema
+/// taken when it was created, like parse_file_iter; create a new one after
+/// reloading. Values are typed as for parse_kv.
+#[pyclass(module = "logparse_rs")]
+struct Parser {
+    inner: core::LineParser<LoadedSchema>,
+}
+
+#[pymethods]
+impl Parser {
+    /// Parser for the schema loaded under `name` (load_schema_named), or the
+    /// default schema.
+    #[new]
+    #[pyo3(signature = (name = None), text_signature = "(name=None)")]
+    fn new(name: Option<&str>) -> PyResult<Self> {
+        let schema = SCHEMA_CACHE
+            .read()
+            .unwrap()
+            .get(name.unwrap_or(DEFAULT_SCHEMA_NAME))
+            .cloned()
+            .ok_or_else(|| match name {
+                Some(name) => PyValueError::new_err(format!(
+                    "No schema named {:?} loaded. Call load_schema_named() first.",
+                    name
+                )),
+                None => PyValueError::new_err("No schema loaded. Call load_schema() first."),
+            })?;
+        Ok(Parser { inner: core::LineParser::new(schema) })
+    }
+
+    /// Parse one line into a dict, as parse_kv.
+    #[pyo3(text_signature = "($self, line)")]
+    fn parse(&mut self, py: Python, line: &str) -> PyResult<Py<PyDict>> {
+        let (fields, layout, defaults) = self.inner.split_with_defaults(line).map_err(|e| {
+            record_parse(0, 1);
+            PyValueError::new_err(e)
+        })?;
+        record_parse(1, 0);
+        Ok(fields_to_pydict(py, fields, layout, defaults, core::SkipFields::default())?.unbind())
+    }
+}
+
+/// Attribute names a class declares via `__dataclass_fields__` or `__slots__`,
+/// or `None` for a plain class that accepts arbitrary attributes.
+fn declared_attrs(cls: &Bound<PyType>) -> PyResult<Option<Vec<String>>> {
+    if let Ok(fields) = cls.getattr("__dataclass_fields__") {
+        let fields = fields.downcast_into::<PyDict>()?;
+        return fields.keys().iter().map(|k| k.extract()).collect::<PyResult<_>>().map(Some);
+    }
+    match cls.getattr("__slots__") {
+        Ok(slots) if slots.is_instance_of::<PyString>() => Ok(Some(vec![slots.extract()?])),
+        Ok(slots) => slots.try_iter()?.map(|s| s?.extract()).collect::<PyResult<_>>().map(Some),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parse a line with the loaded schema straight into a new instance of `cls`,
+/// skipping the intermediate dict. The instance is created with `cls.__new__`
+/// (so `__init__` does not run) and each schema field is set as an attribute.
+/// For dataclasses and slotted classes the declared attribute names must match
+/// the schema's sanitized field names exactly; mismatches raise ValueError.
+#[pyfunction]
+#[pyo3(text_signature = "(line, cls)")]
+fn parse_kv_into<'py>(
+    py: Python<'py>,
+    line: &str,
+    cls: &Bound<'py, PyType>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let t = schema.log_type(line).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    let Some(names) = schema.type_to_fields.get(&t) else {
+        record_parse(0, 1);
+        return Err(PyValueError::new_err(format!("Unknown log type in schema: {}", t)));
+    };
+    let fields = core::split_fields_for_type(line, schema, &t);
+    let names = schema.fields_for_record(&t, &fields).unwrap_or(names);
+    schema.check_overflow(&t, fields.len(), names.len()).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+
+    if let Some(declared) = declared_attrs(cls)? {
+        let missing: Vec<&str> =
+            names.iter().filter(|n| !declared.contains(n)).map(|n| n.as_str()).collect();
+        let extra: Vec<&str> =
+            declared.iter().filter(|d| !names.contains(d)).map(|d| d.as_str()).collect();
+        if !missing.is_empty() || !extra.is_empty() {
+            record_parse(0, 1);
+            return Err(PyValueError::new_err(format!(
+                "{} does not match schema fields for log type {}: missing attributes {:?}, unknown attributes {:?}",
+                cls.name()?,
+                t,
+                missing,
+                extra
+            )));
+        }
+    }
+    record_parse(1, 0);
+
+    let defaults = schema.field_defaults(&t, &fields);
+    let obj = cls.call_method1("__new__", (cls,))?;
+    for (i, name) in names.iter().enumerate() {
+        let key = PyString::intern(py, name);
+        match fields.get(i) {
+            Some(v) => obj.setattr(key, v)?,
+            None => obj.setattr(key, defaults.and_then(|d| d.get(i)?.as_deref()))?,
+        }
+    }
+    Ok(obj)
+}
+
+/// Replace the field list of one log type in the loaded schema, in place,
+/// without reloading the file. Field names are sanitized as on load.
+/// Returns True if the type existed, False if it was added.
+#[pyfunction]
+#[pyo3(text_signature = "(type_value, fields)")]
+fn update_log_type(type_value: &str, fields: Vec<String>) -> PyResult<bool> {
+    core::update_cached_log_type(type_value, &fields).map_err(PyValueError::new_err)
+}
+
+/// Remove one log type from the loaded schema. Returns True if it existed.
+#[pyfunction]
+#[pyo3(text_signature = "(type_value)")]
+fn remove_log_type(type_value: &str) -> PyResult<bool> {
+    core::remove_cached_log_type(type_value).map_err(PyValueError::new_err)
+}
+
+/// Field names the loaded schema produces for `type_value`, in parse order.
+/// Returns None if the type is unknown or no schema is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "(type_value)")]
+fn get_schema_fields(type_value: &str) -> Option<Vec<String>> {
+    core::cached_schema_fields(type_value)
+}
+
+/// Problems found when the loaded schema was built, e.g. duplicate field
+/// names (after sanitization) renamed with a numeric suffix, or a declared
+/// `field_count` that differs from the fields listed. Empty if none or if no
+/// schema is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn get_schema_warnings() -> Vec<String> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    guard.get(DEFAULT_SCHEMA_NAME).map_or_else(Vec::new, |s| s.warnings.clone())
+}
+
+/// Sorted list of every `type_value` in the loaded schema; empty if none is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn list_schema_types() -> Vec<String> {
+    core::cached_schema_types()
+}
+
 /// Return current schema loader status and metadata.
 #[pyfunction]
 #[pyo3(text_signature = "()")]
@@ -88,7 +670,13 @@ fn get_schema_status(py: Python) -> PyResult<Py<PyDict>> {
     use std::time::SystemTime;
     let guard = SCHEMA_CACHE.read().unwrap();
     let d = PyDict::new(py);
-    match guard.as_ref() {
+    match guard.get(DEFAULT_SCHEMA_NAME) {
+        Some(ls) if ls.path.is_empty() => {
+            d.set_item("loaded", true)?;
+            d.set_item("path", py.None())?;
+            d.set_item("source", "string")?;
+            d.set_item("mtime_epoch_ms", py.None())?;
+        }
         Some(ls) => {
             d.set_item("loaded", true)?;
             d.set_item("path", ls.path.clone())?;
@@ -114,187 +702,782 @@ fn get_schema_status(py: Python) -> PyResult<Py<PyDict>> {
             d.set_item("mtime_epoch_ms", py.None())?;
         }
     }
+    let warnings = guard.get(DEFAULT_SCHEMA_NAME).map_or(&[][..], |ls| &ls.warnings[..]);
+    d.set_item("warnings", warnings)?;
+    let schemas = PyDict::new(py);
+    for (name, ls) in guard.iter() {
+        let s = PyDict::new(py);
+        if ls.path.is_empty() {
+            s.set_item("path", py.None())?;
+            s.set_item("source", "string")?;
+        } else {
+            s.set_item("path", &ls.path)?;
+            s.set_item("source", "file")?;
+        }
+        s.set_item("types", ls.type_to_fields.len())?;
+        schemas.set_item(name, s)?;
+    }
+    d.set_item("schemas", schemas)?;
     Ok(d.unbind())
 }
 
+fn delimiter_byte(delimiter: &str) -> PyResult<u8> {
+    match delimiter.as_bytes() {
+        [b] if b.is_ascii() && *b != b'"' => Ok(*b),
+        _ => Err(PyValueError::new_err(format!(
+            "delimiter must be a single ASCII character other than '\"', got {:?}",
+            delimiter
+        ))),
+    }
+}
+
 /// Extract the raw CSV field at the given 0-based index. Returns the field string or None if out of bounds.
 #[pyfunction]
-#[pyo3(text_signature = "(line, index)")]
-fn extract_field(line: &str, index: usize) -> PyResult<Option<String>> {
-    Ok(core::extract_field_internal(line, index))
+#[pyo3(signature = (line, index, delimiter = ","), text_signature = "(line, index, delimiter=',')")]
+fn extract_field(line: &str, index: usize, delimiter: &str) -> PyResult<Option<String>> {
+    Ok(core::extract_field_with_delim(line, index, delimiter_byte(delimiter)?))
+}
+
+/// Type index of the loaded schema, or the PAN-OS default when none is loaded.
+fn current_type_index() -> usize {
+    SCHEMA_CACHE
+        .read()
+        .unwrap()
+        .get(DEFAULT_SCHEMA_NAME)
+        .map_or(core::DEFAULT_TYPE_INDEX, |s| s.type_index)
 }
 
-/// Extract the event type and subtype fields (indexes 3 and 4) from the CSV line.
+/// Extract the event type and subtype fields from the CSV line: the loaded
+/// schema's type index (3 by default) and the field right after it.
 #[pyfunction]
 #[pyo3(text_signature = "(line)")]
 fn extract_type_subtype(line: &str) -> PyResult<(Option<String>, Option<String>)> {
-    let t = core::extract_field_internal(line, 3);
-    let st = core::extract_field_internal(line, 4);
+    let idx = current_type_index();
+    let t = core::extract_field_internal(line, idx);
+    let st = core::extract_field_internal(line, idx + 1);
     Ok((t, st))
 }
 
-/// Split a CSV line (quote-aware) into a list of fields.
+/// Guess the vendor of a log line from its shape, e.g. to pick a schema:
+/// ("palo_alto" | "fortinet" | "cisco", confidence from 0 to 1), or None when
+/// no vendor's markers are found.
 #[pyfunction]
 #[pyo3(text_signature = "(line)")]
-fn split_csv(line: &str) -> PyResult<Vec<String>> {
-    Ok(core::split_csv_internal(line))
+fn detect_source(line: &str) -> Option<(&'static str, f64)> {
+    core::detect(line).map(|d| (d.vendor, d.confidence))
 }
 
-/// Parse a line and return an enriched result with parsed fields, raw excerpt, hash64, and runtime.
+/// Split a CSV line (quote-aware) into a list of fields. `delimiter` selects the
+/// separator, e.g. "|" or "\t".
+#[pyfunction]
+#[pyo3(signature = (line, delimiter = ","), text_signature = "(line, delimiter=',')")]
+fn split_csv(line: &str, delimiter: &str) -> PyResult<Vec<String>> {
+    Ok(core::split_csv_with_delim(line, delimiter_byte(delimiter)?))
+}
+
+/// Join fields into one CSV line, quoting fields that contain the delimiter, a
+/// quote or a line break; split_csv() reads it back into the same list.
+#[pyfunction]
+#[pyo3(signature = (fields, delimiter = ","), text_signature = "(fields, delimiter=',')")]
+fn join_csv(fields: Vec<String>, delimiter: &str) -> PyResult<String> {
+    Ok(core::join_csv_with_delim(&fields, delimiter_byte(delimiter)?))
+}
+
+/// Split a CSV line into (value, was_quoted) pairs, preserving source quoting.
+#[pyfunction]
+#[pyo3(text_signature = "(line)")]
+fn split_csv_with_flags(line: &str) -> PyResult<Vec<(String, bool)>> {
+    Ok(core::split_csv_with_flags(line))
+}
+
+/// Split a CSV line into (start, end, value) tuples, where start and end are
+/// the byte offsets of the field's raw text (quotes included) in the UTF-8
+/// encoded line, for highlighting fields in the original text.
+#[pyfunction]
+#[pyo3(text_signature = "(line)")]
+fn split_csv_spans(line: &str) -> PyResult<Vec<(usize, usize, String)>> {
+    Ok(core::split_csv_spans(line))
+}
+
+/// Number of fields split_csv(line) would return, counted without building them.
 #[pyfunction]
 #[pyo3(text_signature = "(line)")]
-fn parse_kv_enriched(py: Python, line: &str) -> PyResult<Py<PyDict>> {
+fn count_fields(line: &str) -> usize {
+    core::count_fields(line)
+}
+
+/// Parse a `key1=val1 key2="val 2"` line into a dict of strings; no schema is
+/// needed. Bare keys without `=` map to "".
+#[pyfunction]
+#[pyo3(text_signature = "(line)")]
+fn parse_kv_pairs(line: &str) -> std::collections::HashMap<String, String> {
+    core::parse_kv_pairs(line)
+}
+
+/// parse_kv_enriched() result for `line`; `include_type` adds "type" and
+/// "subtype" from the same split, `record_id` the line's core::record_id.
+fn enriched_line_dict(
+    py: Python,
+    schema: &LoadedSchema,
+    line: &str,
+    excerpt_len: usize,
+    include_type: bool,
+    record_id: bool,
+) -> PyResult<Py<PyDict>> {
+    let t0 = Instant::now();
+    let (fields, layout) = core::split_with_layout(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+    let discriminators = include_type.then(|| {
+        let (t, st) = core::type_and_subtype(&fields, schema);
+        (t.map(String::from), st.map(String::from))
+    });
+    let id = record_id.then(|| {
+        let t = core::type_and_subtype(&fields, schema).0.unwrap_or_default();
+        core::record_id(line.as_bytes(), t)
+    });
+    let defaults = record_defaults(schema, &fields);
+    let rec = enriched_from_fields(line, fields, (layout, defaults), t0, Some(excerpt_len));
+    let d = enriched_to_pydict(py, &rec, layout)?;
+    if let Some((t, st)) = discriminators {
+        let d = d.bind(py);
+        d.set_item("type", t)?;
+        d.set_item("subtype", st)?;
+    }
+    if let Some(id) = id {
+        d.bind(py).set_item("record_id", id)?;
+    }
+    Ok(d)
+}
+
+/// Parse a line and return an enriched result with parsed fields, raw excerpt, hash64, and runtime.
+/// `raw_excerpt` holds the first `excerpt_len` bytes of the line (never splitting
+/// a character); 0 keeps the full line. With `include_type=True` the result
+/// also has "type" and "subtype", as extract_type_subtype() would return them.
+/// With `record_id=True` it has "record_id", a UUID string derived from the
+/// line and its log type that is the same every time the line is parsed.
+#[pyfunction]
+#[pyo3(
+    signature = (line, excerpt_len = core::DEFAULT_EXCERPT_LEN, include_type = false, record_id = false),
+    text_signature = "(line, excerpt_len=256, include_type=False, record_id=False)"
+)]
+fn parse_kv_enriched(
+    py: Python,
+    line: &str,
+    excerpt_len: usize,
+    include_type: bool,
+    record_id: bool,
+) -> PyResult<Py<PyDict>> {
     let guard = SCHEMA_CACHE.read().unwrap();
-    let schema = guard.as_ref().ok_or_else(|| {
+    let schema = guard.get(DEFAULT_SCHEMA_NAME).ok_or_else(|| {
         PyValueError::new_err(
             "No schema loaded. Call load_schema() or use parse_kv_enriched_with_schema().",
         )
     })?;
+    enriched_line_dict(py, schema, line, excerpt_len, include_type, record_id)
+}
+
+/// Parse using the schema at the given path and return an enriched result.
+#[pyfunction]
+#[pyo3(
+    signature = (
+        line,
+        schema_path,
+        excerpt_len = core::DEFAULT_EXCERPT_LEN,
+        include_type = false,
+        record_id = false
+    ),
+    text_signature = "(line, schema_path, excerpt_len=256, include_type=False, record_id=False)"
+)]
+fn parse_kv_enriched_with_schema(
+    py: Python,
+    line: &str,
+    schema_path: &str,
+    excerpt_len: usize,
+    include_type: bool,
+    record_id: bool,
+) -> PyResult<Py<PyDict>> {
+    core::ensure_schema_loaded(schema_path).map_err(PyValueError::new_err)?;
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard.get(DEFAULT_SCHEMA_NAME).unwrap();
+    enriched_line_dict(py, schema, line, excerpt_len, include_type, record_id)
+}
+
+/// parse_kv_enriched for a raw `bytes` line; `strict_utf8` as for
+/// parse_kv_bytes. `hash64` is computed over the original bytes.
+#[pyfunction]
+#[pyo3(
+    signature = (line, strict_utf8 = false, excerpt_len = core::DEFAULT_EXCERPT_LEN),
+    text_signature = "(line, strict_utf8=False, excerpt_len=256)"
+)]
+fn parse_kv_enriched_bytes(
+    py: Python,
+    line: &[u8],
+    strict_utf8: bool,
+    excerpt_len: usize,
+) -> PyResult<Py<PyDict>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
     let t0 = Instant::now();
-    let parsed = parse_line_to_dict(py, line, schema)?;
-    let runtime_ns = t0.elapsed().as_nanos() as u128;
+    // hash64 covers the raw bytes, as in EnrichedRecord::parse_bytes
+    let parsed = core::decode_line(line, strict_utf8).map_err(|e| e.to_string()).and_then(|text| {
+        let (fields, layout) = core::split_with_layout(&text, schema)?;
+        let defaults = record_defaults(schema, &fields);
+        let rec = core::EnrichedRecord {
+            parsed: field_map(fields, (layout, defaults)),
+            raw_excerpt: core::raw_excerpt(&text, excerpt_len).to_string(),
+            hash64: core::hash64(line),
+            runtime_ns: t0.elapsed().as_nanos(),
+        };
+        Ok((rec, layout))
+    });
+    let (rec, layout) = parsed.map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+    enriched_to_pydict(py, &rec, layout)
+}
+
+/// Select the algorithm for `hash64` in enriched results, process-wide:
+/// "fnv1a" (default) or "xxhash64" (seed 0).
+#[pyfunction]
+#[pyo3(text_signature = "(name)")]
+fn set_hash_algo(name: &str) -> PyResult<()> {
+    core::set_hash_algo(core::HashAlgo::from_name(name).map_err(PyValueError::new_err)?);
+    Ok(())
+}
+
+/// Run batch parsing (the `*_batch` functions, the arena and dict-encoded
+/// paths and the file conversions) on a dedicated pool of `n` threads instead
+/// of Rayon's global pool; 0 goes back to the global pool. The pool threads
+/// only touch Rust data and never take the GIL, but the calling thread keeps
+/// it while the batch runs, so other Python threads wait for the batch.
+#[pyfunction]
+#[pyo3(text_signature = "(n)")]
+fn set_parse_threads(n: usize) -> PyResult<()> {
+    let pool = if n == 0 {
+        None
+    } else {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .thread_name(|i| format!("logparse-{}", i))
+            .build()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Some(Arc::new(pool))
+    };
+    *PARSE_POOL.write().unwrap() = pool;
+    Ok(())
+}
+
+/// Name of the algorithm currently used for `hash64`.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn get_hash_algo() -> &'static str {
+    core::hash_algo().name()
+}
+
+/// Parse a batch of lines in parallel and return enriched dicts per line.
+/// Heavy parsing happens without the Python GIL using Rayon; Python dicts are
+/// constructed after parsing, minimizing GIL contention.
+/// With `arena_chunk > 0`, lines are parsed in chunks of that size whose field
+/// values share one FieldArena buffer instead of a String per field.
+/// With `strict=False`, a malformed or unknown-type line does not abort the
+/// batch: its slot holds {"error": str, "line_index": int} instead.
+/// With `sample_rate < 1`, only that fraction of records (chosen by hash64)
+/// keep their raw_excerpt; the others have None.
+#[pyfunction]
+#[pyo3(
+    signature = (lines, arena_chunk = 0, excerpt_len = core::DEFAULT_EXCERPT_LEN, strict = true, sample_rate = 1.0),
+    text_signature = "(lines, arena_chunk=0, excerpt_len=256, strict=True, sample_rate=1.0)"
+)]
+fn parse_kv_enriched_batch(
+    py: Python,
+    lines: Vec<String>,
+    arena_chunk: usize,
+    excerpt_len: usize,
+    strict: bool,
+    sample_rate: f64,
+) -> PyResult<Vec<Py<PyDict>>> {
+    if !(0.0..=1.0).contains(&sample_rate) {
+        return Err(PyValueError::new_err("sample_rate must be between 0.0 and 1.0"));
+    }
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
+    if arena_chunk > 0 {
+        let opts = (excerpt_len, sample_rate, strict);
+        return parse_kv_enriched_batch_arena(py, &lines, schema, arena_chunk, opts);
+    }
+
+    // Perform the heavy parsing in parallel without holding the GIL
+    // Unsampled lines never build an excerpt, so the batch does not hold them
+    type Mid<'s> = (core::EnrichedRecord, Layout<'s>, Option<String>);
+    let mids: Vec<Result<Mid, String>> = in_parse_pool(|| {
+        lines
+            .par_iter()
+            .map(|line| {
+                let (rec, layout) = parse_enriched(schema, line, None)?;
+                let excerpt = core::sampled(rec.hash64, sample_rate)
+                    .then(|| core::raw_excerpt(line, excerpt_len).to_string());
+                Ok((rec, layout, excerpt))
+            })
+            .collect()
+    });
+
+    let errors = mids.iter().filter(|r| r.is_err()).count() as u64;
+    record_parse(mids.len() as u64 - errors, errors);
+
+    // In strict mode, return the first error as a Python ValueError
+    if strict {
+        if let Some(Err(e)) = mids.iter().find(|r| r.is_err()) {
+            return Err(PyValueError::new_err(e.clone()));
+        }
+    }
+
+    // Build Python objects; batch results keep raw string values
+    let mut out: Vec<Py<PyDict>> = Vec::with_capacity(mids.len());
+    for (line_index, r) in mids.into_iter().enumerate() {
+        match r {
+            Ok((rec, (names, _), excerpt)) => {
+                let d = enriched_to_pydict(py, &rec, (names, None))?;
+                d.bind(py).set_item("raw_excerpt", excerpt)?;
+                out.push(d)
+            }
+            Err(e) => out.push(batch_error_dict(py, &e, line_index)?),
+        }
+    }
+
+    Ok(out)
+}
+
+/// parse_kv_enriched_batch without repeated lines: a record whose hash64 was
+/// already seen earlier in the batch is dropped, keeping the first. Returns the
+/// remaining records in input order and the number dropped. Raises ValueError
+/// for the first malformed or unknown-type line.
+#[pyfunction]
+#[pyo3(
+    signature = (lines, excerpt_len = core::DEFAULT_EXCERPT_LEN),
+    text_signature = "(lines, excerpt_len=256)"
+)]
+fn parse_kv_enriched_batch_dedup(
+    py: Python,
+    lines: Vec<String>,
+    excerpt_len: usize,
+) -> PyResult<(Vec<Py<PyDict>>, usize)> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
+
+    let mids: Vec<Result<(core::EnrichedRecord, Layout), String>> = in_parse_pool(|| {
+        lines.par_iter().map(|line| parse_enriched(schema, line, Some(excerpt_len))).collect()
+    });
+    let errors = mids.iter().filter(|r| r.is_err()).count() as u64;
+    record_parse(mids.len() as u64 - errors, errors);
+    let mids: Vec<_> = mids.into_iter().collect::<Result<_, _>>().map_err(PyValueError::new_err)?;
+
+    let mut seen = std::collections::HashSet::with_capacity(mids.len());
+    let mut out = Vec::with_capacity(mids.len());
+    for (rec, (names, _)) in mids {
+        if seen.insert(rec.hash64) {
+            out.push(enriched_to_pydict(py, &rec, (names, None))?);
+        }
+    }
+    let dropped = lines.len() - out.len();
+    Ok((out, dropped))
+}
+
+/// Parse a batch of lines in parallel and return one parse_kv-style dict per
+/// line, without the excerpt, hash and timing of parse_kv_enriched_batch.
+/// Errors are handled as in parse_kv_enriched_batch: the first one raises
+/// ValueError, or with `strict=False` the line's slot holds
+/// {"error": str, "line_index": int}.
+#[pyfunction]
+#[pyo3(signature = (lines, strict = true), text_signature = "(lines, strict=True)")]
+fn parse_kv_batch(py: Python, lines: Vec<String>, strict: bool) -> PyResult<Vec<Py<PyDict>>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
+
+    let split = in_parse_pool(|| core::split_batch(&lines, schema));
+    let errors = split.iter().filter(|r| r.is_err()).count() as u64;
+    record_parse(split.len() as u64 - errors, errors);
+
+    if strict {
+        if let Some(Err(e)) = split.iter().find(|r| r.is_err()) {
+            return Err(PyValueError::new_err(e.clone()));
+        }
+    }
+
+    let mut out: Vec<Py<PyDict>> = Vec::with_capacity(split.len());
+    for (line_index, r) in split.into_iter().enumerate() {
+        match r {
+            Ok((fields, layout)) => {
+                let defaults = record_defaults(schema, &fields);
+                let d = fields_to_pydict(py, &fields, layout, defaults, Default::default())?;
+                out.push(d.unbind())
+            }
+            Err(e) => out.push(batch_error_dict(py, &e, line_index)?),
+        }
+    }
+    Ok(out)
+}
+
+/// Per-line failure entry of a non-strict batch.
+fn batch_error_dict(py: Python, error: &str, line_index: usize) -> PyResult<Py<PyDict>> {
     let d = PyDict::new(py);
-    d.set_item("parsed", parsed)?;
-    let max_len = std::cmp::min(256, line.len());
-    d.set_item("raw_excerpt", &line[..max_len])?;
-    let h = core::hash64_fnv1a(line.as_bytes());
-    d.set_item("hash64", h as u128)?;
-    d.set_item("runtime_ns", runtime_ns)?;
+    d.set_item("error", error)?;
+    d.set_item("line_index", line_index)?;
     Ok(d.unbind())
 }
 
-/// Parse using the schema at the given path and return an enriched result.
-#[pyfunction]
-#[pyo3(text_signature = "(line, schema_path)")]
-fn parse_kv_enriched_with_schema(
-    py: Python,
-    line: &str,
-    schema_path: &str,
-) -> PyResult<Py<PyDict>> {
-    core::ensure_schema_loaded(schema_path).map_err(PyValueError::new_err)?;
-    let guard = SCHEMA_CACHE.read().unwrap();
-    let schema = guard.as_ref().unwrap();
-    let t0 = Instant::now();
-    let parsed = parse_line_to_dict(py, line, schema)?;
-    let runtime_ns = t0.elapsed().as_nanos() as u128;
-    let d = PyDict::new(py);
-    d.set_item("parsed", parsed)?;
-    let max_len = std::cmp::min(256, line.len());
-    d.set_item("raw_excerpt", &line[..max_len])?;
-    let h = core::hash64_fnv1a(line.as_bytes());
-    d.set_item("hash64", h as u128)?;
-    d.set_item("runtime_ns", runtime_ns)?;
-    Ok(d.unbind())
+fn parse_kv_enriched_batch_arena(
+    py: Python,
+    lines: &[String],
+    schema: &LoadedSchema,
+    chunk_size: usize,
+    (excerpt_len, sample_rate, strict): (usize, f64, bool),
+) -> PyResult<Vec<Py<PyDict>>> {
+    struct Meta {
+        t: String,
+        row: usize,
+        hash64: u64,
+        excerpt: Option<String>,
+        runtime_ns: u128,
+    }
+
+    // One arena per chunk: all field values of the chunk live in a single buffer
+    let chunks: Vec<(core::FieldArena, Vec<Result<Meta, String>>)> = in_parse_pool(|| {
+        lines
+            .par_chunks(chunk_size)
+            .map(|chunk| {
+                let bytes: usize = chunk.iter().map(|l| l.len()).sum();
+                let mut arena = core::FieldArena::with_capacity(chunk.len(), bytes);
+                let metas = chunk
+                    .iter()
+                    .map(|line| {
+                        let t0 = Instant::now();
+                        let t = schema.log_type(line)?;
+                        if !schema.type_to_fields.contains_key(&t) {
+                            return Err(format!("Unknown log type in schema: {}", t));
+                        }
+                        let row = arena.push_line_for_type(line, schema, &t);
+                        if schema.strict_fields {
+                            let subtype = arena.row(row).nth(schema.type_index + 1);
+                            let names = schema
+                                .subtype_fields(&t, subtype)
+                                .unwrap_or(&schema.type_to_fields[&t]);
+                            schema.check_overflow(&t, arena.row(row).len(), names.len())?;
+                        }
+                        let runtime_ns = t0.elapsed().as_nanos();
+                        let hash64 = core::hash64(line.as_bytes());
+                        let excerpt = core::sampled(hash64, sample_rate)
+                            .then(|| core::raw_excerpt(line, excerpt_len).to_string());
+                        Ok(Meta { t, row, hash64, excerpt, runtime_ns })
+                    })
+                    .collect();
+                (arena, metas)
+            })
+            .collect()
+    });
+
+    let errors = chunks.iter().flat_map(|(_, m)| m).filter(|r| r.is_err()).count() as u64;
+    record_parse(lines.len() as u64 - errors, errors);
+    if strict {
+        if let Some(Err(e)) = chunks.iter().flat_map(|(_, m)| m).find(|r| r.is_err()) {
+            return Err(PyValueError::new_err(e.clone()));
+        }
+    }
+
+    let mut out: Vec<Py<PyDict>> = Vec::with_capacity(lines.len());
+    for (chunk_index, (arena, metas)) in chunks.iter().enumerate() {
+        for (i, m) in metas.iter().enumerate() {
+            let m = match m {
+                Ok(m) => m,
+                Err(e) => {
+                    out.push(batch_error_dict(py, e, chunk_index * chunk_size + i)?);
+                    continue;
+                }
+            };
+            let subtype = arena.row(m.row).nth(schema.type_index + 1);
+            let names =
+                schema.subtype_fields(&m.t, subtype).unwrap_or(&schema.type_to_fields[&m.t]);
+            let defaults = schema.subtype_defaults(&m.t, subtype);
+            let parsed = PyDict::new(py);
+            let mut values = arena.row(m.row);
+            for (i, name) in names.iter().enumerate() {
+                let key = pyo3::types::PyString::intern(py, name);
+                match values.next() {
+                    Some(v) => parsed.set_item(key, v)?,
+                    None => parsed.set_item(key, defaults.and_then(|d| d.get(i)?.as_deref()))?,
+                }
+            }
+            let d = PyDict::new(py);
+            d.set_item("parsed", parsed)?;
+            d.set_item("raw_excerpt", &m.excerpt)?;
+            d.set_item("hash64", m.hash64 as u128)?;
+            d.set_item("runtime_ns", m.runtime_ns)?;
+            out.push(d.unbind());
+        }
+    }
+    Ok(out)
+}
+
+/// Parse a batch of lines into a pyarrow.RecordBatch with one string column per
+/// schema field (null where a line has no value). All lines must share one
+/// field layout; a malformed line or a mix of log types raises ValueError.
+/// The arrays are handed to pyarrow through the Arrow C Data Interface.
+#[cfg(feature = "arrow")]
+#[pyfunction]
+#[pyo3(text_signature = "(lines)")]
+fn parse_to_arrow(py: Python, lines: Vec<String>) -> PyResult<Py<PyAny>> {
+    use arrow_array::ffi::{FFI_ArrowArray, FFI_ArrowSchema};
+    use arrow_array::{Array, StructArray};
+
+    let batch = {
+        let guard = SCHEMA_CACHE.read().unwrap();
+        let schema = guard
+            .get(DEFAULT_SCHEMA_NAME)
+            .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
+        in_parse_pool(|| core::arrow::record_batch(&lines, schema)).map_err(|e| {
+            record_parse(0, 1);
+            PyValueError::new_err(e)
+        })?
+    };
+    record_parse(batch.num_rows() as u64, 0);
+
+    let data = StructArray::from(batch).into_data();
+    let array = FFI_ArrowArray::new(&data);
+    let schema = FFI_ArrowSchema::try_from(data.data_type())
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    // pyarrow moves both structs out; ours are left released and drop as no-ops
+    let rb = py.import("pyarrow")?.getattr("RecordBatch")?.call_method1(
+        "_import_from_c",
+        (&array as *const FFI_ArrowArray as usize, &schema as *const FFI_ArrowSchema as usize),
+    )?;
+    Ok(rb.unbind())
+}
+
+/// Parse a batch of lines of one log type into a pandas.DataFrame with one
+/// column per schema field, in schema order. Columns are built in Rust:
+/// declared `int`/`float` fields whose values all parse are handed to pandas
+/// as typed arrays through the buffer protocol (int64/float64, no Python
+/// object per cell); other columns are lists of values typed as in parse_kv.
+/// A malformed line or a mix of log types raises ValueError.
+#[cfg(feature = "pandas")]
+#[pyfunction]
+#[pyo3(text_signature = "(lines)")]
+fn parse_to_dataframe(py: Python, lines: Vec<String>) -> PyResult<Py<PyAny>> {
+    use core::{FieldType, TypedValue};
+    use pyo3::types::{PyBytes, PyList};
+
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
+    let split = in_parse_pool(|| core::split_uniform_batch(&lines, schema));
+    let (rows, (names, types)) = split.map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(rows.len() as u64, 0);
+
+    let array = py.import("array")?.getattr("array")?;
+    let columns = PyDict::new(py);
+    for (i, name) in names.iter().enumerate() {
+        let ty = field_type(types, i);
+        let packed = match ty {
+            Some(FieldType::Int) => {
+                packed_column(&rows, i, |v| match core::coerce_value(v, FieldType::Int)? {
+                    TypedValue::Int(n) => Some(n.to_ne_bytes()),
+                    _ => None,
+                })
+                .map(|b| ("q", b))
+            }
+            Some(FieldType::Float) => {
+                packed_column(&rows, i, |v| match core::coerce_value(v, FieldType::Float)? {
+                    TypedValue::Float(f) => Some(f.to_ne_bytes()),
+                    _ => None,
+                })
+                .map(|b| ("d", b))
+            }
+            _ => None,
+        };
+        let column = match packed {
+            Some((typecode, bytes)) => {
+                let a = array.call1((typecode,))?;
+                a.call_method1("frombytes", (PyBytes::new(py, &bytes),))?;
+                a
+            }
+            None => {
+                let values = rows
+                    .iter()
+                    .map(|row| field_value(py, row.get(i).map(|s| s.as_str()), ty))
+                    .collect::<PyResult<Vec<_>>>()?;
+                PyList::new(py, values)?.into_any()
+            }
+        };
+        columns.set_item(name, column)?;
+    }
+    let df = py.import("pandas")?.getattr("DataFrame")?.call1((columns,))?;
+    Ok(df.unbind())
+}
+
+/// Native-endian bytes of column `col` for an `array.array`, or None when a
+/// row is missing the value or `parse` rejects it.
+#[cfg(feature = "pandas")]
+fn packed_column<const N: usize>(
+    rows: &[Vec<String>],
+    col: usize,
+    parse: impl Fn(&str) -> Option<[u8; N]>,
+) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(rows.len() * N);
+    for row in rows {
+        out.extend_from_slice(&parse(row.get(col)?)?);
+    }
+    Some(out)
 }
 
-/// Parse a batch of lines in parallel and return enriched dicts per line.
-/// Heavy parsing happens without the Python GIL using Rayon; Python dicts are
-/// constructed after parsing, minimizing GIL contention.
+/// Parse a batch of lines and return dictionary-encoded columns:
+/// {"rows": N, "columns": {name: {"dictionary": [distinct values], "indices": [int | None]}}}.
+/// Columns are the union of the fields of all log types in the batch; rows of
+/// another type (or missing trailing fields) have a None index.
 #[pyfunction]
 #[pyo3(text_signature = "(lines)")]
-fn parse_kv_enriched_batch(py: Python, lines: Vec<String>) -> PyResult<Vec<Py<PyDict>>> {
+fn parse_batch_dict_encoded(py: Python, lines: Vec<String>) -> PyResult<Py<PyDict>> {
     let guard = SCHEMA_CACHE.read().unwrap();
     let schema = guard
-        .as_ref()
+        .get(DEFAULT_SCHEMA_NAME)
         .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema()"))?;
 
-    // Perform the heavy parsing in parallel without holding the GIL
-    // Avoid cloning schema key names per record by carrying only the log type and
-    // the parsed field values; we look up the names again when building Python
-    // objects. This reduces per-record allocations and hashing.
-    struct Mid {
-        t: String,
-        fields: Vec<String>,
-        hash64: u64,
-        excerpt: String,
-        runtime_ns: u128,
-    }
-
-    // Perform heavy compute without interacting with Python; no need to hold the GIL here.
-    let mids: Vec<Result<Mid, String>> = {
+    // (field names for the row's log type, split values)
+    type Row<'a> = (&'a Vec<String>, Vec<String>);
+    let rows: Vec<Result<Row, String>> = in_parse_pool(|| {
         lines
             .par_iter()
             .map(|line| {
-                let t0 = Instant::now();
-                let t = core::extract_field_internal(line, 3)
-                    .ok_or_else(|| "Could not extract log type at index 3".to_string())?;
-                // Validate type exists early to surface errors promptly
-                let _ = schema
+                let t = schema.log_type(line)?;
+                let names = schema
                     .type_to_fields
                     .get(&t)
                     .ok_or_else(|| format!("Unknown log type in schema: {}", t))?;
-                let fields = core::split_csv_internal(line);
-                let runtime_ns = t0.elapsed().as_nanos() as u128;
-                let excerpt_len = std::cmp::min(256, line.len());
-                Ok(Mid {
-                    t,
-                    fields,
-                    hash64: core::hash64_fnv1a(line.as_bytes()),
-                    excerpt: line[..excerpt_len].to_string(),
-                    runtime_ns,
-                })
+                let fields = core::split_fields_for_type(line, schema, &t);
+                let names = schema.fields_for_record(&t, &fields).unwrap_or(names);
+                schema.check_overflow(&t, fields.len(), names.len())?;
+                Ok((names, fields))
             })
             .collect()
-    };
+    });
 
-    // If any error occurred, return the first one as a Python ValueError
-    for r in &mids {
-        if let Err(e) = r {
-            return Err(PyValueError::new_err(e.clone()));
-        }
-    }
+    let errors = rows.iter().filter(|r| r.is_err()).count() as u64;
+    record_parse(rows.len() as u64 - errors, errors);
 
-    // Build Python objects
-    let mut out: Vec<Py<PyDict>> = Vec::with_capacity(mids.len());
-    for r in mids.into_iter().map(|x| x.unwrap()) {
-        let d = PyDict::new(py);
-        let parsed = PyDict::new(py);
-        // Lookup field names by type without cloning them
-        let names = match schema.type_to_fields.get(&r.t) {
-            Some(n) => n,
-            None => {
-                return Err(PyValueError::new_err(format!("Unknown log type in schema: {}", r.t)))
-            }
-        };
-        for (i, name) in names.iter().enumerate() {
-            let key = pyo3::types::PyString::intern(py, name);
-            if i < r.fields.len() {
-                parsed.set_item(key, &r.fields[i])?;
-            } else {
-                parsed.set_item(key, py.None())?;
-            }
-        }
-        d.set_item("parsed", parsed)?;
-        d.set_item("raw_excerpt", r.excerpt)?;
-        d.set_item("hash64", r.hash64 as u128)?;
-        d.set_item("runtime_ns", r.runtime_ns)?;
-        out.push(d.unbind());
+    let mut batch = core::DictEncodedBatch::new();
+    for r in rows {
+        let (names, fields) = r.map_err(PyValueError::new_err)?;
+        batch.push_row(names, &fields);
     }
 
-    Ok(out)
+    let columns = PyDict::new(py);
+    for (name, col) in &batch.columns {
+        let c = PyDict::new(py);
+        c.set_item("dictionary", &col.dictionary)?;
+        c.set_item("indices", &col.indices)?;
+        columns.set_item(name, c)?;
+    }
+    let d = PyDict::new(py);
+    d.set_item("rows", batch.rows)?;
+    d.set_item("columns", columns)?;
+    Ok(d.unbind())
 }
 
 // -------- Anonymizer state (bindings) --------
 static ANONYMIZER: Lazy<RwLock<Option<core::AnonymizerCore>>> = Lazy::new(|| RwLock::new(None));
 
+/// Whether the loaded anonymizer redacts lines of unknown log type.
+fn redacts_unknown() -> bool {
+    ANONYMIZER.read().unwrap().as_ref().is_some_and(|a| a.redacts_unknown())
+}
+
+fn anonymizer_from_text(
+    text: &str,
+    jsonc: bool,
+    strict: bool,
+) -> Result<core::AnonymizerCore, String> {
+    let stripped;
+    let json = if jsonc {
+        stripped = core::strip_jsonc(text);
+        &stripped
+    } else {
+        text
+    };
+    if strict {
+        core::anonymizer_from_json_strict(json)
+    } else {
+        core::anonymizer_from_json(json)
+    }
+}
+
+fn read_anonymizer(
+    config_path: &str,
+    jsonc: bool,
+    strict: bool,
+) -> Result<core::AnonymizerCore, String> {
+    let text = std::fs::read_to_string(config_path).map_err(|e| e.to_string())?;
+    anonymizer_from_text(&text, jsonc || core::is_jsonc_path(config_path), strict)
+}
+
 /// Load anonymizer rules from a JSON file path. Returns True on success.
+/// With `jsonc=True` (implied for .jsonc/.json5 files) comments and trailing
+/// commas are allowed. With `strict=True`, rules that would silently fall back
+/// at runtime (e.g. fixed mode without a fixed value) raise ValueError.
 #[pyfunction]
-#[pyo3(text_signature = "(config_path)")]
-fn load_anonymizer(config_path: &str) -> PyResult<bool> {
-    let json =
-        std::fs::read_to_string(config_path).map_err(|e| PyValueError::new_err(e.to_string()))?;
-    let anon = core::anonymizer_from_json(&json).map_err(PyValueError::new_err)?;
+#[pyo3(
+    signature = (config_path, jsonc = false, strict = false),
+    text_signature = "(config_path, jsonc=False, strict=False)"
+)]
+fn load_anonymizer(config_path: &str, jsonc: bool, strict: bool) -> PyResult<bool> {
+    let anon = read_anonymizer(config_path, jsonc, strict).map_err(PyValueError::new_err)?;
     let mut g = ANONYMIZER.write().unwrap();
     *g = Some(anon);
     Ok(true)
 }
 
 /// Set anonymizer rules from a JSON string. Returns True on success.
+/// `jsonc` and `strict` as for load_anonymizer().
+#[pyfunction]
+#[pyo3(
+    signature = (config_json, jsonc = false, strict = false),
+    text_signature = "(config_json, jsonc=False, strict=False)"
+)]
+fn set_anonymizer_json(config_json: &str, jsonc: bool, strict: bool) -> PyResult<bool> {
+    let anon = anonymizer_from_text(config_json, jsonc, strict).map_err(PyValueError::new_err)?;
+    let mut g = ANONYMIZER.write().unwrap();
+    *g = Some(anon);
+    Ok(true)
+}
+
+/// Switch to new anonymizer rules from a JSON file, typically a new salt and
+/// `key_version`, keeping the integrity table learned so far so tokens issued
+/// under the old key can still be reversed. `jsonc` and `strict` as for
+/// load_anonymizer(). Raises ValueError if no anonymizer is loaded.
 #[pyfunction]
-#[pyo3(text_signature = "(config_json)")]
-fn set_anonymizer_json(config_json: &str) -> PyResult<bool> {
-    let anon = core::anonymizer_from_json(config_json).map_err(PyValueError::new_err)?;
+#[pyo3(
+    signature = (config_path, jsonc = false, strict = false),
+    text_signature = "(config_path, jsonc=False, strict=False)"
+)]
+fn rotate_anonymizer(config_path: &str, jsonc: bool, strict: bool) -> PyResult<bool> {
+    let mut anon = read_anonymizer(config_path, jsonc, strict).map_err(PyValueError::new_err)?;
     let mut g = ANONYMIZER.write().unwrap();
+    let previous = g.take().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    anon.inherit_table(previous);
     *g = Some(anon);
     Ok(true)
 }
@@ -306,10 +1489,30 @@ fn get_anonymizer_status(py: Python) -> PyResult<Py<PyDict>> {
     let d = PyDict::new(py);
     let g = ANONYMIZER.read().unwrap();
     if let Some(a) = g.as_ref() {
-        let total_pairs: usize = a.table.values().map(|m| m.len()).sum();
         d.set_item("enabled", true)?;
-        d.set_item("fields", a.table.len())?;
-        d.set_item("pairs", total_pairs)?;
+        d.set_item("fields", a.table.field_count())?;
+        d.set_item("pairs", a.total_pairs())?;
+        d.set_item("dry_run", a.is_dry_run())?;
+        let per_field = PyDict::new(py);
+        let (mut total_calls, mut cache_hits) = (0u64, 0u64);
+        for (field, s) in a.field_stats() {
+            let fs = PyDict::new(py);
+            fs.set_item("total_calls", s.total_calls)?;
+            fs.set_item("cache_hits", s.cache_hits)?;
+            per_field.set_item(field, fs)?;
+            total_calls += s.total_calls;
+            cache_hits += s.cache_hits;
+        }
+        d.set_item("total_calls", total_calls)?;
+        d.set_item("cache_hits", cache_hits)?;
+        d.set_item("field_stats", per_field)?;
+        if a.is_dry_run() {
+            let matches = PyDict::new(py);
+            for (field, n) in a.dry_run_matches() {
+                matches.set_item(field, n)?;
+            }
+            d.set_item("dry_run_matches", matches)?;
+        }
     } else {
         d.set_item("enabled", false)?;
     }
@@ -323,7 +1526,7 @@ fn export_integrity_table(py: Python) -> PyResult<Py<PyDict>> {
     let g = ANONYMIZER.read().unwrap();
     let d = PyDict::new(py);
     if let Some(a) = g.as_ref() {
-        for (field, map) in &a.table {
+        for (field, map) in a.table.snapshot() {
             let sub = PyDict::new(py);
             for (orig, repl) in map {
                 sub.set_item(orig, repl)?;
@@ -334,43 +1537,245 @@ fn export_integrity_table(py: Python) -> PyResult<Py<PyDict>> {
     Ok(d.unbind())
 }
 
+/// Forget all integrity mappings while keeping the loaded config, e.g. when
+/// rotating datasets. Returns the number of pairs cleared. Raises ValueError
+/// if no anonymizer is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn reset_anonymizer() -> PyResult<usize> {
+    let mut g = ANONYMIZER.write().unwrap();
+    let a = g.as_mut().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    Ok(a.clear_table())
+}
+
+/// Forget the integrity mappings of one field. Returns the number of pairs
+/// cleared. Raises ValueError if no anonymizer is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "(field)")]
+fn clear_anonymizer_field(field: &str) -> PyResult<usize> {
+    let mut g = ANONYMIZER.write().unwrap();
+    let a = g.as_mut().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    Ok(a.clear_field(field))
+}
+
+/// Reverse an anonymized value: the original that `field` mapped to `token`,
+/// or None if the token is unknown or shared by several originals (e.g. a
+/// `fixed` replacement). Raises ValueError if no anonymizer is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "(field, token)")]
+fn deanonymize(field: &str, token: &str) -> PyResult<Option<String>> {
+    let g = ANONYMIZER.read().unwrap();
+    let a = g.as_ref().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    Ok(a.deanonymize_one(field, token))
+}
+
+/// Replacement the anonymizer would produce for `value` in `field`, without
+/// adding it to the integrity table or consuming a counter value. None means
+/// the value would pass through. Raises ValueError if no anonymizer is loaded.
+#[pyfunction]
+#[pyo3(text_signature = "(field, value)")]
+fn preview_anonymize(field: &str, value: &str) -> PyResult<Option<String>> {
+    let g = ANONYMIZER.read().unwrap();
+    let a = g.as_ref().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    Ok(a.preview_one(field, value))
+}
+
+/// Return a flat snapshot of internal state suitable for metrics exporters:
+/// schema_loaded, schema_types, anonymizer_enabled, anonymizer_fields,
+/// anonymizer_pairs, anonymizer_table_bytes, parse_lines_ok, parse_lines_err,
+/// parse_lines_redacted. Parse counters are cumulative for the process.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn metrics_snapshot(py: Python) -> PyResult<Py<PyDict>> {
+    let d = PyDict::new(py);
+    {
+        let guard = SCHEMA_CACHE.read().unwrap();
+        d.set_item("schema_loaded", guard.contains_key(DEFAULT_SCHEMA_NAME))?;
+        d.set_item(
+            "schema_types",
+            guard.get(DEFAULT_SCHEMA_NAME).map_or(0, |ls| ls.type_to_fields.len()),
+        )?;
+    }
+    {
+        let g = ANONYMIZER.read().unwrap();
+        d.set_item("anonymizer_enabled", g.is_some())?;
+        d.set_item("anonymizer_fields", g.as_ref().map_or(0, |a| a.table.field_count()))?;
+        d.set_item("anonymizer_pairs", g.as_ref().map_or(0, |a| a.total_pairs()))?;
+        d.set_item("anonymizer_table_bytes", g.as_ref().map_or(0, |a| a.table_bytes()))?;
+    }
+    d.set_item("parse_lines_ok", PARSE_LINES_OK.load(Ordering::Relaxed))?;
+    d.set_item("parse_lines_err", PARSE_LINES_ERR.load(Ordering::Relaxed))?;
+    d.set_item("parse_lines_redacted", PARSE_LINES_REDACTED.load(Ordering::Relaxed))?;
+    Ok(d.unbind())
+}
+
+/// FieldFilter from the `include_fields` / `exclude_fields` arguments of the
+/// anonymize functions.
+fn field_filter(include: Option<Vec<String>>, exclude: Option<Vec<String>>) -> core::FieldFilter {
+    core::FieldFilter {
+        include: include.map(|fields| fields.into_iter().collect()),
+        exclude: exclude.unwrap_or_default().into_iter().collect(),
+    }
+}
+
+/// Copy of a parsed dict with every string value of a field allowed by
+/// `filter` passed through the anonymizer, plus the names of the fields whose
+/// value was replaced.
+fn anonymize_parsed<'py>(
+    py: Python<'py>,
+    a: &core::AnonymizerCore,
+    parsed: &Bound<'py, PyDict>,
+    filter: &core::FieldFilter,
+) -> PyResult<(Bound<'py, PyDict>, Vec<String>)> {
+    let out = PyDict::new(py);
+    let mut changed = Vec::new();
+    for (k, v) in parsed.iter() {
+        let key: String = k.extract()?;
+        if !filter.allows(&key) {
+            out.set_item(k, v)?;
+            continue;
+        }
+        if let Some(value_str) = v.extract::<Option<String>>().ok().flatten() {
+            // date_shift fields take their offset from another field of the record
+            let subject = match a.subject_field(&key) {
+                Some(sf) => parsed.get_item(sf)?.and_then(|s| s.str().ok()).map(|s| s.to_string()),
+                None => None,
+            };
+            if let Some(repl) = a.anonymize_one_for_subject(&key, &value_str, subject.as_deref()) {
+                out.set_item(k, repl)?;
+                changed.push(key);
+                continue;
+            }
+        }
+        out.set_item(k, v)?;
+    }
+    Ok((out, changed))
+}
+
+/// Anonymize an already parsed dict (e.g. from parse_kv) with the loaded
+/// anonymizer, returning a new dict; the input is left unchanged. Same result
+/// as the `parsed` dict of parse_kv_enriched_anon for the line. Without an
+/// anonymizer the copy has the original values. `include_fields` limits
+/// anonymization to the listed fields and `exclude_fields` leaves the listed
+/// ones alone (exclude wins), whatever rules the config has for them.
+#[pyfunction]
+#[pyo3(
+    signature = (d, include_fields = None, exclude_fields = None),
+    text_signature = "(d, include_fields=None, exclude_fields=None)"
+)]
+fn anonymize_dict(
+    py: Python,
+    d: &Bound<PyDict>,
+    include_fields: Option<Vec<String>>,
+    exclude_fields: Option<Vec<String>>,
+) -> PyResult<Py<PyDict>> {
+    let filter = field_filter(include_fields, exclude_fields);
+    let g = ANONYMIZER.read().unwrap();
+    match g.as_ref() {
+        Some(a) => Ok(anonymize_parsed(py, a, d, &filter)?.0.unbind()),
+        None => Ok(d.copy()?.unbind()),
+    }
+}
+
+/// (field, original, replacement)
+type MappingTuple = (String, String, String);
+
+/// Parse and anonymize a batch of lines, returning (results, new_mappings).
+/// `results` holds one anonymized parsed dict per line; `new_mappings` lists
+/// only the (field, original, replacement) entries the integrity table learned
+/// during this call. All lines are parsed before anything is anonymized, so a
+/// malformed line raises ValueError without touching the table.
+/// `include_fields` / `exclude_fields` as for anonymize_dict().
+#[pyfunction]
+#[pyo3(
+    signature = (lines, include_fields = None, exclude_fields = None),
+    text_signature = "(lines, include_fields=None, exclude_fields=None)"
+)]
+fn anonymize_batch_with_deltas(
+    py: Python,
+    lines: Vec<String>,
+    include_fields: Option<Vec<String>>,
+    exclude_fields: Option<Vec<String>>,
+) -> PyResult<(Vec<Py<PyDict>>, Vec<MappingTuple>)> {
+    let filter = field_filter(include_fields, exclude_fields);
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema =
+        guard.get(DEFAULT_SCHEMA_NAME).ok_or_else(|| PyValueError::new_err("No schema loaded"))?;
+    let parsed: Vec<Bound<PyDict>> = lines
+        .iter()
+        .map(|line| parse_line_to_dict(py, line, schema, None, Default::default()))
+        .collect::<PyResult<_>>()?;
+
+    let anon_guard = ANONYMIZER.write().unwrap();
+    let a = anon_guard.as_ref().ok_or_else(|| PyValueError::new_err("Anonymizer not enabled"))?;
+    // Holding the write lock for the whole batch keeps other callers out of the delta
+    a.begin_delta();
+    let results: PyResult<Vec<Py<PyDict>>> = parsed
+        .iter()
+        .map(|p| anonymize_parsed(py, a, p, &filter).map(|(d, _)| d.unbind()))
+        .collect();
+    let delta = a.end_delta();
+    let mappings = delta.into_iter().map(|m| (m.field, m.original, m.replacement)).collect();
+    Ok((results?, mappings))
+}
+
 /// Parse a line and return enriched results with anonymization applied when enabled.
+/// `_anonymized_fields` lists the fields whose value was replaced, and
+/// `_anonymized` is true when that list is non-empty. When the anonymizer
+/// config sets `redact_unknown`, a line whose log type is not in the schema
+/// gives an empty `parsed` and a "[REDACTED]" `raw_excerpt` (its `hash64` is
+/// still that of the line) instead of raising ValueError. `include_fields` /
+/// `exclude_fields` as for anonymize_dict().
 #[pyfunction]
-#[pyo3(text_signature = "(line)")]
-fn parse_kv_enriched_anon(py: Python, line: &str) -> PyResult<Py<PyDict>> {
+#[pyo3(
+    signature = (line, excerpt_len = core::DEFAULT_EXCERPT_LEN, include_fields = None, exclude_fields = None),
+    text_signature = "(line, excerpt_len=256, include_fields=None, exclude_fields=None)"
+)]
+fn parse_kv_enriched_anon(
+    py: Python,
+    line: &str,
+    excerpt_len: usize,
+    include_fields: Option<Vec<String>>,
+    exclude_fields: Option<Vec<String>>,
+) -> PyResult<Py<PyDict>> {
+    let filter = field_filter(include_fields, exclude_fields);
     let guard = SCHEMA_CACHE.read().unwrap();
-    let schema = guard.as_ref().ok_or_else(|| PyValueError::new_err("No schema loaded"))?;
+    let schema =
+        guard.get(DEFAULT_SCHEMA_NAME).ok_or_else(|| PyValueError::new_err("No schema loaded"))?;
     let t_parse = Instant::now();
-    let parsed0 = parse_line_to_dict(py, line, schema)?;
-    let parse_ns = t_parse.elapsed().as_nanos() as u128;
+    if redacts_unknown() && schema.has_unknown_type(line) {
+        record_redacted(1);
+        let rec = core::EnrichedRecord::redacted(line, t_parse.elapsed().as_nanos());
+        let out = PyDict::new(py);
+        out.set_item("parsed", PyDict::new(py))?;
+        out.set_item("raw_excerpt", rec.raw_excerpt)?;
+        out.set_item("hash64", rec.hash64 as u128)?;
+        out.set_item("_anonymized", false)?;
+        out.set_item("_anonymized_fields", Vec::<String>::new())?;
+        out.set_item("parse_ns", rec.runtime_ns)?;
+        out.set_item("anonymize_ns", 0)?;
+        out.set_item("runtime_ns_total", rec.runtime_ns)?;
+        return Ok(out.unbind());
+    }
+    let parsed0 = parse_line_to_dict(py, line, schema, None, Default::default())?;
+    let parse_ns = t_parse.elapsed().as_nanos();
     let t_anon = Instant::now();
-    let parsed = {
-        let mut anon_guard = ANONYMIZER.write().unwrap();
-        if let Some(a) = anon_guard.as_mut() {
-            let out = PyDict::new(py);
-            for (k, v) in parsed0.iter() {
-                let key: String = k.extract()?;
-                if let Some(value_str) = v.extract::<Option<String>>().ok().flatten() {
-                    if let Some(repl) = a.anonymize_one(&key, &value_str) {
-                        out.set_item(k, repl)?;
-                        continue;
-                    }
-                }
-                out.set_item(k, v)?;
-            }
-            out
+    let (parsed, changed) = {
+        let anon_guard = ANONYMIZER.read().unwrap();
+        if let Some(a) = anon_guard.as_ref() {
+            anonymize_parsed(py, a, &parsed0, &filter)?
         } else {
-            parsed0
+            (parsed0, Vec::new())
         }
     };
-    let anonymize_ns = t_anon.elapsed().as_nanos() as u128;
+    let anonymize_ns = t_anon.elapsed().as_nanos();
     let total_ns = parse_ns + anonymize_ns;
     let out = PyDict::new(py);
     out.set_item("parsed", parsed)?;
-    let max_len = std::cmp::min(256, line.len());
-    out.set_item("raw_excerpt", &line[..max_len])?;
-    out.set_item("hash64", core::hash64_fnv1a(line.as_bytes()) as u128)?;
-    out.set_item("_anonymized", true)?;
+    out.set_item("raw_excerpt", core::raw_excerpt(line, excerpt_len))?;
+    out.set_item("hash64", core::hash64(line.as_bytes()) as u128)?;
+    out.set_item("_anonymized", !changed.is_empty())?;
+    out.set_item("_anonymized_fields", changed)?;
     out.set_item("parse_ns", parse_ns)?;
     out.set_item("anonymize_ns", anonymize_ns)?;
     out.set_item("runtime_ns_total", total_ns)?;
@@ -379,32 +1784,502 @@ fn parse_kv_enriched_anon(py: Python, line: &str) -> PyResult<Py<PyDict>> {
 
 /// Parse a line using the given schema path and return enriched results with anonymization when enabled.
 #[pyfunction]
-#[pyo3(text_signature = "(line, schema_path)")]
+#[pyo3(
+    signature = (line, schema_path, excerpt_len = core::DEFAULT_EXCERPT_LEN, include_fields = None, exclude_fields = None),
+    text_signature = "(line, schema_path, excerpt_len=256, include_fields=None, exclude_fields=None)"
+)]
 fn parse_kv_enriched_with_schema_anon(
     py: Python,
     line: &str,
     schema_path: &str,
+    excerpt_len: usize,
+    include_fields: Option<Vec<String>>,
+    exclude_fields: Option<Vec<String>>,
 ) -> PyResult<Py<PyDict>> {
     core::ensure_schema_loaded(schema_path).map_err(PyValueError::new_err)?;
-    parse_kv_enriched_anon(py, line)
+    parse_kv_enriched_anon(py, line, excerpt_len, include_fields, exclude_fields)
+}
+
+/// Parse and anonymize a batch of lines in parallel. Returns one dict per line
+/// shaped like parse_kv_enriched_anon() (parsed, raw_excerpt, hash64,
+/// _anonymized, _anonymized_fields, parse_ns, anonymize_ns, runtime_ns_total); like
+/// parse_kv_enriched_batch, parsed values are the raw strings. All lines are
+/// parsed before anything is anonymized, so a malformed line raises ValueError
+/// without touching the integrity table. Unknown-type lines are redacted as in
+/// parse_kv_enriched_anon when `redact_unknown` is set, and `include_fields` /
+/// `exclude_fields` work as for anonymize_dict().
+#[pyfunction]
+#[pyo3(
+    signature = (lines, excerpt_len = core::DEFAULT_EXCERPT_LEN, include_fields = None, exclude_fields = None),
+    text_signature = "(lines, excerpt_len=256, include_fields=None, exclude_fields=None)"
+)]
+fn parse_kv_enriched_anon_batch(
+    py: Python,
+    lines: Vec<String>,
+    excerpt_len: usize,
+    include_fields: Option<Vec<String>>,
+    exclude_fields: Option<Vec<String>>,
+) -> PyResult<Vec<Py<PyDict>>> {
+    let filter = field_filter(include_fields, exclude_fields);
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema =
+        guard.get(DEFAULT_SCHEMA_NAME).ok_or_else(|| PyValueError::new_err("No schema loaded"))?;
+
+    struct Mid<'a> {
+        names: &'a [String],
+        fields: Vec<String>,
+        parse_ns: u128,
+        redacted: bool,
+    }
+    let redact = redacts_unknown();
+    let mids: Vec<Result<Mid, String>> = in_parse_pool(|| {
+        lines
+            .par_iter()
+            .map(|line| {
+                let t0 = Instant::now();
+                let t = schema.log_type(line)?;
+                let Some(names) = schema.type_to_fields.get(&t) else {
+                    if !redact {
+                        return Err(format!("Unknown log type in schema: {}", t));
+                    }
+                    let parse_ns = t0.elapsed().as_nanos();
+                    return Ok(Mid { names: &[], fields: Vec::new(), parse_ns, redacted: true });
+                };
+                let fields = core::split_fields_for_type(line, schema, &t);
+                let names = schema.fields_for_record(&t, &fields).unwrap_or(names);
+                schema.check_overflow(&t, fields.len(), names.len())?;
+                Ok(Mid { names, fields, parse_ns: t0.elapsed().as_nanos(), redacted: false })
+            })
+            .collect()
+    });
+    let errors = mids.iter().filter(|r| r.is_err()).count() as u64;
+    let redacted = mids.iter().filter(|r| r.as_ref().is_ok_and(|m| m.redacted)).count() as u64;
+    record_parse(mids.len() as u64 - errors - redacted, errors);
+    record_redacted(redacted);
+    let mids: Vec<Mid> =
+        mids.into_iter().collect::<Result<_, _>>().map_err(PyValueError::new_err)?;
+
+    // The anonymizer is shared by reference: its integrity table locks per shard
+    let anon_guard = ANONYMIZER.read().unwrap();
+    let anonymized: Vec<(Vec<Option<String>>, u128)> = in_parse_pool(|| {
+        mids.par_iter()
+            .map(|m| {
+                let t0 = Instant::now();
+                let repl = match anon_guard.as_ref() {
+                    Some(a) => {
+                        let values: Vec<Option<&str>> = (0..m.names.len())
+                            .map(|i| m.fields.get(i).map(|s| s.as_str()))
+                            .collect();
+                        a.anonymize_record_filtered(m.names, &values, &filter)
+                    }
+                    None => vec![None; m.names.len()],
+                };
+                (repl, t0.elapsed().as_nanos())
+            })
+            .collect()
+    });
+    drop(anon_guard);
+
+    let mut out = Vec::with_capacity(lines.len());
+    for ((line, m), (repl, anonymize_ns)) in lines.iter().zip(&mids).zip(anonymized) {
+        let parsed = PyDict::new(py);
+        let mut changed = Vec::new();
+        for (i, (name, r)) in m.names.iter().zip(repl).enumerate() {
+            let name = PyString::intern(py, name);
+            match r {
+                Some(r) => {
+                    parsed.set_item(&name, r)?;
+                    changed.push(name);
+                }
+                None => parsed.set_item(name, m.fields.get(i))?,
+            }
+        }
+        let d = PyDict::new(py);
+        d.set_item("parsed", parsed)?;
+        let excerpt =
+            if m.redacted { core::REDACTED_EXCERPT } else { core::raw_excerpt(line, excerpt_len) };
+        d.set_item("raw_excerpt", excerpt)?;
+        d.set_item("hash64", core::hash64(line.as_bytes()) as u128)?;
+        d.set_item("_anonymized", !changed.is_empty())?;
+        d.set_item("_anonymized_fields", changed)?;
+        d.set_item("parse_ns", m.parse_ns)?;
+        d.set_item("anonymize_ns", anonymize_ns)?;
+        d.set_item("runtime_ns_total", m.parse_ns + anonymize_ns)?;
+        out.push(d.unbind());
+    }
+    Ok(out)
+}
+
+/// Parse a file into NDJSON enriched records. Lines are parsed in parallel in
+/// chunks and written in input order; empty, malformed and unknown-type lines
+/// are skipped. With `provenance`, each record also carries its 1-based
+/// "line_no" and the "byte_offset" of the line start in the input file.
+/// Paths ending in `.gz` are read/written gzip-compressed; `compressed`
+/// overrides the suffix check for both files.
+///
+/// Returns a dict of counts: "records" written, "unknown" lines whose type is
+/// not in the schema, and "long_lines" skipped for length. With
+/// `emit_unknown`, unknown-type lines are written as {"_unknown_type",
+/// "raw_excerpt", "hash64"} instead of skipped; "records" still counts parsed
+/// records only. `record_id` adds "record_id" as for parse_kv_enriched().
+///
+/// To resume an interrupted run, pass `append=True` to add to the existing
+/// output instead of truncating it, and `skip_lines` to pass over the input
+/// lines already converted; only newly written records are counted.
+///
+/// Lines longer than `max_line_bytes` (16 MiB unless given) are skipped
+/// without being read into memory.
+#[pyfunction]
+#[pyo3(
+    signature = (
+        input_path,
+        output_path,
+        provenance = false,
+        compressed = None,
+        excerpt_len = core::DEFAULT_EXCERPT_LEN,
+        emit_unknown = false,
+        record_id = false,
+        append = false,
+        skip_lines = 0,
+        max_line_bytes = None
+    ),
+    text_signature = "(input_path, output_path, provenance=False, compressed=None, excerpt_len=256, emit_unknown=False, record_id=False, append=False, skip_lines=0, max_line_bytes=None)"
+)]
+#[allow(clippy::too_many_arguments)]
+fn parse_file_to_ndjson(
+    py: Python,
+    input_path: &str,
+    output_path: &str,
+    provenance: bool,
+    compressed: Option<bool>,
+    excerpt_len: usize,
+    emit_unknown: bool,
+    record_id: bool,
+    append: bool,
+    skip_lines: usize,
+    max_line_bytes: Option<usize>,
+) -> PyResult<Py<PyDict>> {
+    // Ensure schema is loaded
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+
+    let reader = core::open_input(input_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let writer = if append {
+        core::OutputFile::append(output_path, compressed)
+    } else {
+        core::OutputFile::create(output_path, compressed)
+    };
+    let mut writer = writer.map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    let unknown = std::sync::atomic::AtomicUsize::new(0);
+    let opts = core::EnrichedLineOptions {
+        provenance,
+        excerpt_len,
+        record_id,
+        emit_unknown,
+        unknown: Some(&unknown),
+        ..Default::default()
+    };
+    let chunk_lines = core::DEFAULT_CHUNK_LINES;
+    let max_bytes = max_line_bytes.unwrap_or(core::DEFAULT_MAX_LINE_BYTES);
+    let converted = in_parse_pool(|| {
+        core::transform_lines_bounded(
+            reader,
+            &mut writer,
+            chunk_lines,
+            skip_lines,
+            max_bytes,
+            |src| core::enriched_line(src, schema, &opts),
+        )
+    });
+    let (count, long_lines) = converted.map_err(|e| PyValueError::new_err(e.to_string()))?;
+    writer.finish().map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let unknown = unknown.into_inner();
+    let d = PyDict::new(py);
+    d.set_item("records", if emit_unknown { count - unknown } else { count })?;
+    d.set_item("unknown", unknown)?;
+    d.set_item("long_lines", long_lines)?;
+    Ok(d.unbind())
+}
+
+/// Read log lines from the process's stdin until EOF and write an enriched
+/// NDJSON record per line to its stdout, e.g. for `cat logs | python -c
+/// "import logparse_rs as lp; lp.load_schema(...); lp.parse_stdin_to_stdout()"`.
+/// Empty, malformed and unknown-type lines are skipped, as in
+/// parse_file_to_ndjson. Returns the number of records written. The file
+/// descriptors are used directly, bypassing `sys.stdin`/`sys.stdout`; pending
+/// `sys.stdout` output is flushed first so it keeps its place.
+#[pyfunction]
+#[pyo3(text_signature = "()")]
+fn parse_stdin_to_stdout(py: Python) -> PyResult<usize> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    py.import("sys")?.getattr("stdout")?.call_method0("flush")?;
+    let count = in_parse_pool(|| {
+        let mut writer = std::io::BufWriter::new(std::io::stdout().lock());
+        core::filter_stream(std::io::stdin().lock(), &mut writer, schema)
+    });
+    count.map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Parse a file into length-prefixed MessagePack frames for IPC: each record
+/// is a 4-byte big-endian length followed by a map shaped like the enriched
+/// JSON (parsed, raw_excerpt, hash64, runtime_ns). Empty, malformed and
+/// unknown-type lines are skipped; `compressed` as for parse_file_to_ndjson.
+/// Returns the number of records written.
+#[cfg(feature = "msgpack")]
+#[pyfunction]
+#[pyo3(
+    signature = (input_path, output_path, compressed = None, excerpt_len = core::DEFAULT_EXCERPT_LEN),
+    text_signature = "(input_path, output_path, compressed=None, excerpt_len=256)"
+)]
+fn parse_file_to_msgpack(
+    input_path: &str,
+    output_path: &str,
+    compressed: Option<bool>,
+    excerpt_len: usize,
+) -> PyResult<usize> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+
+    let reader = core::open_input(input_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut writer = core::OutputFile::create(output_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let frame = |src: &core::SourceLine| {
+        if src.text.is_empty() {
+            return None;
+        }
+        let (rec, _) = parse_enriched(schema, &src.text, Some(excerpt_len)).ok()?;
+        Some(core::to_msgpack(&rec))
+    };
+    let chunk_lines = core::DEFAULT_CHUNK_LINES;
+    let count = in_parse_pool(|| core::transform_frames(reader, &mut writer, chunk_lines, frame))
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    writer.finish().map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(count)
+}
+
+/// Iterator returned by parse_file_iter. Holds the open file and a snapshot of
+/// the schema taken when it was created, so reloading the schema does not
+/// affect an iteration in progress (and is not blocked by it).
+#[pyclass(module = "logparse_rs")]
+struct ParsedFileIter {
+    lines: Mutex<core::EnrichedLines<Box<dyn BufRead + Send>, Arc<LoadedSchema>>>,
+}
+
+#[pymethods]
+impl ParsedFileIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python) -> PyResult<Option<Py<PyDict>>> {
+        let mut lines = self.lines.lock().unwrap();
+        let Some(next) = lines.next_with_layout() else { return Ok(None) };
+        let (_, rec, (names, _)) = next.map_err(|e| PyValueError::new_err(e.to_string()))?;
+        enriched_to_pydict(py, &rec, (names, None)).map(Some)
+    }
+}
+
+/// Lazily parse a file into enriched dicts, one line at a time, skipping empty,
+/// malformed and unknown-type lines like parse_file_to_ndjson. Values are
+/// strings, as in the NDJSON output. `compressed` as for parse_file_to_ndjson.
+#[pyfunction]
+#[pyo3(
+    signature = (input_path, compressed = None, excerpt_len = core::DEFAULT_EXCERPT_LEN),
+    text_signature = "(input_path, compressed=None, excerpt_len=256)"
+)]
+fn parse_file_iter(
+    input_path: &str,
+    compressed: Option<bool>,
+    excerpt_len: usize,
+) -> PyResult<ParsedFileIter> {
+    let schema = SCHEMA_CACHE
+        .read()
+        .unwrap()
+        .get(DEFAULT_SCHEMA_NAME)
+        .cloned()
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let reader = core::open_input(input_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let lines = core::EnrichedLines::new(reader, Arc::new(schema), excerpt_len);
+    Ok(ParsedFileIter { lines: Mutex::new(lines) })
+}
+
+/// Like parse_file_to_ndjson, but only emits lines whose type (schema type index) is in
+/// `types`. The type is checked before the full split, so discarded lines cost
+/// only the type extraction. `compressed` as for parse_file_to_ndjson. Returns
+/// the number of records written.
+#[pyfunction]
+#[pyo3(
+    signature = (
+        input_path,
+        output_path,
+        types,
+        provenance = false,
+        excerpt_len = core::DEFAULT_EXCERPT_LEN,
+        compressed = None
+    ),
+    text_signature = "(input_path, output_path, types, provenance=False, excerpt_len=256, compressed=None)"
+)]
+fn parse_file_filtered(
+    input_path: &str,
+    output_path: &str,
+    types: Vec<String>,
+    provenance: bool,
+    excerpt_len: usize,
+    compressed: Option<bool>,
+) -> PyResult<usize> {
+    use std::collections::HashSet;
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let wanted: HashSet<String> = types.into_iter().collect();
+    let opts = core::EnrichedLineOptions {
+        provenance,
+        excerpt_len,
+        types: Some(&wanted),
+        ..Default::default()
+    };
+
+    let reader = core::open_input(input_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let mut writer = core::OutputFile::create(output_path, compressed)
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let count = in_parse_pool(|| {
+        core::transform_lines(reader, &mut writer, core::DEFAULT_CHUNK_LINES, |src| {
+            core::enriched_line(src, schema, &opts)
+        })
+    });
+    let count = count.map_err(|e| PyValueError::new_err(e.to_string()))?;
+    writer.finish().map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(count)
+}
+
+/// Serialize a parsed dict as a logfmt line (`key=value key2="value 2"`).
+/// None values are omitted; other non-string values use their str() form.
+#[pyfunction]
+#[pyo3(text_signature = "(parsed)")]
+fn to_logfmt(parsed: &Bound<PyDict>) -> PyResult<String> {
+    let mut pairs: Vec<(String, Option<String>)> = Vec::with_capacity(parsed.len());
+    for (k, v) in parsed.iter() {
+        let value = if v.is_none() { None } else { Some(v.str()?.to_string()) };
+        pairs.push((k.str()?.to_string(), value));
+    }
+    Ok(core::to_logfmt(pairs.iter().map(|(k, v)| (k.as_str(), v.as_deref()))))
+}
+
+/// Parse a line with the loaded schema and serialize it as an ArcSight CEF
+/// line. Header fields are set per call; `signature_id` and `name` default to
+/// the record's log type and `version` to this package's version.
+#[pyfunction]
+#[pyo3(
+    signature = (line, vendor = "logparse_rs", product = "logparse_rs", version = None, signature_id = None, name = None, severity = "Unknown"),
+    text_signature = "(line, vendor='logparse_rs', product='logparse_rs', version=None, signature_id=None, name=None, severity='Unknown')"
+)]
+fn parse_to_cef(
+    line: &str,
+    vendor: &str,
+    product: &str,
+    version: Option<&str>,
+    signature_id: Option<&str>,
+    name: Option<&str>,
+    severity: &str,
+) -> PyResult<String> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let map = core::parse_line_to_map(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+    let t = schema.log_type(line).unwrap_or_default();
+    let header = core::CefHeader {
+        vendor: vendor.to_string(),
+        product: product.to_string(),
+        version: version.unwrap_or(env!("CARGO_PKG_VERSION")).to_string(),
+        signature_id: signature_id.unwrap_or(&t).to_string(),
+        name: name.unwrap_or(&t).to_string(),
+        severity: severity.to_string(),
+    };
+    Ok(core::to_cef(&map, header))
+}
+
+/// Parse a line with the loaded schema and serialize it as an IBM QRadar LEEF
+/// 2.0 line with `delimiter`-separated attributes (tab by default).
+/// `event_id` defaults to the record's log type and `version` to this
+/// package's version.
+#[pyfunction]
+#[pyo3(
+    signature = (line, vendor = "logparse_rs", product = "logparse_rs", version = None, event_id = None, delimiter = "\t"),
+    text_signature = "(line, vendor='logparse_rs', product='logparse_rs', version=None, event_id=None, delimiter='\\t')"
+)]
+fn parse_to_leef(
+    line: &str,
+    vendor: &str,
+    product: &str,
+    version: Option<&str>,
+    event_id: Option<&str>,
+    delimiter: &str,
+) -> PyResult<String> {
+    let mut chars = delimiter.chars();
+    let delimiter = match (chars.next(), chars.next()) {
+        (Some(c), None) if !matches!(c, '=' | '\\' | '\n' | '\r') => c,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "delimiter must be one character other than '=', '\\' or a line break, got {:?}",
+                delimiter
+            )))
+        }
+    };
+    let guard = SCHEMA_CACHE.read().unwrap();
+    let schema = guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
+    let map = core::parse_line_to_map(line, schema).map_err(|e| {
+        record_parse(0, 1);
+        PyValueError::new_err(e)
+    })?;
+    record_parse(1, 0);
+    let t = schema.log_type(line).unwrap_or_default();
+    let header = core::LeefHeader {
+        vendor: vendor.to_string(),
+        product: product.to_string(),
+        version: version.unwrap_or(env!("CARGO_PKG_VERSION")).to_string(),
+        event_id: event_id.unwrap_or(&t).to_string(),
+        delimiter,
+    };
+    Ok(core::to_leef(&map, header))
 }
 
+/// Parse a file line by line and write one logfmt line per record. Malformed
+/// and unknown-type lines are skipped. Returns the number of records written.
 #[pyfunction]
 #[pyo3(text_signature = "(input_path, output_path)")]
-fn parse_file_to_ndjson(input_path: &str, output_path: &str) -> PyResult<usize> {
+fn parse_file_to_logfmt(input_path: &str, output_path: &str) -> PyResult<usize> {
     use std::io::{BufRead, BufReader, BufWriter, Write};
-    // Ensure schema is loaded
     let guard = SCHEMA_CACHE.read().unwrap();
     let schema = guard
-        .as_ref()
+        .get(DEFAULT_SCHEMA_NAME)
         .ok_or_else(|| PyValueError::new_err("No schema loaded. Call load_schema() first."))?;
 
     let infile =
         std::fs::File::open(input_path).map_err(|e| PyValueError::new_err(e.to_string()))?;
-    let mut outfile =
+    let outfile =
         std::fs::File::create(output_path).map_err(|e| PyValueError::new_err(e.to_string()))?;
     let reader = BufReader::new(infile);
-    let mut writer = BufWriter::new(&mut outfile);
+    let mut writer = BufWriter::new(outfile);
 
     let mut count: usize = 0;
     for line_res in reader.lines() {
@@ -412,49 +2287,16 @@ fn parse_file_to_ndjson(input_path: &str, output_path: &str) -> PyResult<usize>
         if line.is_empty() {
             continue;
         }
-        let t0 = Instant::now();
-        let t = match core::extract_field_internal(&line, 3) {
-            Some(s) => s,
-            None => continue, // skip malformed lines
-        };
-        let names = match schema.type_to_fields.get(&t) {
-            Some(n) => n,
-            None => continue, // unknown type; skip
-        };
-        let fields = core::split_csv_internal(&line);
-        let runtime_ns = t0.elapsed().as_nanos() as u128;
-
-        // Build JSON object directly using serde_json::Map to minimize allocations
-        let mut parsed = serde_json::Map::with_capacity(names.len());
-        for (i, name) in names.iter().enumerate() {
-            if i < fields.len() {
-                parsed.insert(name.clone(), serde_json::Value::String(fields[i].clone()));
-            } else {
-                parsed.insert(name.clone(), serde_json::Value::Null);
-            }
+        let Ok(t) = schema.log_type(&line) else { continue };
+        let fields = core::split_fields_for_type(&line, schema, &t);
+        let Some(names) = schema.fields_for_record(&t, &fields) else { continue };
+        if schema.check_overflow(&t, fields.len(), names.len()).is_err() {
+            continue;
         }
-        // Enriched payload aligns to parse_kv_enriched()
-        let max_len = std::cmp::min(256, line.len());
-        let mut root = serde_json::Map::with_capacity(4);
-        root.insert("parsed".to_string(), serde_json::Value::Object(parsed));
-        root.insert(
-            "raw_excerpt".to_string(),
-            serde_json::Value::String(line[..max_len].to_string()),
-        );
-        root.insert(
-            "hash64".to_string(),
-            serde_json::Value::Number(serde_json::Number::from(
-                core::hash64_fnv1a(line.as_bytes()) as u64,
-            )),
+        let out = core::to_logfmt(
+            names.iter().enumerate().map(|(i, n)| (n.as_str(), fields.get(i).map(|s| s.as_str()))),
         );
-        root.insert(
-            "runtime_ns".to_string(),
-            serde_json::Value::Number(serde_json::Number::from(runtime_ns as u64)),
-        );
-
-        let value = serde_json::Value::Object(root);
-        serde_json::to_writer(&mut writer, &value)
-            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        writer.write_all(out.as_bytes()).map_err(|e| PyValueError::new_err(e.to_string()))?;
         writer.write_all(b"\n").map_err(|e| PyValueError::new_err(e.to_string()))?;
         count += 1;
     }
@@ -462,6 +2304,32 @@ fn parse_file_to_ndjson(input_path: &str, output_path: &str) -> PyResult<usize>
     Ok(count)
 }
 
+/// Estimate the size and shape of a file from its first `sample_n` lines:
+/// {"file_bytes", "sampled_lines", "avg_line_bytes", "estimated_lines",
+/// "sampled_to_eof", "distinct_types"}. Only the sample is read.
+#[pyfunction]
+#[pyo3(signature = (input_path, sample_n = 1000), text_signature = "(input_path, sample_n=1000)")]
+fn estimate_file(py: Python, input_path: &str, sample_n: usize) -> PyResult<Py<PyDict>> {
+    let est = core::estimate_file(input_path, sample_n, current_type_index())
+        .map_err(PyValueError::new_err)?;
+    let d = PyDict::new(py);
+    d.set_item("file_bytes", est.file_bytes)?;
+    d.set_item("sampled_lines", est.sampled_lines)?;
+    d.set_item("avg_line_bytes", est.avg_line_bytes)?;
+    d.set_item("estimated_lines", est.estimated_lines)?;
+    d.set_item("sampled_to_eof", est.sampled_to_eof)?;
+    d.set_item("distinct_types", est.distinct_types)?;
+    Ok(d.unbind())
+}
+
+/// Histogram of fields per line over a whole file: {field_count: line_count}.
+/// Counts are quote-aware and comma-delimited; no schema mapping is done.
+#[pyfunction]
+#[pyo3(text_signature = "(input_path)")]
+fn field_count_histogram(input_path: &str) -> PyResult<std::collections::BTreeMap<usize, u64>> {
+    core::field_count_histogram(input_path).map_err(PyValueError::new_err)
+}
+
 #[pymodule]
 #[pyo3(module = "logparse_rs")]
 fn logparse_rs(_py: Python, m: &Bound<PyModule>) -> PyResult<()> {
@@ -481,26 +2349,81 @@ fn logparse_rs(_py: Python, m: &Bound<PyModule>) -> PyResult<()> {
 
     // Schema-driven parsing APIs
     m.add_function(wrap_pyfunction!(load_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(load_schema_json, m)?)?;
+    m.add_function(wrap_pyfunction!(load_schema_named, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_schema_autoreload, m)?)?;
+    m.add_function(wrap_pyfunction!(disable_schema_autoreload, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_with_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_line_with_schema_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_named, m)?)?;
+    m.add_class::<Parser>()?;
+    m.add_function(wrap_pyfunction!(parse_kv_strict, m)?)?;
+    m.add("ParseError", m.py().get_type::<ParseError>())?;
+    m.add_function(wrap_pyfunction!(parse_kv_typed, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_into, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_enriched, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_enriched_with_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_enriched_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(set_hash_algo, m)?)?;
+    m.add_function(wrap_pyfunction!(get_hash_algo, m)?)?;
+    m.add_function(wrap_pyfunction!(set_parse_threads, m)?)?;
     m.add_function(wrap_pyfunction!(get_schema_status, m)?)?;
+    m.add_function(wrap_pyfunction!(update_log_type, m)?)?;
+    m.add_function(wrap_pyfunction!(remove_log_type, m)?)?;
+    m.add_function(wrap_pyfunction!(get_schema_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(list_schema_types, m)?)?;
+    m.add_function(wrap_pyfunction!(get_schema_warnings, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_enriched_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_enriched_batch_dedup, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_batch_dict_encoded, m)?)?;
+    #[cfg(feature = "arrow")]
+    m.add_function(wrap_pyfunction!(parse_to_arrow, m)?)?;
+    #[cfg(feature = "pandas")]
+    m.add_function(wrap_pyfunction!(parse_to_dataframe, m)?)?;
     m.add_function(wrap_pyfunction!(parse_file_to_ndjson, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_stdin_to_stdout, m)?)?;
+    #[cfg(feature = "msgpack")]
+    m.add_function(wrap_pyfunction!(parse_file_to_msgpack, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_file_iter, m)?)?;
+    m.add_class::<ParsedFileIter>()?;
+    m.add_function(wrap_pyfunction!(parse_file_filtered, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_file_to_logfmt, m)?)?;
+    m.add_function(wrap_pyfunction!(to_logfmt, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_to_cef, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_to_leef, m)?)?;
+    m.add_function(wrap_pyfunction!(estimate_file, m)?)?;
+    m.add_function(wrap_pyfunction!(field_count_histogram, m)?)?;
 
     // CSV helpers
     m.add_function(wrap_pyfunction!(extract_field, m)?)?;
     m.add_function(wrap_pyfunction!(extract_type_subtype, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_source, m)?)?;
     m.add_function(wrap_pyfunction!(split_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(join_csv, m)?)?;
+    m.add_function(wrap_pyfunction!(split_csv_with_flags, m)?)?;
+    m.add_function(wrap_pyfunction!(split_csv_spans, m)?)?;
+    m.add_function(wrap_pyfunction!(count_fields, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_pairs, m)?)?;
 
     // Anonymizer APIs
     m.add_function(wrap_pyfunction!(load_anonymizer, m)?)?;
     m.add_function(wrap_pyfunction!(set_anonymizer_json, m)?)?;
+    m.add_function(wrap_pyfunction!(rotate_anonymizer, m)?)?;
     m.add_function(wrap_pyfunction!(get_anonymizer_status, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics_snapshot, m)?)?;
     m.add_function(wrap_pyfunction!(export_integrity_table, m)?)?;
+    m.add_function(wrap_pyfunction!(deanonymize, m)?)?;
+    m.add_function(wrap_pyfunction!(preview_anonymize, m)?)?;
+    m.add_function(wrap_pyfunction!(anonymize_dict, m)?)?;
+    m.add_function(wrap_pyfunction!(reset_anonymizer, m)?)?;
+    m.add_function(wrap_pyfunction!(clear_anonymizer_field, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_enriched_anon, m)?)?;
     m.add_function(wrap_pyfunction!(parse_kv_enriched_with_schema_anon, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_kv_enriched_anon_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(anonymize_batch_with_deltas, m)?)?;
 
     // Optional: preload schema from env var for a faster startup in hot paths.
     if let Ok(path) = std::env::var("LOGPARSE_PRELOAD_SCHEMA")
@@ -509,7 +2432,7 @@ fn logparse_rs(_py: Python, m: &Bound<PyModule>) -> PyResult<()> {
     {
         if let Ok(loaded) = core::load_schema_internal(&path) {
             let mut guard = SCHEMA_CACHE.write().unwrap();
-            *guard = Some(loaded);
+            guard.insert(DEFAULT_SCHEMA_NAME.to_string(), loaded);
         }
     }
 
@@ -517,11 +2440,9 @@ fn logparse_rs(_py: Python, m: &Bound<PyModule>) -> PyResult<()> {
     if let Ok(anon_path) =
         std::env::var("LOGPARSE_ANON_CONFIG").or_else(|_| std::env::var("PAN_RUST_ANON_CONFIG"))
     {
-        if let Ok(json) = std::fs::read_to_string(&anon_path) {
-            if let Ok(anon) = core::anonymizer_from_json(&json) {
-                let mut g = ANONYMIZER.write().unwrap();
-                *g = Some(anon);
-            }
+        if let Ok(anon) = read_anonymizer(&anon_path, false, false) {
+            let mut g = ANONYMIZER.write().unwrap();
+            *g = Some(anon);
         }
     }
 
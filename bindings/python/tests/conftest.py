@@ -0,0 +1,22 @@
+import json
+
+import pytest
+
+import logparse_rs as lp
+
+TRAFFIC_FIELDS = ["serial", "time", "vsys", "type", "src", "dst", "action"]
+
+
+@pytest.fixture
+def log_types():
+    """Log types for the default schema; override in a module for another layout."""
+    return {"traffic": {"type_value": "TRAFFIC", "fields": TRAFFIC_FIELDS}}
+
+
+@pytest.fixture(autouse=True)
+def schema(tmp_path, log_types):
+    path = tmp_path / "schema.json"
+    path.write_text(json.dumps({"palo_alto_syslog_fields": {"log_types": log_types}}))
+    lp.load_schema(str(path))
+    yield
+    lp.set_parse_threads(0)
@@ -0,0 +1,77 @@
+import json
+
+import pytest
+
+import logparse_rs as lp
+
+ANON = {
+    "version": 1,
+    "defaults": {"mode": "tokenize", "tokenize": {"prefix": "T_", "salt": "pepper"}},
+    "fields": {
+        "src": {"mode": "tokenize", "tokenize": {"prefix": "IP_"}},
+        "user": {"mode": "counter", "tokenize": {"prefix": "user_"}},
+    },
+}
+
+
+@pytest.fixture
+def log_types():
+    fields = ["serial", "time", "vsys", "type", "src", "user", "action"]
+    return {"traffic": {"type_value": "TRAFFIC", "fields": fields}}
+
+
+@pytest.fixture(autouse=True)
+def anonymizer(schema):
+    lp.set_anonymizer_json(json.dumps(ANON))
+
+
+def test_anonymize_dict_matches_one_shot():
+    lines = [
+        "1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1,alice,allow",
+        "2,2025/10/12 05:07:30,001,TRAFFIC,10.0.0.2,bob",
+    ]
+    for line in lines:
+        parsed = lp.parse_kv(line)
+        staged = lp.anonymize_dict(parsed)
+        assert staged == lp.parse_kv_enriched_anon(line)["parsed"]
+        assert staged["src"].startswith("IP_") and staged["user"].startswith("user_")
+        # The input dict is not modified
+        assert parsed == lp.parse_kv(line)
+    assert lp.anonymize_dict(lp.parse_kv(lines[1]))["action"] is None
+
+
+def test_redact_unknown_type_keeps_hash():
+    lp.set_anonymizer_json(json.dumps({**ANON, "redact_unknown": True}))
+    line = "3,2025/10/12 05:07:31,001,NOPE,10.0.0.3,carol,deny"
+    before = lp.metrics_snapshot()
+    rec = lp.parse_kv_enriched_anon(line)
+    after = lp.metrics_snapshot()
+    assert after["parse_lines_redacted"] == before["parse_lines_redacted"] + 1
+    assert after["parse_lines_ok"] == before["parse_lines_ok"]
+    assert rec["raw_excerpt"] == "[REDACTED]"
+    assert rec["parsed"] == {}
+    assert rec["hash64"] != lp.parse_kv_enriched_anon(line + "x")["hash64"]
+    [batch] = lp.parse_kv_enriched_anon_batch([line])
+    assert (batch["raw_excerpt"], batch["hash64"]) == ("[REDACTED]", rec["hash64"])
+
+    lp.set_anonymizer_json(json.dumps(ANON))
+    with pytest.raises(ValueError):
+        lp.parse_kv_enriched_anon(line)
+
+
+def test_include_fields_limits_anonymization():
+    fields = {**ANON["fields"], "vsys": {"mode": "fixed", "fixed": "V"}}
+    lp.set_anonymizer_json(json.dumps({**ANON, "fields": fields}))
+    line = "4,2025/10/12 05:07:32,001,TRAFFIC,10.0.0.4,dave,allow"
+    rec = lp.parse_kv_enriched_anon(line, include_fields=["src"])
+    assert rec["_anonymized_fields"] == ["src"]
+    assert rec["parsed"]["src"].startswith("IP_")
+    assert (rec["parsed"]["user"], rec["parsed"]["vsys"]) == ("dave", "001")
+    [batch] = lp.parse_kv_enriched_anon_batch([line], include_fields=["src"])
+    assert batch["parsed"] == rec["parsed"]
+
+    # Exclude wins over include
+    parsed = lp.parse_kv(line)
+    out = lp.anonymize_dict(parsed, include_fields=["src", "user"], exclude_fields=["src"])
+    assert out["src"] == "10.0.0.4"
+    assert out["user"].startswith("user_")
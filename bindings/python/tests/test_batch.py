@@ -0,0 +1,95 @@
+import gzip
+import json
+
+import logparse_rs as lp
+
+
+def test_single_thread_pool_matches_global_pool():
+    lines = [
+        "%d,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.%d,10.0.1.1,allow" % (i, i % 250)
+        for i in range(2000)
+    ]
+    lines[7] = "7,2025/10/12 05:07:29,001"
+
+    def run():
+        enriched = lp.parse_kv_enriched_batch(lines, strict=False)
+        for rec in enriched:
+            rec.pop("runtime_ns", None)
+        arena = lp.parse_kv_enriched_batch(lines, arena_chunk=64, strict=False)
+        for rec in arena:
+            rec.pop("runtime_ns", None)
+        return lp.parse_kv_batch(lines, strict=False), enriched, arena
+
+    lp.set_parse_threads(0)
+    expected = run()
+    lp.set_parse_threads(1)
+    assert run() == expected
+    lp.set_parse_threads(4)
+    assert run() == expected
+    assert expected[0][7]["line_index"] == 7
+
+
+def test_enriched_batch_dedup_keeps_first_copy():
+    a = "1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1,10.0.1.1,allow"
+    b = "2,2025/10/12 05:07:30,001,TRAFFIC,10.0.0.2,10.0.1.1,deny"
+    records, dropped = lp.parse_kv_enriched_batch_dedup([a, b, a, a, b])
+    assert dropped == 3
+    assert [r["parsed"]["serial"] for r in records] == ["1", "2"]
+    assert records[0]["hash64"] != records[1]["hash64"]
+
+
+def test_arena_batch_fills_schema_defaults(tmp_path):
+    action = {"name": "action", "default": "none"}
+    fields = ["serial", "time", "vsys", "type", "src", "dst", action]
+    log_types = {"traffic": {"type_value": "TRAFFIC", "fields": fields}}
+    path = tmp_path / "defaults.json"
+    path.write_text(json.dumps({"log_types": log_types}))
+    lp.load_schema(str(path))
+    lines = ["1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1", "2,2025/10/12 05:07:30,001,TRAFFIC"]
+
+    def parsed(**kw):
+        return [r["parsed"] for r in lp.parse_kv_enriched_batch(lines, **kw)]
+
+    assert parsed(arena_chunk=1) == parsed()
+    assert parsed(arena_chunk=1)[0]["action"] == "none"
+    assert parsed(arena_chunk=1)[1]["src"] is None
+
+
+def test_parse_file_filtered_reads_and_writes_gzip(tmp_path):
+    lines = [
+        "1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1,10.0.1.1,allow",
+        "2,2025/10/12 05:07:30,001,THREAT,10.0.0.2,10.0.1.1,block",
+        "3,2025/10/12 05:07:31,001,TRAFFIC,10.0.0.3,10.0.1.1,deny",
+    ]
+    text = "\n".join(lines) + "\n"
+    plain_in, gz_in = tmp_path / "in.csv", tmp_path / "in.csv.gz"
+    plain_in.write_text(text)
+    with gzip.open(gz_in, "wt") as f:
+        f.write(text)
+
+    plain_out, gz_out = tmp_path / "out.ndjson", tmp_path / "out.ndjson.gz"
+    assert lp.parse_file_filtered(str(plain_in), str(plain_out), ["TRAFFIC"]) == 2
+    assert lp.parse_file_filtered(str(gz_in), str(gz_out), ["TRAFFIC"], provenance=True) == 2
+    records = [json.loads(l) for l in gzip.open(gz_out, "rt")]
+    assert [r["parsed"]["serial"] for r in records] == ["1", "3"]
+    assert [r["line_no"] for r in records] == [1, 3]
+    plain = [json.loads(l) for l in plain_out.read_text().splitlines()]
+    assert [r["parsed"] for r in plain] == [r["parsed"] for r in records]
+
+
+def test_parse_file_to_ndjson_returns_counts(tmp_path):
+    lines = [
+        "1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1,10.0.1.1,allow",
+        "2,2025/10/12 05:07:30,001,THREAT,10.0.0.2,10.0.1.1,block",
+        "3,2025/10/12 05:07:31,001,TRAFFIC,10.0.0.3,10.0.1.1," + "x" * 100,
+        "4,2025/10/12 05:07:32,001,TRAFFIC,10.0.0.4,10.0.1.1,deny",
+    ]
+    src, out = tmp_path / "in.csv", tmp_path / "out.ndjson"
+    src.write_text("\n".join(lines) + "\n")
+
+    counts = lp.parse_file_to_ndjson(str(src), str(out))
+    assert counts == {"records": 3, "unknown": 1, "long_lines": 0}
+    counts = lp.parse_file_to_ndjson(str(src), str(out), emit_unknown=True, max_line_bytes=80)
+    assert counts == {"records": 2, "unknown": 1, "long_lines": 1}
+    records = [json.loads(l) for l in out.read_text().splitlines()]
+    assert [r.get("_unknown_type") for r in records] == [None, "THREAT", None]
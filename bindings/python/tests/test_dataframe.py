@@ -0,0 +1,44 @@
+import pytest
+
+import logparse_rs as lp
+
+pd = pytest.importorskip("pandas")
+
+pytestmark = pytest.mark.skipif(
+    not hasattr(lp, "parse_to_dataframe"), reason="built without the pandas feature"
+)
+
+@pytest.fixture
+def log_types():
+    traffic = [
+        {"name": "serial", "type": "int"},
+        "time",
+        "vsys",
+        "type",
+        "action",
+        {"name": "bytes", "type": "int"},
+    ]
+    return {
+        "traffic": {"type_value": "TRAFFIC", "fields": traffic},
+        "threat": {"type_value": "THREAT", "fields": ["serial", "time", "vsys", "type"]},
+    }
+
+
+def test_columns_and_rows():
+    lines = [
+        "1,2025/10/12 05:07:29,001,TRAFFIC,allow,100",
+        "2,2025/10/12 05:07:30,001,TRAFFIC,deny,250",
+        "3,2025/10/12 05:07:31,001,TRAFFIC,allow",
+    ]
+    df = lp.parse_to_dataframe(lines)
+    assert list(df.columns) == ["serial", "time", "vsys", "type", "action", "bytes"]
+    assert len(df) == 3
+    assert df["serial"].dtype == "int64"
+    assert df["action"].tolist() == ["allow", "deny", "allow"]
+    assert df["bytes"].isna().tolist() == [False, False, True]
+
+
+def test_mixed_log_types_raise():
+    lines = ["1,t,001,TRAFFIC,allow,1", "2,t,001,THREAT"]
+    with pytest.raises(ValueError, match="mixes log types"):
+        lp.parse_to_dataframe(lines)
@@ -0,0 +1,7 @@
+import logparse_rs as lp
+
+
+def test_parse_line_with_schema_dict():
+    schema = {"TRAFFIC": ["serial", "Receive Time"]}
+    got = lp.parse_line_with_schema_dict("001,2025/10/12 05:07:29,vsys1,TRAFFIC", schema)
+    assert got == {"serial": "001", "receive_time": "2025/10/12 05:07:29"}
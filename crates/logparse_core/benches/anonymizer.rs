@@ -0,0 +1,126 @@
+// benches/anonymizer.rs: anonymize_one throughput per mode, plus lock contention
+//
+// Run with `cargo bench -p logparse_core --bench anonymizer`.
+use std::sync::Mutex;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use logparse_core::{anonymizer_from_json, AnonymizerCore};
+
+const CONFIG: &str = r#"{
+  "version": 1,
+  "defaults": { "tokenize": { "prefix": "T_", "salt": "bench-salt" } },
+  "fields": {
+    "fixed": { "mode": "fixed", "fixed": "REDACTED" },
+    "tokenize": { "mode": "tokenize" },
+    "map_hit": { "mode": "map", "map": { "alice": "user_001", "bob": "user_002" } },
+    "map_fallback": { "mode": "map", "map": { "alice": "user_001" }, "fallback": "tokenize" }
+  }
+}"#;
+
+/// Values shaped like source IPs; `unique` controls the cardinality so that both
+/// the integrity-table hit path and the miss path (compute + insert) are covered.
+fn values(count: usize, unique: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| format!("10.{}.{}.{}", (i % unique) / 65536, (i % unique) / 256 % 256, i % 256))
+        .collect()
+}
+
+fn new_anon() -> AnonymizerCore {
+    anonymizer_from_json(CONFIG).expect("bench config")
+}
+
+fn bench_modes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("anonymize_one");
+    for &(label, unique) in &[("hits", 16usize), ("misses", 10_000usize)] {
+        let vals = values(10_000, unique);
+        group.throughput(Throughput::Elements(vals.len() as u64));
+        for field in ["fixed", "tokenize", "map_fallback"] {
+            group.bench_with_input(BenchmarkId::new(field, label), &vals, |b, vals| {
+                // Fresh table per iteration batch so "misses" really miss
+                b.iter_batched(
+                    new_anon,
+                    |anon| {
+                        for v in vals {
+                            black_box(anon.anonymize_one(field, v));
+                        }
+                    },
+                    criterion::BatchSize::LargeInput,
+                );
+            });
+        }
+    }
+    // Map lookups that always hit the configured map (no fallback tokenization)
+    let names: Vec<String> =
+        (0..10_000).map(|i| if i % 2 == 0 { "alice" } else { "bob" }.to_string()).collect();
+    group.throughput(Throughput::Elements(names.len() as u64));
+    group.bench_function(BenchmarkId::new("map_hit", "hits"), |b| {
+        b.iter_batched(
+            new_anon,
+            |anon| {
+                for v in &names {
+                    black_box(anon.anonymize_one("map_hit", v));
+                }
+            },
+            criterion::BatchSize::LargeInput,
+        );
+    });
+    group.finish();
+}
+
+/// Workers behind one global lock (the bindings' old batch path) versus
+/// calling `anonymize_one` on a shared instance, which locks only one shard of
+/// the integrity table per insert.
+fn bench_lock_contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("anonymize_one_contention");
+    let vals = values(20_000, 5_000);
+    group.throughput(Throughput::Elements(vals.len() as u64));
+    for threads in [1usize, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("global_lock", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_batched(
+                    || Mutex::new(new_anon()),
+                    |lock| {
+                        let chunk = vals.len().div_ceil(threads);
+                        std::thread::scope(|s| {
+                            for part in vals.chunks(chunk) {
+                                let lock = &lock;
+                                s.spawn(move || {
+                                    for v in part {
+                                        let g = lock.lock().unwrap();
+                                        black_box(g.anonymize_one("tokenize", v));
+                                    }
+                                });
+                            }
+                        });
+                    },
+                    criterion::BatchSize::LargeInput,
+                );
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("sharded", threads), &threads, |b, &threads| {
+            b.iter_batched(
+                new_anon,
+                |anon| {
+                    let chunk = vals.len().div_ceil(threads);
+                    std::thread::scope(|s| {
+                        for part in vals.chunks(chunk) {
+                            let anon = &anon;
+                            s.spawn(move || {
+                                for v in part {
+                                    black_box(anon.anonymize_one("tokenize", v));
+                                }
+                            });
+                        }
+                    });
+                },
+                criterion::BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_modes, bench_lock_contention);
+criterion_main!(benches);
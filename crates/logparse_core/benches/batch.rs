@@ -0,0 +1,54 @@
+// benches/batch.rs: per-line String allocation vs a batch-scoped FieldArena
+//
+// Run with `cargo bench -p logparse_core --bench batch`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use logparse_core::{split_csv_internal, FieldArena};
+
+/// PAN-OS-like TRAFFIC lines: ~60 short fields with a couple of quoted ones.
+fn lines(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| {
+            let mut f: Vec<String> = vec![
+                "1".into(),
+                "2025/10/12 05:07:29".into(),
+                "001801000001".into(),
+                "TRAFFIC".into(),
+                "end".into(),
+            ];
+            f.push(format!("10.0.{}.{}", i % 256, (i / 256) % 256));
+            f.push("\"rule, with comma\"".into());
+            for j in 0..53 {
+                f.push(if j % 3 == 0 { String::new() } else { format!("v{}", j) });
+            }
+            f.join(",")
+        })
+        .collect()
+}
+
+fn bench_batch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batch_split");
+    for &n in &[1_000usize, 10_000] {
+        let input = lines(n);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("per_line_alloc", n), &input, |b, input| {
+            b.iter(|| {
+                let rows: Vec<Vec<String>> = input.iter().map(|l| split_csv_internal(l)).collect();
+                black_box(rows)
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("field_arena", n), &input, |b, input| {
+            b.iter(|| {
+                let bytes = input.iter().map(|l| l.len()).sum();
+                let mut arena = FieldArena::with_capacity(input.len(), bytes);
+                for l in input {
+                    arena.push_line(l);
+                }
+                black_box(arena)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_batch);
+criterion_main!(benches);
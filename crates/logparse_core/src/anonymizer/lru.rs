@@ -0,0 +1,73 @@
+// anonymizer/lru.rs: access order for integrity table fields with a size limit
+//
+// Tokenize replacements are a pure function of the value and salt, so for
+// those fields the table is only a cache and can drop its least recently
+// used pairs. Each access stamps the value with a counter; the stamp -> value
+// map is ordered, so the oldest pair is found without scanning the field.
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Default)]
+pub struct AccessOrder {
+    tick: u64,
+    by_value: HashMap<String, u64>,
+    by_tick: BTreeMap<u64, String>,
+}
+
+impl AccessOrder {
+    /// Mark `value` as the most recently used, adding it if new.
+    pub fn touch(&mut self, value: &str) {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.by_value.get_mut(value) {
+            Some(t) => {
+                let old = std::mem::replace(t, tick);
+                let v = self.by_tick.remove(&old).expect("ticks mirror values");
+                self.by_tick.insert(tick, v);
+            }
+            None => {
+                self.by_value.insert(value.to_string(), tick);
+                self.by_tick.insert(tick, value.to_string());
+            }
+        }
+    }
+
+    /// `touch` for a value already tracked; unknown values are ignored, so a
+    /// hit racing with the eviction of the same value does not bring it back.
+    pub fn touch_existing(&mut self, value: &str) {
+        if self.by_value.contains_key(value) {
+            self.touch(value);
+        }
+    }
+
+    /// Remove and return the least recently used value.
+    pub fn pop_oldest(&mut self) -> Option<String> {
+        let (_, value) = self.by_tick.pop_first()?;
+        self.by_value.remove(&value);
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_value.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccessOrder;
+
+    #[test]
+    fn test_access_order_pops_least_recently_used() {
+        let mut o = AccessOrder::default();
+        for v in ["a", "b", "c"] {
+            o.touch(v);
+        }
+        o.touch("a");
+        o.touch_existing("z");
+        assert_eq!(o.len(), 3);
+        assert_eq!(o.pop_oldest().as_deref(), Some("b"));
+        assert_eq!(o.pop_oldest().as_deref(), Some("c"));
+        assert_eq!(o.pop_oldest().as_deref(), Some("a"));
+        assert!(o.pop_oldest().is_none());
+        assert_eq!(o.len(), 0);
+    }
+}
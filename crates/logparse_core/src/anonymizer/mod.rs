@@ -1,7 +1,10 @@
+mod lru;
 pub mod rules;
+pub mod shards;
 pub mod table;
 
 pub use rules::*;
+pub use shards::*;
 pub use table::*;
 
 /// Construct an AnonymizerCore from JSON config
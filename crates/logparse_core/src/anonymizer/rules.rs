@@ -1,11 +1,32 @@
 // anonymizer/rules.rs: configuration types for anonymization
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Deserialize, Clone, Default)]
 pub struct TokenizeCfg {
     pub prefix: Option<String>,
     pub salt: Option<String>,
+    /// Characters used to render the token hash instead of lowercase hex.
+    /// Each character carries log2(len) bits, so smaller alphabets produce
+    /// longer tokens for the same 64-bit hash (e.g. 16 chars -> 16 digits,
+    /// 32 chars -> 13 digits, 10 chars -> 20 digits).
+    pub token_alphabet: Option<String>,
+    /// Hash behind the token (default `fnv1a`); inherited from `defaults`
+    pub algorithm: Option<TokenAlgorithm>,
+    /// Salt epoch embedded in tokens after the prefix (`T_v2_...`), so tokens
+    /// from different salts are distinguishable; inherited from `defaults`
+    pub key_version: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenAlgorithm {
+    /// Salted FNV-1a 64: fast, but not keyed, so small value spaces can be
+    /// brute-forced back to their originals
+    #[default]
+    Fnv1a,
+    /// HMAC-SHA256 keyed with the salt, truncated to 64 bits
+    HmacSha256,
 }
 
 #[derive(Deserialize, Clone)]
@@ -22,6 +43,36 @@ pub enum Mode {
     Fixed,
     Map,
     Tokenize,
+    /// Keep the network prefix of an IP address and replace the host bits
+    #[serde(rename = "ip_mask")]
+    IpMask,
+    /// Sequential pseudonyms per field: the tokenize prefix plus 1, 2, 3, ...
+    /// in first-seen order
+    Counter,
+    /// Move timestamps by a per-subject offset, keeping intervals within a subject
+    #[serde(rename = "date_shift")]
+    DateShift,
+}
+
+/// Settings for `date_shift` mode. Unset values fall back to the defaults.
+#[derive(Deserialize, Clone, Default)]
+pub struct DateShiftCfg {
+    /// strftime-style format used to parse and re-emit values
+    /// (default `%Y/%m/%d %H:%M:%S`)
+    pub format: Option<String>,
+    /// Offsets are whole days in `-max_days..=max_days` (default 365)
+    pub max_days: Option<u32>,
+    /// Field whose value in the same record picks the offset, e.g. a user or
+    /// patient ID. Without it every value of the field shifts by one offset.
+    pub subject_field: Option<String>,
+}
+
+/// Prefix lengths kept by `ip_mask` mode. Unset values fall back to the
+/// defaults, then to /24 for IPv4 and /64 for IPv6.
+#[derive(Deserialize, Clone, Default)]
+pub struct IpMaskCfg {
+    pub v4_prefix: Option<u8>,
+    pub v6_prefix: Option<u8>,
 }
 
 #[derive(Deserialize, Clone, Default)]
@@ -33,6 +84,13 @@ pub struct FieldRule {
     pub fallback: Option<FallbackMode>,
     #[serde(default)]
     pub tokenize: TokenizeCfg,
+    #[serde(default)]
+    pub ip_mask: IpMaskCfg,
+    #[serde(default)]
+    pub date_shift: DateShiftCfg,
+    /// Values left as they are whatever the mode, e.g. `0.0.0.0` or `-`
+    #[serde(default)]
+    pub passthrough: HashSet<String>,
 }
 
 #[derive(Deserialize, Clone, Default)]
@@ -41,13 +99,100 @@ pub struct Defaults {
     pub fixed: Option<String>,
     #[serde(default)]
     pub tokenize: TokenizeCfg,
+    #[serde(default)]
+    pub ip_mask: IpMaskCfg,
+    #[serde(default)]
+    pub date_shift: DateShiftCfg,
+}
+
+/// Content-based rule: every match of `pattern` inside a value is replaced in
+/// place, whatever field it appears in. Only `fixed` and `tokenize` apply.
+#[derive(Deserialize, Clone)]
+pub struct ValuePattern {
+    #[serde(alias = "regex")]
+    pub pattern: String,
+    pub mode: Mode,
+    pub fixed: Option<String>,
+    #[serde(default)]
+    pub tokenize: TokenizeCfg,
 }
 
 #[derive(Deserialize, Clone, Default)]
 pub struct AnonConfig {
     pub version: Option<u32>,
+    /// Observe-only: resolve and count rule decisions but return values unchanged
+    #[serde(default)]
+    pub dry_run: bool,
     #[serde(default)]
     pub defaults: Defaults,
     #[serde(default)]
     pub fields: HashMap<String, FieldRule>,
+    /// Enriched anonymized results for lines of a log type the schema does not
+    /// know carry a redacted excerpt and no fields instead of failing
+    #[serde(default)]
+    pub redact_unknown: bool,
+    /// Keep at most this many pairs per `tokenize` field in the integrity
+    /// table, evicting the least recently used; other modes are never evicted
+    pub max_entries_per_field: Option<usize>,
+    /// Applied, in order, to values of fields without an explicit rule and to
+    /// values that a field rule passed through
+    #[serde(default, alias = "patterns")]
+    pub value_patterns: Vec<ValuePattern>,
+}
+
+impl AnonConfig {
+    /// Check for rules that load but would silently fall back at runtime,
+    /// such as `fixed` mode without a `fixed` value (emits `REDACTED`) or an
+    /// empty `map` without a `fallback` (tokenizes everything). Returns every
+    /// problem found, separated by `; `.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        let default_fixed = self.defaults.fixed.is_some();
+        if matches!(self.defaults.mode, Some(Mode::Fixed)) && !default_fixed {
+            problems.push("defaults: mode fixed needs a fixed value".to_string());
+        }
+        if matches!(self.defaults.mode, Some(Mode::Map)) {
+            problems.push("defaults: mode map needs a per-field map".to_string());
+        }
+        let mut names: Vec<&String> = self.fields.keys().collect();
+        names.sort();
+        for name in names {
+            let rule = &self.fields[name];
+            let mode = rule.mode.as_ref().or(self.defaults.mode.as_ref());
+            let has_fixed = rule.fixed.is_some() || default_fixed;
+            if matches!(mode, Some(Mode::Fixed)) && !has_fixed {
+                problems.push(format!("field {:?}: mode fixed needs a fixed value", name));
+            }
+            if matches!(mode, Some(Mode::Map)) && rule.map.is_empty() && rule.fallback.is_none() {
+                problems
+                    .push(format!("field {:?}: mode map has an empty map and no fallback", name));
+            }
+            if !rule.map.is_empty() && !matches!(mode, Some(Mode::Map)) {
+                problems.push(format!("field {:?}: map is only used with mode map", name));
+            }
+            if let Some(fallback) = &rule.fallback {
+                if !matches!(mode, Some(Mode::Map | Mode::IpMask | Mode::DateShift)) {
+                    problems.push(format!(
+                        "field {:?}: fallback is only used with modes map, ip_mask and date_shift",
+                        name
+                    ));
+                } else if matches!(fallback, FallbackMode::Fixed) && !has_fixed {
+                    problems.push(format!("field {:?}: fallback fixed needs a fixed value", name));
+                }
+            }
+        }
+        for vp in &self.value_patterns {
+            if matches!(vp.mode, Mode::Fixed) && vp.fixed.is_none() {
+                problems.push(format!(
+                    "value_patterns entry {:?}: mode fixed needs a fixed value",
+                    vp.pattern
+                ));
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
 }
@@ -0,0 +1,171 @@
+// anonymizer/shards.rs: integrity table split across independently locked shards
+//
+// A single `RwLock<HashMap>` around the whole table serializes every insert, so
+// parallel batch anonymization spends its time waiting for the lock. Pairs are
+// instead spread over `SHARDS` maps by a hash of (field, original). Hashing the
+// original too, not just the field, keeps one hot field (say `src_ip`) from
+// funnelling every thread into the same shard.
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::RwLock;
+
+const SHARDS: usize = 32;
+
+/// field -> (original -> replacement)
+pub type FieldTable = HashMap<String, HashMap<String, String>>;
+
+pub struct IntegrityTable {
+    shards: Box<[RwLock<FieldTable>]>,
+    hasher: RandomState,
+}
+
+impl Default for IntegrityTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IntegrityTable {
+    pub fn new() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard(&self, field: &str, orig: &str) -> &RwLock<FieldTable> {
+        let mut h = self.hasher.build_hasher();
+        field.hash(&mut h);
+        orig.hash(&mut h);
+        &self.shards[h.finish() as usize % SHARDS]
+    }
+
+    /// Replacement recorded for `orig` in `field`, if any.
+    pub fn get(&self, field: &str, orig: &str) -> Option<String> {
+        let shard = self.shard(field, orig).read().unwrap();
+        shard.get(field).and_then(|m| m.get(orig)).cloned()
+    }
+
+    /// Record `orig -> repl` unless `orig` already has a replacement. Returns
+    /// the stored replacement and whether this call inserted it, so concurrent
+    /// callers racing on the same original all agree on the first one stored.
+    pub fn insert_if_absent(&self, field: &str, orig: &str, repl: &str) -> (String, bool) {
+        let mut shard = self.shard(field, orig).write().unwrap();
+        if !shard.contains_key(field) {
+            shard.insert(field.to_string(), HashMap::new());
+        }
+        let by_orig = shard.get_mut(field).expect("field map inserted above");
+        if let Some(existing) = by_orig.get(orig) {
+            return (existing.clone(), false);
+        }
+        by_orig.insert(orig.to_string(), repl.to_string());
+        (repl.to_string(), true)
+    }
+
+    /// Remove one pair; returns its replacement if it was present.
+    pub fn remove(&self, field: &str, orig: &str) -> Option<String> {
+        let mut shard = self.shard(field, orig).write().unwrap();
+        shard.get_mut(field)?.remove(orig)
+    }
+
+    /// Remove every pair; returns how many were removed.
+    pub fn clear(&mut self) -> usize {
+        let mut removed = 0;
+        for shard in self.shards.iter_mut() {
+            let shard = shard.get_mut().unwrap();
+            removed += shard.values().map(|m| m.len()).sum::<usize>();
+            shard.clear();
+        }
+        removed
+    }
+
+    /// Remove the pairs of one field; returns how many were removed.
+    pub fn clear_field(&mut self, field: &str) -> usize {
+        self.clear_fields_where(|f| f == field)
+    }
+
+    /// Remove the pairs of every field for which `f` is true; returns how
+    /// many were removed.
+    pub fn clear_fields_where(&mut self, f: impl Fn(&str) -> bool) -> usize {
+        let mut removed = 0;
+        for shard in self.shards.iter_mut() {
+            shard.get_mut().unwrap().retain(|field, m| {
+                let drop = f(field);
+                if drop {
+                    removed += m.len();
+                }
+                !drop
+            });
+        }
+        removed
+    }
+
+    /// Call `f(field, original, replacement)` for every pair, one shard at a time.
+    pub fn for_each(&self, mut f: impl FnMut(&str, &str, &str)) {
+        for shard in self.shards.iter() {
+            for (field, m) in shard.read().unwrap().iter() {
+                for (orig, repl) in m {
+                    f(field, orig, repl);
+                }
+            }
+        }
+    }
+
+    /// Merge all shards into a single field -> (original -> replacement) map.
+    pub fn snapshot(&self) -> FieldTable {
+        let mut out = FieldTable::new();
+        self.for_each(|field, orig, repl| {
+            out.entry(field.to_string()).or_default().insert(orig.to_string(), repl.to_string());
+        });
+        out
+    }
+
+    /// Number of distinct fields with at least one pair.
+    pub fn field_count(&self) -> usize {
+        let mut fields = HashSet::new();
+        for shard in self.shards.iter() {
+            fields.extend(shard.read().unwrap().keys().cloned());
+        }
+        fields.len()
+    }
+
+    /// Total number of (original -> replacement) pairs across all fields.
+    pub fn total_pairs(&self) -> usize {
+        self.shards.iter().map(|s| s.read().unwrap().values().map(|m| m.len()).sum::<usize>()).sum()
+    }
+
+    /// Byte length of every distinct field name plus every original and
+    /// replacement string held.
+    pub fn bytes(&self) -> usize {
+        let mut fields = HashSet::new();
+        let mut pairs = 0usize;
+        for shard in self.shards.iter() {
+            for (field, m) in shard.read().unwrap().iter() {
+                fields.insert(field.clone());
+                pairs += m.iter().map(|(o, r)| o.len() + r.len()).sum::<usize>();
+            }
+        }
+        fields.iter().map(|f| f.len()).sum::<usize>() + pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IntegrityTable;
+
+    #[test]
+    fn test_integrity_table_first_insert_wins() {
+        let t = IntegrityTable::new();
+        assert_eq!(t.insert_if_absent("ip", "10.0.0.1", "A"), ("A".to_string(), true));
+        assert_eq!(t.insert_if_absent("ip", "10.0.0.1", "B"), ("A".to_string(), false));
+        t.insert_if_absent("ip", "10.0.0.2", "C");
+        t.insert_if_absent("user", "alice", "U");
+        assert_eq!(t.get("ip", "10.0.0.1").as_deref(), Some("A"));
+        assert_eq!(t.get("user", "10.0.0.1"), None);
+        assert_eq!(t.field_count(), 2);
+        assert_eq!(t.total_pairs(), 3);
+        assert_eq!(t.bytes(), "ip".len() + "user".len() + 8 + 1 + 8 + 1 + 5 + 1);
+        assert_eq!(t.snapshot()["ip"].len(), 2);
+    }
+}
@@ -1,96 +1,833 @@
 // anonymizer/table.rs: anonymization engine and integrity table
-use std::collections::HashMap;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
 
-use super::rules::{AnonConfig, FallbackMode, Mode};
+use super::lru::AccessOrder;
+use super::rules::{
+    AnonConfig, DateShiftCfg, FallbackMode, FieldRule, IpMaskCfg, Mode, TokenAlgorithm, TokenizeCfg,
+};
+use super::shards::IntegrityTable;
+use crate::schema::DEFAULT_DATETIME_FORMAT;
 
+/// (index into `value_patterns`, matched text, replacement)
+type PatternMatch = (usize, String, String);
+
+/// field -> (replacement -> original); `None` marks a replacement shared by
+/// several originals
+type ReverseIndex = HashMap<String, HashMap<String, Option<String>>>;
+
+/// An entry added to the integrity table; see `AnonymizerCore::begin_delta`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewMapping {
+    pub field: String,
+    pub original: String,
+    pub replacement: String,
+}
+
+/// Per-field throughput; see `AnonymizerCore::field_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FieldStats {
+    /// Values passed to `anonymize_one` for the field
+    pub total_calls: u64,
+    /// Calls answered from the integrity table without computing a replacement
+    pub cache_hits: u64,
+}
+
+/// Fields a single call may anonymize, narrowing the config for one run
+/// without editing it; see `AnonymizerCore::anonymize_one_filtered`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldFilter {
+    /// Only these fields are anonymized; `None` allows every field
+    pub include: Option<HashSet<String>>,
+    /// Never anonymized, even when also in `include`
+    pub exclude: HashSet<String>,
+}
+
+impl FieldFilter {
+    /// Whether `field` is anonymized under this filter.
+    pub fn allows(&self, field: &str) -> bool {
+        !self.exclude.contains(field) && self.include.as_ref().is_none_or(|i| i.contains(field))
+    }
+}
+
+#[derive(Default)]
+struct FieldCounters {
+    total_calls: AtomicU64,
+    cache_hits: AtomicU64,
+}
+
+impl FieldCounters {
+    fn add(&self, hit: bool) {
+        self.total_calls.fetch_add(1, Ordering::Relaxed);
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// All methods take `&self`, so one instance can be shared across threads
+/// (e.g. Rayon workers); the integrity table and bookkeeping lock internally.
 pub struct AnonymizerCore {
     pub(crate) cfg: AnonConfig,
-    pub table: HashMap<String, HashMap<String, String>>, // field -> (orig -> repl)
+    pub table: IntegrityTable,
+    /// Dry-run only: field -> number of values a rule would have replaced
+    dry_run_matches: Mutex<HashMap<String, u64>>,
     salt: Vec<u8>,
+    /// Compiled `cfg.value_patterns`, same order
+    value_regexes: Vec<Regex>,
+    /// `cfg.fields` keys containing `*` or `?`, compiled, in match order
+    field_globs: Vec<(Regex, String)>,
+    /// Mappings learned since `begin_delta`, when recording
+    delta: Mutex<Option<Vec<NewMapping>>>,
+    /// Built on the first `deanonymize_one`, then kept in sync by `learn`
+    reverse: Mutex<Option<ReverseIndex>>,
+    /// `counter` mode: field -> last number assigned
+    counters: Mutex<HashMap<String, u64>>,
+    /// field -> call counters; the write lock is only taken for a new field
+    stats: RwLock<HashMap<String, FieldCounters>>,
+    /// With `max_entries_per_field`: table key -> access order of the pairs
+    /// of a `tokenize` field; the write lock is only taken for a new field
+    lru: RwLock<HashMap<String, Mutex<AccessOrder>>>,
 }
 
 impl AnonymizerCore {
-    pub fn from_config(cfg: AnonConfig) -> Self {
+    pub fn from_config(cfg: AnonConfig) -> Result<Self, String> {
         let salt = cfg.defaults.tokenize.salt.clone().unwrap_or_default().into_bytes();
-        Self { cfg, table: HashMap::new(), salt }
+        let mut value_regexes = Vec::with_capacity(cfg.value_patterns.len());
+        for vp in &cfg.value_patterns {
+            if !matches!(vp.mode, Mode::Fixed | Mode::Tokenize) {
+                return Err(format!(
+                    "value_patterns entry {:?}: mode must be fixed or tokenize",
+                    vp.pattern
+                ));
+            }
+            let re = Regex::new(&vp.pattern)
+                .map_err(|e| format!("Invalid value pattern {:?}: {}", vp.pattern, e))?;
+            value_regexes.push(re);
+        }
+        let field_globs = compile_field_globs(cfg.fields.keys())?;
+        Ok(Self {
+            cfg,
+            table: IntegrityTable::new(),
+            dry_run_matches: Mutex::new(HashMap::new()),
+            salt,
+            value_regexes,
+            field_globs,
+            delta: Mutex::new(None),
+            reverse: Mutex::new(None),
+            counters: Mutex::new(HashMap::new()),
+            stats: RwLock::new(HashMap::new()),
+            lru: RwLock::new(HashMap::new()),
+        })
+    }
+    pub fn is_dry_run(&self) -> bool {
+        self.cfg.dry_run
+    }
+    /// Whether lines of unknown log type are redacted (`redact_unknown`).
+    pub fn redacts_unknown(&self) -> bool {
+        self.cfg.redact_unknown
+    }
+    /// Dry-run only: field -> number of values a rule would have replaced.
+    pub fn dry_run_matches(&self) -> HashMap<String, u64> {
+        self.dry_run_matches.lock().unwrap().clone()
+    }
+    fn count_dry_run_match(&self, field: &str) {
+        *self.dry_run_matches.lock().unwrap().entry(field.to_string()).or_default() += 1;
+    }
+    fn count_call(&self, field: &str, hit: bool) {
+        if let Some(c) = self.stats.read().unwrap().get(field) {
+            c.add(hit);
+            return;
+        }
+        self.stats.write().unwrap().entry(field.to_string()).or_default().add(hit);
+    }
+    /// field -> calls and integrity-table hits since the anonymizer was built.
+    /// The counters are not reset by `clear_table`/`clear_field`, so values
+    /// seen before a clear count as misses again afterwards. `date_shift`
+    /// values are never stored in the table and always count as misses.
+    pub fn field_stats(&self) -> HashMap<String, FieldStats> {
+        let stats = self.stats.read().unwrap();
+        stats
+            .iter()
+            .map(|(field, c)| {
+                let s = FieldStats {
+                    total_calls: c.total_calls.load(Ordering::Relaxed),
+                    cache_hits: c.cache_hits.load(Ordering::Relaxed),
+                };
+                (field.clone(), s)
+            })
+            .collect()
+    }
+    /// Rule for `field`: its exact entry in `fields`, else the first glob key
+    /// matching it (see `compile_field_globs` for the order).
+    fn field_rule(&self, field: &str) -> Option<&FieldRule> {
+        if let Some(fr) = self.cfg.fields.get(field) {
+            return Some(fr);
+        }
+        let (_, key) = self.field_globs.iter().find(|(re, _)| re.is_match(field))?;
+        self.cfg.fields.get(key)
+    }
+    /// Whether `orig` is in the `passthrough` list of the rule for `field`.
+    fn is_passthrough(&self, field: &str, orig: &str) -> bool {
+        self.field_rule(field).is_some_and(|r| r.passthrough.contains(orig))
     }
     fn resolve_rule<'a>(
         &'a self,
         field: &str,
-    ) -> (Option<&'a Mode>, Option<&'a str>, &'a super::rules::TokenizeCfg) {
-        let fr = self.cfg.fields.get(field);
+    ) -> (Option<&'a Mode>, Option<&'a str>, &'a TokenizeCfg) {
+        let fr = self.field_rule(field);
         // Determine mode: field rule wins; else defaults.mode; else None (passthrough)
         let mode_opt = fr.and_then(|r| r.mode.as_ref()).or(self.cfg.defaults.mode.as_ref());
         let fixed = fr.and_then(|r| r.fixed.as_deref()).or(self.cfg.defaults.fixed.as_deref());
         let tk = fr.map(|r| &r.tokenize).unwrap_or(&self.cfg.defaults.tokenize);
         (mode_opt, fixed, tk)
     }
-    fn tokenize_value(&self, prefix: &str, salt_override: Option<&str>, value: &str) -> String {
-        // simple salted fnv-like rolling hash
-        let mut h: u64 = 0xcbf29ce484222325;
-        for b in salt_override
-            .unwrap_or("")
-            .as_bytes()
-            .iter()
-            .chain(self.salt.iter())
-            .chain(value.as_bytes())
-        {
-            let bb = *b as u64;
-            let mut x = h ^ bb;
-            x = x.wrapping_mul(0x100000001b3);
-            h = x;
+    /// Deterministic token for `value`. The hash is FNV-1a 64 over the bytes of
+    /// `tk.salt` (the field's tokenize salt, or the defaults' salt again for
+    /// fields without a rule), then the default salt, then the UTF-8 value. Only
+    /// byte-wise u64 wrapping arithmetic is used, so tokens are identical on
+    /// every target regardless of endianness or pointer width (see golden tests).
+    ///
+    /// With `algorithm: "hmac_sha256"` the same salt bytes are the HMAC key and
+    /// the token is the first 8 bytes of the MAC over the value, big-endian.
+    ///
+    /// A `key_version` is rendered after the prefix, e.g. `T_v2_<hash>`.
+    fn tokenize_value(&self, tk: &TokenizeCfg, value: &str) -> String {
+        let mut prefix = Cow::Borrowed(tk.prefix.as_deref().unwrap_or("T_"));
+        if let Some(v) = self.key_version(tk) {
+            prefix = Cow::Owned(format!("{}v{}_", prefix, v));
+        }
+        let h = self.hash_value(tk, value);
+        let alphabet =
+            tk.token_alphabet.as_deref().or(self.cfg.defaults.tokenize.token_alphabet.as_deref());
+        match alphabet {
+            Some(a) => format!("{}{}", prefix, encode_in_alphabet(h, a)),
+            None => format!("{}{:016x}", prefix, h),
         }
-        format!("{}{:016x}", prefix, h)
     }
-    pub fn anonymize_one(&mut self, field: &str, orig: &str) -> Option<String> {
-        use Mode::*;
-        if let Some(existing) = self.table.get(field).and_then(|m| m.get(orig)) {
-            return Some(existing.clone());
+    fn key_version(&self, tk: &TokenizeCfg) -> Option<u32> {
+        tk.key_version.or(self.cfg.defaults.tokenize.key_version)
+    }
+    /// Integrity table key for pairs of `field` (or a value-pattern key) under
+    /// `tk`: the name itself, or `name@v<N>` for a versioned rule, so pairs
+    /// learned under each salt epoch are kept side by side.
+    fn table_key<'a>(&self, field: &'a str, tk: &TokenizeCfg) -> Cow<'a, str> {
+        match self.key_version(tk) {
+            Some(v) => Cow::Owned(format!("{}@v{}", field, v)),
+            None => Cow::Borrowed(field),
         }
+    }
+    /// The salted 64-bit hash behind `tokenize_value`.
+    fn hash_value(&self, tk: &TokenizeCfg, value: &str) -> u64 {
+        let field_salt = tk.salt.as_deref().unwrap_or("").as_bytes();
+        let algorithm = tk.algorithm.or(self.cfg.defaults.tokenize.algorithm).unwrap_or_default();
+        let h = match algorithm {
+            TokenAlgorithm::Fnv1a => {
+                // simple salted fnv-like rolling hash
+                let mut h: u64 = 0xcbf29ce484222325;
+                for b in field_salt.iter().chain(self.salt.iter()).chain(value.as_bytes()) {
+                    let bb = *b as u64;
+                    let mut x = h ^ bb;
+                    x = x.wrapping_mul(0x100000001b3);
+                    h = x;
+                }
+                h
+            }
+            TokenAlgorithm::HmacSha256 => {
+                let key = [field_salt, self.salt.as_slice()].concat();
+                let mut mac =
+                    Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts keys of any length");
+                mac.update(value.as_bytes());
+                let digest = mac.finalize().into_bytes();
+                u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 digest is 32 bytes"))
+            }
+        };
+        h
+    }
+    /// Keep the configured network prefix of an IP address and replace the host
+    /// bits with bits of the salted hash of the whole address, so each host
+    /// maps to a stable address in the same network. `None` if `value` is not
+    /// an IP address.
+    fn mask_ip(&self, tk: &TokenizeCfg, ip: Option<&IpMaskCfg>, value: &str) -> Option<String> {
+        let addr: IpAddr = value.trim().parse().ok()?;
+        let defaults = &self.cfg.defaults.ip_mask;
+        let h = self.hash_value(tk, value);
+        let masked = match addr {
+            IpAddr::V4(a) => {
+                let prefix = ip.and_then(|c| c.v4_prefix).or(defaults.v4_prefix).unwrap_or(24);
+                let net = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                let bits = (u32::from(a) & net) | (h as u32 & !net);
+                IpAddr::V4(Ipv4Addr::from(bits))
+            }
+            IpAddr::V6(a) => {
+                let prefix = ip.and_then(|c| c.v6_prefix).or(defaults.v6_prefix).unwrap_or(64);
+                let net = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                // Widen the 64-bit hash to cover prefixes shorter than /64
+                let host = ((h.rotate_left(32) ^ 0x9e3779b97f4a7c15) as u128) << 64 | h as u128;
+                let bits = (u128::from(a) & net) | (host & !net);
+                IpAddr::V6(Ipv6Addr::from(bits))
+            }
+        };
+        Some(masked.to_string())
+    }
+    /// Field named by the `date_shift.subject_field` that applies to `field`.
+    pub fn subject_field(&self, field: &str) -> Option<&str> {
+        let fr = self.field_rule(field);
+        fr.and_then(|r| r.date_shift.subject_field.as_deref()).or(self
+            .cfg
+            .defaults
+            .date_shift
+            .subject_field
+            .as_deref())
+    }
+    /// Parse `value` with the configured format, move it by a whole number of
+    /// days derived from the salted hash of `subject`, and format it back.
+    /// Every value of the same subject moves by the same offset, so intervals
+    /// between them are preserved. `None` if `value` does not parse.
+    fn shift_date(
+        &self,
+        tk: &TokenizeCfg,
+        ds: Option<&DateShiftCfg>,
+        subject: &str,
+        value: &str,
+    ) -> Option<String> {
+        let defaults = &self.cfg.defaults.date_shift;
+        let format = ds
+            .and_then(|c| c.format.as_deref())
+            .or(defaults.format.as_deref())
+            .unwrap_or(DEFAULT_DATETIME_FORMAT);
+        let max_days = ds.and_then(|c| c.max_days).or(defaults.max_days).unwrap_or(365) as i64;
+        let days = (self.hash_value(tk, subject) % (2 * max_days as u64 + 1)) as i64 - max_days;
+        let value = value.trim();
+        // Date-only formats do not parse as a NaiveDateTime
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.checked_add_signed(Duration::days(days))?.format(format).to_string());
+        }
+        let d = NaiveDate::parse_from_str(value, format).ok()?;
+        Some(d.checked_add_signed(Duration::days(days))?.format(format).to_string())
+    }
+    /// Resolve the rule for `field` and compute the replacement without touching
+    /// the integrity table. `None` means the value passes through.
+    fn compute_replacement(&self, field: &str, orig: &str, subject: &str) -> Option<String> {
+        use Mode::*;
         let (mode_ref, fixed_ref, tk_ref) = self.resolve_rule(field);
-        let fixed_owned: Option<String> = fixed_ref.map(|s| s.to_string());
-        let tk_prefix: String = tk_ref.prefix.clone().unwrap_or_else(|| "T_".to_string());
-        let tk_salt_override: Option<String> = tk_ref.salt.clone();
-        let fr = self.cfg.fields.get(field).cloned().unwrap_or_default();
-        let field_map = fr.map;
-        let fallback = fr.fallback;
+        let fr = self.field_rule(field);
+        // Used by `map` for unknown values, and by `ip_mask` and `date_shift` for
+        // values they cannot parse
+        let fallback = || match fr.and_then(|r| r.fallback.as_ref()) {
+            Some(FallbackMode::Fixed) => Some(fixed_ref.unwrap_or("REDACTED").to_string()),
+            Some(FallbackMode::Reject) => None,
+            _ => Some(self.tokenize_value(tk_ref, orig)),
+        };
         let repl: String = match mode_ref {
-            Some(Fixed) => fixed_owned.as_deref().unwrap_or("REDACTED").to_string(),
-            Some(Map) => {
-                if let Some(r) = field_map.get(orig) {
-                    r.clone()
-                } else {
-                    match fallback {
-                        Some(FallbackMode::Fixed) => {
-                            fixed_owned.as_deref().unwrap_or("REDACTED").to_string()
-                        }
-                        Some(FallbackMode::Reject) => return None,
-                        _ => self.tokenize_value(&tk_prefix, tk_salt_override.as_deref(), orig),
-                    }
+            Some(Fixed) => fixed_ref.unwrap_or("REDACTED").to_string(),
+            Some(Map) => match fr.and_then(|r| r.map.get(orig)) {
+                Some(r) => r.clone(),
+                None => fallback()?,
+            },
+            Some(Tokenize) => self.tokenize_value(tk_ref, orig),
+            Some(IpMask) => match self.mask_ip(tk_ref, fr.map(|r| &r.ip_mask), orig) {
+                Some(r) => r,
+                None => fallback()?,
+            },
+            Some(DateShift) => {
+                match self.shift_date(tk_ref, fr.map(|r| &r.date_shift), subject, orig) {
+                    Some(r) => r,
+                    None => fallback()?,
                 }
             }
-            Some(Tokenize) => self.tokenize_value(&tk_prefix, tk_salt_override.as_deref(), orig),
+            // The number that would be assigned next; `assign_counter` commits it
+            Some(Counter) => {
+                let last = self.counters.lock().unwrap().get(field).copied().unwrap_or(0);
+                format!("{}{}", tk_ref.prefix.as_deref().unwrap_or("T_"), last + 1)
+            }
             None => return None,
         };
-        let table_for_field = self.table.entry(field.to_string()).or_default();
-        table_for_field.insert(orig.to_string(), repl.clone());
         Some(repl)
     }
+    /// Scrub every `value_patterns` match inside `value`, in pattern order.
+    /// Returns the rewritten value plus (pattern, match, replacement) triples,
+    /// or `None` when nothing matched.
+    fn scrub_by_value(&self, value: &str) -> Option<(String, Vec<PatternMatch>)> {
+        let mut out: Option<String> = None;
+        let mut pairs = Vec::new();
+        for (i, (re, vp)) in self.value_regexes.iter().zip(&self.cfg.value_patterns).enumerate() {
+            let current = out.as_deref().unwrap_or(value);
+            if !re.is_match(current) {
+                continue;
+            }
+            let replaced = re.replace_all(current, |caps: &regex::Captures| {
+                let m = &caps[0];
+                let repl = match vp.mode {
+                    Mode::Tokenize => self.tokenize_value(&vp.tokenize, m),
+                    _ => vp.fixed.clone().unwrap_or_else(|| "REDACTED".to_string()),
+                };
+                pairs.push((i, m.to_string(), repl.clone()));
+                repl
+            });
+            out = Some(replaced.into_owned());
+        }
+        out.map(|v| (v, pairs))
+    }
+    /// Apply the field rule for `field`; if it leaves the value unchanged, scrub
+    /// substrings matching `value_patterns` instead. Fields without an explicit
+    /// rule are scrubbed by pattern first and only fall back to `defaults` when
+    /// nothing matched. `None` means pass-through. Values in the rule's
+    /// `passthrough` list pass through before any of this, and are neither
+    /// recorded nor counted.
+    pub fn anonymize_one(&self, field: &str, orig: &str) -> Option<String> {
+        self.anonymize_one_for_subject(field, orig, None)
+    }
+    /// `anonymize_one`, passing values of fields that `filter` does not allow
+    /// through untouched whatever their rule. Filtered values are not counted.
+    pub fn anonymize_one_filtered(
+        &self,
+        field: &str,
+        orig: &str,
+        filter: &FieldFilter,
+    ) -> Option<String> {
+        filter.allows(field).then(|| self.anonymize_one(field, orig)).flatten()
+    }
+    /// `anonymize_one` for a value from a record whose `subject_field(field)`
+    /// value is `subject`; only `date_shift` uses it. Shifted dates depend on
+    /// the subject as well as the value, so they are not recorded in the
+    /// integrity table.
+    pub fn anonymize_one_for_subject(
+        &self,
+        field: &str,
+        orig: &str,
+        subject: Option<&str>,
+    ) -> Option<String> {
+        if self.is_passthrough(field, orig) {
+            return None;
+        }
+        let (mode, _, tk) = self.resolve_rule(field);
+        let key = self.table_key(field, tk);
+        let bound = self.cfg.max_entries_per_field.filter(|_| matches!(mode, Some(Mode::Tokenize)));
+        let existing = self.table.get(&key, orig);
+        self.count_call(field, existing.is_some());
+        if existing.is_some() {
+            if bound.is_some() {
+                self.with_access_order(&key, |o| o.touch_existing(orig));
+            }
+            return existing;
+        }
+        let has_rule = self.field_rule(field).is_some();
+        if !self.value_regexes.is_empty() && !has_rule {
+            if let Some((scrubbed, pairs)) = self.scrub_by_value(orig) {
+                return self.record_value_matches(field, scrubbed, pairs);
+            }
+        }
+        let repl = self.compute_replacement(field, orig, subject.unwrap_or(""));
+        if repl.is_none() && has_rule && !self.value_regexes.is_empty() {
+            return self.anonymize_by_value(field, orig);
+        }
+        if self.cfg.dry_run {
+            // Record the decision only; the caller keeps the original value
+            if repl.is_some() {
+                self.count_dry_run_match(field);
+            }
+            return None;
+        }
+        let repl = repl?;
+        match mode {
+            Some(Mode::Counter) => Some(self.assign_counter(field, &key, orig)),
+            Some(Mode::DateShift) => Some(repl),
+            _ => {
+                let (stored, inserted) = self.learn(&key, orig, &repl);
+                if let (Some(max), true) = (bound, inserted) {
+                    self.evict_over(&key, orig, max);
+                }
+                Some(stored)
+            }
+        }
+    }
+    /// Run `f` on the access order of table key `key`, creating it if new.
+    fn with_access_order<R>(&self, key: &str, f: impl FnOnce(&mut AccessOrder) -> R) -> R {
+        if let Some(o) = self.lru.read().unwrap().get(key) {
+            return f(&mut o.lock().unwrap());
+        }
+        let mut lru = self.lru.write().unwrap();
+        f(lru.entry(key.to_string()).or_default().get_mut().unwrap())
+    }
+    /// Track the pair just learned for `orig` under `key` and drop the least
+    /// recently used pairs beyond `max`. Only `tokenize` pairs are tracked:
+    /// an evicted value simply gets the same token recomputed when seen again.
+    fn evict_over(&self, key: &str, orig: &str, max: usize) {
+        let evicted: Vec<String> = self.with_access_order(key, |o| {
+            o.touch(orig);
+            let mut evicted = Vec::new();
+            while o.len() > max {
+                evicted.extend(o.pop_oldest());
+            }
+            evicted
+        });
+        if evicted.is_empty() {
+            return;
+        }
+        let mut reverse = self.reverse.lock().unwrap();
+        for old in evicted {
+            let Some(repl) = self.table.remove(key, &old) else { continue };
+            let by_repl = reverse.as_mut().and_then(|rev| rev.get_mut(key));
+            if let Some(by_repl) = by_repl {
+                if by_repl.get(&repl).is_some_and(|o| o.as_deref() == Some(old.as_str())) {
+                    by_repl.remove(&repl);
+                }
+            }
+        }
+    }
+    /// What `anonymize_one` would return for `orig`, without recording
+    /// anything: the integrity table, counters and dry-run tallies are left
+    /// untouched, and `dry_run` is ignored. A `counter` field previews a new
+    /// value as the next number, which is not consumed.
+    pub fn preview_one(&self, field: &str, orig: &str) -> Option<String> {
+        if self.is_passthrough(field, orig) {
+            return None;
+        }
+        let key = self.table_key(field, self.resolve_rule(field).2);
+        if let Some(existing) = self.table.get(&key, orig) {
+            return Some(existing);
+        }
+        let has_rule = self.field_rule(field).is_some();
+        if !has_rule {
+            if let Some((scrubbed, _)) = self.scrub_by_value(orig) {
+                return Some(scrubbed);
+            }
+        }
+        match self.compute_replacement(field, orig, "") {
+            None if has_rule => self.scrub_by_value(orig).map(|(scrubbed, _)| scrubbed),
+            repl => repl,
+        }
+    }
+    /// Anonymize one record given as parallel field `names` and `values`
+    /// (`None` for missing fields), taking each `date_shift` subject from the
+    /// record itself. Returns the replacement for every position, `None` where
+    /// the value passes through unchanged.
+    pub fn anonymize_record(
+        &self,
+        names: &[String],
+        values: &[Option<&str>],
+    ) -> Vec<Option<String>> {
+        self.anonymize_record_filtered(names, values, &FieldFilter::default())
+    }
+    /// `anonymize_record` leaving fields that `filter` does not allow as they
+    /// are. A filtered-out field can still be the `date_shift` subject of
+    /// another.
+    pub fn anonymize_record_filtered(
+        &self,
+        names: &[String],
+        values: &[Option<&str>],
+        filter: &FieldFilter,
+    ) -> Vec<Option<String>> {
+        names
+            .iter()
+            .zip(values)
+            .map(|(name, value)| {
+                if !filter.allows(name) {
+                    return None;
+                }
+                let subject = self.subject_field(name).and_then(|sf| {
+                    let pos = names.iter().position(|n| n == sf)?;
+                    values.get(pos).copied().flatten()
+                });
+                self.anonymize_one_for_subject(name, (*value)?, subject)
+            })
+            .collect()
+    }
+    /// Give `orig` the next number for `field` and record it under `key`. The
+    /// counters lock is held across the table check and insert, so concurrent
+    /// callers never skip or reuse a number.
+    fn assign_counter(&self, field: &str, key: &str, orig: &str) -> String {
+        let mut counters = self.counters.lock().unwrap();
+        if let Some(existing) = self.table.get(key, orig) {
+            return existing;
+        }
+        let n = counters.entry(field.to_string()).or_insert(0);
+        *n += 1;
+        let prefix = self.resolve_rule(field).2.prefix.as_deref().unwrap_or("T_");
+        self.learn(key, orig, &format!("{}{}", prefix, n)).0
+    }
+    /// Insert a new pair into the integrity table, recording it for the delta.
+    /// Returns the stored replacement, which is another thread's if it won a
+    /// race on the same original, and whether this call inserted it.
+    fn learn(&self, field: &str, orig: &str, repl: &str) -> (String, bool) {
+        // The shard lock is released before the reverse index and delta locks
+        // are taken, so `deanonymize_one` building the index cannot deadlock
+        let (stored, inserted) = self.table.insert_if_absent(field, orig, repl);
+        if inserted {
+            if let Some(rev) = self.reverse.lock().unwrap().as_mut() {
+                add_reverse(rev.entry(field.to_string()).or_default(), orig, repl);
+            }
+            if let Some(d) = self.delta.lock().unwrap().as_mut() {
+                d.push(NewMapping {
+                    field: field.to_string(),
+                    original: orig.to_string(),
+                    replacement: repl.to_string(),
+                });
+            }
+        }
+        (stored, inserted)
+    }
+    /// Original value that was replaced with `token` in `field`, looked up in
+    /// the integrity table. Returns `None` for unknown tokens and for ambiguous
+    /// ones: `fixed` mode (and `map` entries sharing a target) send many
+    /// originals to the same replacement, which cannot be reversed. Pairs of
+    /// every `key_version` of `field` are searched.
+    pub fn deanonymize_one(&self, field: &str, token: &str) -> Option<String> {
+        let mut guard = self.reverse.lock().unwrap();
+        let rev = guard.get_or_insert_with(|| {
+            let mut rev = ReverseIndex::new();
+            self.table.for_each(|f, orig, repl| {
+                add_reverse(rev.entry(f.to_string()).or_default(), orig, repl);
+            });
+            rev
+        });
+        rev.iter()
+            .filter(|(key, _)| is_field_key(key, field))
+            .find_map(|(_, by_repl)| by_repl.get(token))?
+            .clone()
+    }
+    /// Take over the integrity table and `counter` numbering of `previous`,
+    /// e.g. to rotate to a new salt and `key_version` without losing the
+    /// pairs learned under the old one. Versioned pairs are kept per version,
+    /// so old tokens still reverse while values seen again get new tokens.
+    pub fn inherit_table(&mut self, previous: AnonymizerCore) {
+        self.table = previous.table;
+        *self.reverse.get_mut().unwrap() = previous.reverse.into_inner().unwrap();
+        *self.counters.get_mut().unwrap() = previous.counters.into_inner().unwrap();
+        *self.lru.get_mut().unwrap() = previous.lru.into_inner().unwrap();
+    }
+    /// Forget every learned mapping, e.g. when a long-running service moves to a
+    /// new dataset and must not link values across datasets. The config is
+    /// kept; `counter` numbering restarts at 1, while `field_stats` keeps
+    /// counting. Returns the number of pairs removed.
+    pub fn clear_table(&mut self) -> usize {
+        *self.reverse.get_mut().unwrap() = None;
+        self.counters.get_mut().unwrap().clear();
+        self.lru.get_mut().unwrap().clear();
+        self.table.clear()
+    }
+    /// Like `clear_table`, for a single field (or value-pattern key),
+    /// including the pairs it holds under every `key_version`.
+    pub fn clear_field(&mut self, field: &str) -> usize {
+        if let Some(rev) = self.reverse.get_mut().unwrap().as_mut() {
+            rev.retain(|key, _| !is_field_key(key, field));
+        }
+        self.counters.get_mut().unwrap().remove(field);
+        self.lru.get_mut().unwrap().retain(|key, _| !is_field_key(key, field));
+        self.table.clear_fields_where(|key| is_field_key(key, field))
+    }
+    /// Start recording every mapping newly added to the integrity table, so a
+    /// caller can ship just the increment instead of re-exporting the table.
+    pub fn begin_delta(&self) {
+        *self.delta.lock().unwrap() = Some(Vec::new());
+    }
+    /// Stop recording and return the mappings added since `begin_delta`.
+    pub fn end_delta(&self) -> Vec<NewMapping> {
+        self.delta.lock().unwrap().take().unwrap_or_default()
+    }
+    /// Scrub `value_patterns` matches in `value` regardless of field rules. Each
+    /// (match -> replacement) pair is recorded in the integrity table under the
+    /// pattern string. In dry-run mode matches are counted under `field` and
+    /// `None` is returned.
+    pub fn anonymize_by_value(&self, field: &str, value: &str) -> Option<String> {
+        let (scrubbed, pairs) = self.scrub_by_value(value)?;
+        self.record_value_matches(field, scrubbed, pairs)
+    }
+    /// Replace every `value_patterns` match inside free text, whatever field it
+    /// came from, leaving the surrounding text intact. Values without a match,
+    /// and every value in dry-run mode, are returned unchanged.
+    pub fn anonymize_text(&self, value: &str) -> String {
+        match self.scrub_by_value(value) {
+            Some((scrubbed, pairs)) if !self.cfg.dry_run => {
+                self.learn_pattern_matches(pairs);
+                scrubbed
+            }
+            _ => value.to_string(),
+        }
+    }
+    fn record_value_matches(
+        &self,
+        field: &str,
+        scrubbed: String,
+        pairs: Vec<PatternMatch>,
+    ) -> Option<String> {
+        if self.cfg.dry_run {
+            self.count_dry_run_match(field);
+            return None;
+        }
+        self.learn_pattern_matches(pairs);
+        Some(scrubbed)
+    }
+    fn learn_pattern_matches(&self, pairs: Vec<PatternMatch>) {
+        for (i, m, repl) in pairs {
+            let vp = &self.cfg.value_patterns[i];
+            self.learn(&self.table_key(&vp.pattern, &vp.tokenize), &m, &repl);
+        }
+    }
+    /// Total number of (original -> replacement) pairs across all fields.
+    pub fn total_pairs(&self) -> usize {
+        self.table.total_pairs()
+    }
+    /// Approximate heap footprint of the integrity table: the byte length of
+    /// every field name, original, and replacement string it holds.
+    pub fn table_bytes(&self) -> usize {
+        self.table.bytes()
+    }
+}
+
+/// Whether integrity table key `key` holds pairs of `field`: the field itself
+/// or one of its `field@v<N>` versions (see `table_key`).
+fn is_field_key(key: &str, field: &str) -> bool {
+    match key.strip_prefix(field) {
+        Some("") => true,
+        Some(rest) => rest.strip_prefix("@v").is_some_and(|v| v.parse::<u32>().is_ok()),
+        None => false,
+    }
 }
 
+/// Idempotent, since a pair learned while the index is being built can be
+/// added both by the build and by `learn`.
+fn add_reverse(by_repl: &mut HashMap<String, Option<String>>, orig: &str, repl: &str) {
+    by_repl
+        .entry(repl.to_string())
+        .and_modify(|o| {
+            if o.as_deref() != Some(orig) {
+                *o = None
+            }
+        })
+        .or_insert_with(|| Some(orig.to_string()));
+}
+
+/// Render `h` in base `alphabet.len()`, most significant digit first, always
+/// using enough digits to cover the full u64 range so tokens have equal length.
+fn encode_in_alphabet(mut h: u64, alphabet: &str) -> String {
+    let chars: Vec<char> = alphabet.chars().collect();
+    let base = chars.len() as u64;
+    let mut width = 0usize;
+    let mut cap: u128 = 1;
+    while cap <= u64::MAX as u128 {
+        cap *= base as u128;
+        width += 1;
+    }
+    let mut digits = vec![chars[0]; width];
+    for slot in digits.iter_mut().rev() {
+        *slot = chars[(h % base) as usize];
+        h /= base;
+    }
+    digits.into_iter().collect()
+}
+
+/// Compile the glob keys of `fields` (`*` matches any run of characters, `?`
+/// one character) in the order they are tried: more literal characters first,
+/// so `http_header_x_*` wins over `http_header_*`, then by key.
+fn compile_field_globs<'a>(
+    keys: impl Iterator<Item = &'a String>,
+) -> Result<Vec<(Regex, String)>, String> {
+    let literal_len = |k: &str| k.chars().filter(|c| !matches!(c, '*' | '?')).count();
+    let mut globs: Vec<&String> = keys.filter(|k| k.contains(['*', '?'])).collect();
+    globs.sort_by(|a, b| literal_len(b).cmp(&literal_len(a)).then_with(|| a.cmp(b)));
+    globs
+        .into_iter()
+        .map(|key| {
+            let mut pattern = String::from("^");
+            for c in key.chars() {
+                match c {
+                    '*' => pattern.push_str(".*"),
+                    '?' => pattern.push('.'),
+                    c => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+                }
+            }
+            pattern.push('$');
+            let re =
+                Regex::new(&pattern).map_err(|e| format!("Invalid field glob {:?}: {}", key, e))?;
+            Ok((re, key.clone()))
+        })
+        .collect()
+}
+
+fn check_ip_mask(scope: &str, ip: &IpMaskCfg) -> Result<(), String> {
+    for (family, prefix, max) in [("v4", ip.v4_prefix, 32), ("v6", ip.v6_prefix, 128)] {
+        if prefix.is_some_and(|p| p > max) {
+            return Err(format!(
+                "Invalid ip_mask for {}: {}_prefix must be at most {}, got {}",
+                scope,
+                family,
+                max,
+                prefix.unwrap_or_default()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check_alphabet(scope: &str, tk: &TokenizeCfg) -> Result<(), String> {
+    if let Some(a) = tk.token_alphabet.as_deref() {
+        let mut seen = std::collections::HashSet::new();
+        if a.chars().count() < 2 || !a.chars().all(|c| seen.insert(c)) {
+            return Err(format!(
+                "Invalid token_alphabet for {}: need at least 2 distinct characters, got {:?}",
+                scope, a
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Like `anonymizer_from_json`, but tolerates comments and trailing commas.
+pub fn anonymizer_from_jsonc(jsonc: &str) -> Result<AnonymizerCore, String> {
+    anonymizer_from_json(&crate::jsonc::strip_jsonc(jsonc))
+}
+
+/// Build an anonymizer from a JSON config. Only structural errors are
+/// rejected; see `anonymizer_from_json_strict` for semantic checks.
 pub fn anonymizer_from_json(json: &str) -> Result<AnonymizerCore, String> {
-    let cfg: super::rules::AnonConfig = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    AnonymizerCore::from_config(config_from_json(json)?)
+}
+
+/// Like `anonymizer_from_json`, but also rejects rules that would silently
+/// fall back at runtime (see `AnonConfig::validate`).
+pub fn anonymizer_from_json_strict(json: &str) -> Result<AnonymizerCore, String> {
+    let cfg = config_from_json(json)?;
+    cfg.validate().map_err(|e| format!("Invalid anonymizer config: {}", e))?;
+    AnonymizerCore::from_config(cfg)
+}
+
+fn config_from_json(json: &str) -> Result<AnonConfig, String> {
+    let cfg: AnonConfig = serde_json::from_str(json).map_err(|e| e.to_string())?;
     if let Some(v) = cfg.version {
         if v != 1 {
             return Err(format!("Unsupported anonymizer config version: {}", v));
         }
     }
-    Ok(AnonymizerCore::from_config(cfg))
+    check_alphabet("defaults", &cfg.defaults.tokenize)?;
+    check_ip_mask("defaults", &cfg.defaults.ip_mask)?;
+    for (name, rule) in &cfg.fields {
+        check_alphabet(name, &rule.tokenize)?;
+        check_ip_mask(name, &rule.ip_mask)?;
+    }
+    for vp in &cfg.value_patterns {
+        check_alphabet(&vp.pattern, &vp.tokenize)?;
+    }
+    Ok(cfg)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::anonymizer_from_json;
+    use super::{
+        anonymizer_from_json, anonymizer_from_json_strict, encode_in_alphabet, FieldFilter,
+        FieldStats, NewMapping,
+    };
+    use chrono::{NaiveDate, NaiveDateTime};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
 
     #[test]
     fn test_anonymizer_tokenize_and_map() {
@@ -105,7 +842,7 @@ mod tests {
             "fixed_fallback": { "mode": "map", "map": {}, "fallback": "fixed", "fixed": "REDACTED" }
           }
         }"#;
-        let mut anon = anonymizer_from_json(cfg_json).expect("anon json");
+        let anon = anonymizer_from_json(cfg_json).expect("anon json");
 
         // Deterministic tokenization
         let t1 = anon.anonymize_one("ip", "10.0.0.1").unwrap();
@@ -132,7 +869,600 @@ mod tests {
         assert_eq!(ff, "REDACTED");
 
         // Integrity table growth
-        let status: usize = anon.table.values().map(|m| m.len()).sum();
+        let status = anon.total_pairs();
         assert!(status >= 4);
+        assert_eq!(anon.total_pairs(), status);
+    }
+
+    #[test]
+    fn test_field_glob_rules_with_exact_override() {
+        let cfg_json = r#"{
+          "defaults": { "tokenize": { "salt": "pepper" } },
+          "fields": {
+            "http_header_*": { "mode": "tokenize", "tokenize": { "prefix": "H_" } },
+            "http_header_x_*": { "mode": "fixed", "fixed": "X" },
+            "http_header_host": { "mode": "fixed", "fixed": "HOST" },
+            "vsys?": { "mode": "fixed", "fixed": "V" }
+          }
+        }"#;
+        let anon = anonymizer_from_json(cfg_json).expect("anon json");
+        for field in ["http_header_accept", "http_header_cookie", "http_header_"] {
+            assert!(anon.anonymize_one(field, "v").unwrap().starts_with("H_"), "{}", field);
+        }
+        assert_eq!(anon.anonymize_one("http_header_host", "a.example").as_deref(), Some("HOST"));
+        assert_eq!(anon.anonymize_one("http_header_x_forwarded_for", "v").as_deref(), Some("X"));
+        assert_eq!(anon.anonymize_one("vsys1", "v").as_deref(), Some("V"));
+        // No defaults mode: fields matching no key pass through
+        assert_eq!(anon.anonymize_one("vsys10", "v"), None);
+        assert_eq!(anon.anonymize_one("xhttp_header_a", "v"), None);
+    }
+
+    #[test]
+    fn test_passthrough_values_are_left_alone() {
+        let cfg_json = r#"{
+          "defaults": { "tokenize": { "salt": "pepper" } },
+          "fields": {
+            "src_ip": { "mode": "tokenize", "passthrough": ["0.0.0.0", "-"] }
+          }
+        }"#;
+        let anon = anonymizer_from_json(cfg_json).expect("anon json");
+        assert_eq!(anon.anonymize_one("src_ip", "0.0.0.0"), None);
+        assert_eq!(anon.anonymize_one("src_ip", "-"), None);
+        assert_eq!(anon.preview_one("src_ip", "0.0.0.0"), None);
+        assert_eq!(anon.total_pairs(), 0);
+        assert!(anon.field_stats().is_empty());
+        let t = anon.anonymize_one("src_ip", "10.0.0.1").unwrap();
+        assert!(t.starts_with("T_"));
+        assert_eq!(anon.total_pairs(), 1);
+    }
+
+    #[test]
+    fn test_field_filter_limits_anonymized_fields() {
+        let cfg_json = r#"{
+          "defaults": { "tokenize": { "salt": "pepper" } },
+          "fields": {
+            "src_ip": { "mode": "tokenize" },
+            "dst_ip": { "mode": "tokenize" },
+            "user": { "mode": "fixed", "fixed": "U" }
+          }
+        }"#;
+        let anon = anonymizer_from_json(cfg_json).expect("anon json");
+        let set = |names: &[&str]| names.iter().map(|s| s.to_string()).collect();
+        let only_src = FieldFilter { include: Some(set(&["src_ip"])), ..Default::default() };
+        let names: Vec<String> = ["src_ip", "dst_ip", "user"].map(String::from).to_vec();
+        let values = [Some("10.0.0.1"), Some("10.0.0.2"), Some("alice")];
+        let got = anon.anonymize_record_filtered(&names, &values, &only_src);
+        assert_eq!(got[0], anon.anonymize_one("src_ip", "10.0.0.1"));
+        assert!(got[0].is_some());
+        assert_eq!(got[1..], [None, None]);
+        assert_eq!(anon.anonymize_one_filtered("user", "alice", &only_src), None);
+
+        // Exclude wins over include
+        let excluded = FieldFilter { exclude: set(&["src_ip"]), ..only_src };
+        assert_eq!(anon.anonymize_one_filtered("src_ip", "10.0.0.1", &excluded), None);
+        let no_user = FieldFilter { exclude: set(&["user"]), ..Default::default() };
+        assert!(anon.anonymize_one_filtered("dst_ip", "10.0.0.2", &no_user).is_some());
+    }
+
+    #[test]
+    fn test_max_entries_per_field_evicts_tokenize_pairs() {
+        let cfg_json = r#"{
+          "max_entries_per_field": 3,
+          "defaults": { "tokenize": { "salt": "pepper" } },
+          "fields": {
+            "session": { "mode": "tokenize" },
+            "user": { "mode": "map", "map": {}, "fallback": "fixed", "fixed": "U" }
+          }
+        }"#;
+        let anon = anonymizer_from_json(cfg_json).expect("anon json");
+        let first: Vec<String> =
+            (0..10).map(|i| anon.anonymize_one("session", &format!("s{}", i)).unwrap()).collect();
+        assert_eq!(anon.total_pairs(), 3);
+        // s9 is the most recent; touching s7 makes s8 the next to go
+        assert_eq!(anon.deanonymize_one("session", &first[9]).as_deref(), Some("s9"));
+        anon.anonymize_one("session", "s7");
+        anon.anonymize_one("session", "s0");
+        assert_eq!(anon.deanonymize_one("session", &first[8]), None);
+        assert_eq!(anon.deanonymize_one("session", &first[7]).as_deref(), Some("s7"));
+        assert_eq!(anon.total_pairs(), 3);
+        // Evicted values get the same token again
+        for (i, token) in first.iter().enumerate() {
+            assert_eq!(anon.anonymize_one("session", &format!("s{}", i)).as_ref(), Some(token));
+        }
+        assert_eq!(anon.total_pairs(), 3);
+
+        for i in 0..10 {
+            anon.anonymize_one("user", &format!("u{}", i));
+        }
+        assert_eq!(anon.total_pairs(), 13);
+    }
+
+    #[test]
+    fn test_field_stats_hits_and_misses() {
+        let mut anon = anonymizer_from_json(r#"{"defaults": {"mode": "tokenize"}}"#).unwrap();
+        for _ in 0..3 {
+            anon.anonymize_one("ip", "10.0.0.1").unwrap();
+        }
+        anon.anonymize_one("ip", "10.0.0.2").unwrap();
+        anon.anonymize_one("user", "alice").unwrap();
+        let stats = anon.field_stats();
+        assert_eq!(stats["ip"], FieldStats { total_calls: 4, cache_hits: 2 });
+        assert_eq!(stats["user"], FieldStats { total_calls: 1, cache_hits: 0 });
+        // Previews are not counted
+        anon.preview_one("ip", "10.0.0.1");
+        assert_eq!(anon.field_stats()["ip"].total_calls, 4);
+
+        // Counters survive a clear; the next call is a miss again
+        anon.clear_table();
+        anon.anonymize_one("ip", "10.0.0.1").unwrap();
+        assert_eq!(anon.field_stats()["ip"], FieldStats { total_calls: 5, cache_hits: 2 });
+    }
+
+    #[test]
+    fn test_strict_config_validation() {
+        let strict_err = |json: &str| {
+            assert!(anonymizer_from_json(json).is_ok(), "lenient load: {}", json);
+            anonymizer_from_json_strict(json).err().unwrap_or_default()
+        };
+        assert!(strict_err(r#"{"fields": {"u": {"mode": "fixed"}}}"#)
+            .contains("field \"u\": mode fixed needs a fixed value"));
+        assert!(strict_err(r#"{"defaults": {"mode": "fixed"}}"#)
+            .contains("defaults: mode fixed needs a fixed value"));
+        assert!(strict_err(r#"{"fields": {"u": {"mode": "map"}}}"#)
+            .contains("field \"u\": mode map has an empty map and no fallback"));
+        assert!(strict_err(r#"{"fields": {"u": {"mode": "map", "fallback": "fixed"}}}"#)
+            .contains("field \"u\": fallback fixed needs a fixed value"));
+        assert!(strict_err(r#"{"fields": {"u": {"mode": "tokenize", "fallback": "reject"}}}"#)
+            .contains("field \"u\": fallback is only used with"));
+        assert!(strict_err(r#"{"fields": {"u": {"map": {"a": "b"}}}}"#)
+            .contains("field \"u\": map is only used with mode map"));
+        assert!(strict_err(r#"{"value_patterns": [{"pattern": "x", "mode": "fixed"}]}"#)
+            .contains("value_patterns entry \"x\": mode fixed needs a fixed value"));
+        // Every problem is reported
+        let both = strict_err(r#"{"fields": {"a": {"mode": "fixed"}, "b": {"mode": "map"}}}"#);
+        assert!(both.contains("\"a\"") && both.contains("\"b\""), "{}", both);
+
+        // A fixed value from defaults, or a map fallback, is enough
+        assert!(anonymizer_from_json_strict(
+            r#"{"defaults": {"fixed": "X"},
+                "fields": {"u": {"mode": "fixed"},
+                           "v": {"mode": "map", "fallback": "fixed"},
+                           "w": {"mode": "map", "map": {}, "fallback": "reject"}}}"#
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_preview_one_leaves_table_unchanged() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "s"}},
+                "fields": {"user": {"mode": "counter"}}}"#,
+        )
+        .unwrap();
+        anon.anonymize_one("user", "alice").unwrap();
+        let pairs = anon.total_pairs();
+
+        let tok = anon.preview_one("ip", "10.0.0.1").unwrap();
+        assert_eq!(anon.preview_one("user", "alice").unwrap(), "T_1");
+        // The next counter value is shown but not consumed
+        assert_eq!(anon.preview_one("user", "bob").unwrap(), "T_2");
+        assert_eq!(anon.preview_one("user", "carol").unwrap(), "T_2");
+        assert_eq!(anon.total_pairs(), pairs);
+
+        assert_eq!(anon.anonymize_one("ip", "10.0.0.1").unwrap(), tok);
+        assert_eq!(anon.anonymize_one("user", "carol").unwrap(), "T_2");
+    }
+
+    #[test]
+    fn test_anonymize_record_reports_changed_fields() {
+        let anon = anonymizer_from_json(r#"{"fields": {"user": {"mode": "fixed", "fixed": "U"}}}"#)
+            .unwrap();
+        let names: Vec<String> = ["src", "user", "action"].iter().map(|s| s.to_string()).collect();
+        let repl = anon.anonymize_record(&names, &[Some("10.0.0.1"), Some("alice"), Some("allow")]);
+        assert_eq!(repl, vec![None, Some("U".to_string()), None]);
+        let changed: Vec<&str> =
+            names.iter().zip(&repl).filter(|(_, r)| r.is_some()).map(|(n, _)| n.as_str()).collect();
+        assert_eq!(changed, ["user"]);
+        // Missing values are skipped
+        assert_eq!(anon.anonymize_record(&names, &[None, None, None]), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_table_bytes() {
+        let anon =
+            anonymizer_from_json(r#"{"fields": {"f": {"mode": "fixed", "fixed": "XY"}}}"#).unwrap();
+        assert_eq!(anon.table_bytes(), 0);
+        anon.anonymize_one("f", "abc");
+        // "f" + "abc" + "XY"
+        assert_eq!(anon.table_bytes(), 6);
+    }
+
+    #[test]
+    fn test_tokenize_golden_vectors() {
+        // Without salt the token is plain FNV-1a 64, so the published reference
+        // vectors pin the algorithm independently of the host architecture.
+        let plain = anonymizer_from_json(r#"{"defaults": {"mode": "tokenize"}}"#).unwrap();
+        assert_eq!(plain.anonymize_one("f", "a").unwrap(), "T_af63dc4c8601ec8c");
+        assert_eq!(plain.anonymize_one("f", "foobar").unwrap(), "T_85944171f73967e8");
+        assert_eq!(crate::hash64_fnv1a(b"foobar"), 0x85944171f73967e8);
+
+        // Salted vectors: byte order is tk.salt, default salt, value. A field with
+        // no rule resolves tk to the defaults, so "pepper" is hashed twice.
+        let salted = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "pepper"}},
+                "fields": {"over": {"mode": "tokenize", "tokenize": {"salt": "field-salt"}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(salted.anonymize_one("ip", "10.0.0.1").unwrap(), "T_bc52cb0ddcb74485");
+        assert_eq!(salted.anonymize_one("over", "10.0.0.1").unwrap(), "T_3a739f9325413a2a");
+        // Multibyte input hashes its UTF-8 bytes
+        assert_eq!(salted.anonymize_one("city", "münchen").unwrap(), "T_1abf557e4f94790b");
+    }
+
+    #[test]
+    fn test_tokenize_hmac_sha256() {
+        let cfg = r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "pepper"}},
+                      "fields": {"user": {"mode": "tokenize",
+                                          "tokenize": {"salt": "k", "algorithm": "hmac_sha256"}}}}"#;
+        let a = anonymizer_from_json(cfg).unwrap();
+        let b = anonymizer_from_json(cfg).unwrap();
+        let tok = a.anonymize_one("user", "alice").unwrap();
+        // Deterministic across instances, prefixed, 64-bit hex
+        assert_eq!(tok, b.anonymize_one("user", "alice").unwrap());
+        assert!(tok.starts_with("T_") && tok.len() == 2 + 16);
+        // Key is "k" + "pepper", as for FNV-1a
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"kpepper").unwrap();
+        mac.update(b"alice");
+        assert_eq!(tok[2..], hex_prefix(&mac.finalize().into_bytes()));
+        // Differs from the FNV-1a token for the same salt and value
+        let fnv = anonymizer_from_json(&cfg.replace(r#", "algorithm": "hmac_sha256""#, ""))
+            .unwrap()
+            .anonymize_one("user", "alice")
+            .unwrap();
+        assert_ne!(tok, fnv);
+
+        assert!(
+            anonymizer_from_json(r#"{"defaults": {"tokenize": {"algorithm": "md5"}}}"#).is_err()
+        );
+    }
+
+    fn hex_prefix(digest: &[u8]) -> String {
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_deanonymize_one() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "s"}},
+                "fields": {"user": {"mode": "fixed", "fixed": "USER"}}}"#,
+        )
+        .unwrap();
+        let tok = anon.anonymize_one("src", "10.0.0.1").unwrap();
+        assert_eq!(anon.deanonymize_one("src", &tok).as_deref(), Some("10.0.0.1"));
+        // Mappings learned after the index was built are reversible too
+        let tok2 = anon.anonymize_one("src", "10.0.0.2").unwrap();
+        assert_eq!(anon.deanonymize_one("src", &tok2).as_deref(), Some("10.0.0.2"));
+        assert_eq!(anon.deanonymize_one("dst", &tok), None);
+        assert_eq!(anon.deanonymize_one("src", "T_unknown"), None);
+
+        // A single fixed mapping reverses; once two originals share it, it is ambiguous
+        anon.anonymize_one("user", "alice");
+        assert_eq!(anon.deanonymize_one("user", "USER").as_deref(), Some("alice"));
+        anon.anonymize_one("user", "bob");
+        assert_eq!(anon.deanonymize_one("user", "USER"), None);
+    }
+
+    #[test]
+    fn test_key_version_rotation_keeps_old_pairs() {
+        let config = |salt: &str, v: u32| {
+            format!(
+                r#"{{"defaults": {{"mode": "tokenize", "tokenize": {{"salt": "{}", "key_version": {}}}}}}}"#,
+                salt, v
+            )
+        };
+        let v1 = anonymizer_from_json(&config("old", 1)).unwrap();
+        let tok1 = v1.anonymize_one("src", "10.0.0.1").unwrap();
+        assert!(tok1.starts_with("T_v1_"), "{}", tok1);
+
+        let mut v2 = anonymizer_from_json(&config("new", 2)).unwrap();
+        v2.inherit_table(v1);
+        let tok2 = v2.anonymize_one("src", "10.0.0.1").unwrap();
+        assert!(tok2.starts_with("T_v2_"), "{}", tok2);
+        assert_ne!(tok1["T_v1_".len()..], tok2["T_v2_".len()..]);
+
+        // Both epochs are held and reverse to the same original
+        let snap = v2.table.snapshot();
+        assert_eq!(snap["src@v1"]["10.0.0.1"], tok1);
+        assert_eq!(snap["src@v2"]["10.0.0.1"], tok2);
+        assert_eq!(v2.deanonymize_one("src", &tok1).as_deref(), Some("10.0.0.1"));
+        assert_eq!(v2.deanonymize_one("src", &tok2).as_deref(), Some("10.0.0.1"));
+        assert_eq!(v2.deanonymize_one("dst", &tok1), None);
+
+        // Clearing the field drops every version, but not other fields
+        v2.anonymize_one("src_port", "443").unwrap();
+        assert_eq!(v2.clear_field("src"), 2);
+        assert_eq!(v2.deanonymize_one("src", &tok1), None);
+        assert_eq!(v2.deanonymize_one("src", &tok2), None);
+        let snap = v2.table.snapshot();
+        assert!(!snap.contains_key("src@v1") && !snap.contains_key("src@v2"));
+        assert_eq!(snap["src_port@v2"].len(), 1);
+    }
+
+    #[test]
+    fn test_ip_mask() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"tokenize": {"salt": "s"}},
+                "fields": {"src_ip": {"mode": "ip_mask"},
+                           "dst_ip": {"mode": "ip_mask", "fallback": "fixed", "fixed": "NOT_IP",
+                                      "ip_mask": {"v4_prefix": 16, "v6_prefix": 48}}}}"#,
+        )
+        .unwrap();
+        // v4 /24: network kept, last octet replaced but stable
+        let a = anon.anonymize_one("src_ip", "10.1.2.3").unwrap();
+        assert!(a.starts_with("10.1.2."), "{}", a);
+        assert_ne!(a, "10.1.2.3");
+        assert_eq!(anon.anonymize_one("src_ip", "10.1.2.3").unwrap(), a);
+        let other = anon.anonymize_one("src_ip", "10.1.2.4").unwrap();
+        assert!(other.starts_with("10.1.2.") && other != a);
+        assert!(anon.anonymize_one("dst_ip", "10.1.2.3").unwrap().starts_with("10.1."));
+
+        // v6: the /64 (or configured /48) network is kept
+        let v6: std::net::Ipv6Addr =
+            anon.anonymize_one("src_ip", "2001:db8:1:2::42").unwrap().parse().unwrap();
+        assert_eq!(v6.segments()[..4], [0x2001, 0xdb8, 1, 2]);
+        let v6: std::net::Ipv6Addr =
+            anon.anonymize_one("dst_ip", "2001:db8:1:2::42").unwrap().parse().unwrap();
+        assert_eq!(v6.segments()[..3], [0x2001, 0xdb8, 1]);
+
+        // Non-IP values take the field's fallback (tokenize by default)
+        assert!(anon.anonymize_one("src_ip", "localhost").unwrap().starts_with("T_"));
+        assert_eq!(anon.anonymize_one("dst_ip", "localhost").unwrap(), "NOT_IP");
+
+        assert!(anonymizer_from_json(
+            r#"{"fields": {"ip": {"mode": "ip_mask", "ip_mask": {"v4_prefix": 33}}}}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_counter_mode() {
+        let anon = anonymizer_from_json(
+            r#"{"fields": {"user": {"mode": "counter", "tokenize": {"prefix": "user_"}},
+                           "host": {"mode": "counter"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(anon.anonymize_one("user", "alice").unwrap(), "user_1");
+        assert_eq!(anon.anonymize_one("user", "bob").unwrap(), "user_2");
+        assert_eq!(anon.anonymize_one("user", "alice").unwrap(), "user_1");
+        assert_eq!(anon.anonymize_one("user", "carol").unwrap(), "user_3");
+        assert_eq!(anon.anonymize_one("user", "bob").unwrap(), "user_2");
+        // Counters are per field
+        assert_eq!(anon.anonymize_one("host", "alice").unwrap(), "T_1");
+        assert_eq!(anon.total_pairs(), 4);
+        assert_eq!(anon.deanonymize_one("user", "user_3").as_deref(), Some("carol"));
+    }
+
+    #[test]
+    fn test_clear_table_and_field() {
+        let mut anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize"}, "fields": {"user": {"mode": "counter"}}}"#,
+        )
+        .unwrap();
+        let tok = anon.anonymize_one("ip", "10.0.0.1").unwrap();
+        anon.anonymize_one("ip", "10.0.0.2");
+        anon.anonymize_one("user", "alice");
+        assert_eq!(anon.deanonymize_one("ip", &tok).as_deref(), Some("10.0.0.1"));
+
+        assert_eq!(anon.clear_field("ip"), 2);
+        assert_eq!(anon.total_pairs(), 1);
+        assert_eq!(anon.deanonymize_one("ip", &tok), None);
+        assert_eq!(anon.clear_field("ip"), 0);
+
+        anon.anonymize_one("user", "bob");
+        assert_eq!(anon.clear_table(), 2);
+        assert_eq!(anon.total_pairs(), 0);
+        assert_eq!(anon.table_bytes(), 0);
+        // Config survives; counters restart
+        assert_eq!(anon.anonymize_one("user", "bob").unwrap(), "T_1");
+        assert_eq!(anon.anonymize_one("ip", "10.0.0.1").unwrap(), tok);
+    }
+
+    #[test]
+    fn test_date_shift() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"tokenize": {"salt": "s"}},
+                "fields": {"ts": {"mode": "date_shift", "date_shift": {"subject_field": "user"}},
+                           "day": {"mode": "date_shift", "fallback": "fixed",
+                                   "date_shift": {"format": "%Y-%m-%d", "max_days": 30}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(anon.subject_field("ts"), Some("user"));
+        let parse = |s: &str| NaiveDateTime::parse_from_str(s, "%Y/%m/%d %H:%M:%S").unwrap();
+        let shift =
+            |v: &str, who: &str| anon.anonymize_one_for_subject("ts", v, Some(who)).unwrap();
+
+        // Same subject: both timestamps move by the same amount
+        let a1 = shift("2025/10/12 05:07:29", "alice");
+        let a2 = shift("2025/10/14 08:00:00", "alice");
+        assert_ne!(a1, "2025/10/12 05:07:29");
+        assert_eq!(
+            parse(&a2) - parse(&a1),
+            parse("2025/10/14 08:00:00") - parse("2025/10/12 05:07:29")
+        );
+        assert_eq!(shift("2025/10/12 05:07:29", "alice"), a1);
+        // Different subjects get different offsets
+        assert_ne!(shift("2025/10/12 05:07:29", "bob"), a1);
+        // Whole days: the time of day is kept
+        assert!(a1.ends_with(" 05:07:29"));
+        assert_eq!(anon.total_pairs(), 0);
+
+        let d = anon.anonymize_one("day", "2025-10-12").unwrap();
+        let delta = NaiveDate::parse_from_str(&d, "%Y-%m-%d").unwrap()
+            - NaiveDate::from_ymd_opt(2025, 10, 12).unwrap();
+        assert!(delta.num_days().abs() <= 30);
+        // Unparseable values take the fallback
+        assert_eq!(anon.anonymize_one("day", "yesterday").unwrap(), "REDACTED");
+        assert!(anon
+            .anonymize_one_for_subject("ts", "n/a", Some("alice"))
+            .unwrap()
+            .starts_with("T_"));
+    }
+
+    #[test]
+    fn test_token_alphabet() {
+        assert_eq!(encode_in_alphabet(0xff, "0123456789abcdef"), "00000000000000ff");
+        assert_eq!(encode_in_alphabet(u64::MAX, "01"), "1".repeat(64));
+        assert_eq!(encode_in_alphabet(5, "0123456789").len(), 20);
+
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "s"}},
+                "fields": {"hex": {"mode": "tokenize"},
+                           "friendly": {"mode": "tokenize",
+                                        "tokenize": {"prefix": "U_", "token_alphabet": "ABCDEFGHJKLMNPQRSTUVWXYZ23456789"}}}}"#,
+        )
+        .unwrap();
+        let hex = anon.anonymize_one("hex", "alice").unwrap();
+        assert_eq!(hex.len(), 2 + 16);
+        let friendly = anon.anonymize_one("friendly", "alice").unwrap();
+        assert!(friendly.starts_with("U_"));
+        assert_eq!(friendly.len(), 2 + 13);
+        assert!(friendly[2..].chars().all(|c| c.is_ascii_uppercase() || ('2'..='9').contains(&c)));
+        assert!(!friendly.contains('O') && !friendly.contains('0'));
+
+        assert!(anonymizer_from_json(r#"{"defaults": {"tokenize": {"token_alphabet": "aa"}}}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_dry_run_counts_without_replacing() {
+        let anon = anonymizer_from_json(
+            r#"{"dry_run": true, "fields": {
+                "ip": {"mode": "tokenize"},
+                "user": {"mode": "map", "map": {}, "fallback": "reject"}
+            }}"#,
+        )
+        .unwrap();
+        assert!(anon.is_dry_run());
+        assert_eq!(anon.anonymize_one("ip", "10.0.0.1"), None);
+        assert_eq!(anon.anonymize_one("ip", "10.0.0.2"), None);
+        assert_eq!(anon.anonymize_one("user", "bob"), None);
+        assert_eq!(anon.anonymize_one("other", "x"), None);
+        assert_eq!(anon.dry_run_matches().get("ip"), Some(&2));
+        assert!(!anon.dry_run_matches().contains_key("user"));
+        assert_eq!(anon.total_pairs(), 0);
+    }
+
+    #[test]
+    fn test_value_patterns() {
+        let anon = anonymizer_from_json(
+            r#"{"fields": {"user": {"mode": "fixed", "fixed": "U"}},
+                "value_patterns": [
+                  {"pattern": "[\\w.]+@[\\w.]+", "mode": "tokenize", "tokenize": {"prefix": "E_"}},
+                  {"pattern": "tok-[0-9a-f]{8}", "mode": "fixed", "fixed": "<token>"}
+                ]}"#,
+        )
+        .unwrap();
+        let out = anon.anonymize_one("msg", "login a.b@x.org with tok-deadbeef ok").unwrap();
+        assert!(out.starts_with("login E_"), "{}", out);
+        assert!(out.ends_with(" with <token> ok"), "{}", out);
+        // Same match -> same token, in any field
+        let again = anon.anonymize_one("other", "a.b@x.org").unwrap();
+        assert_eq!(&out[6..6 + again.len()], again);
+        // Field rules take precedence; unmatched values pass through
+        assert_eq!(anon.anonymize_one("user", "a.b@x.org").unwrap(), "U");
+        assert_eq!(anon.anonymize_one("msg", "nothing here"), None);
+        assert_eq!(anon.table.get("tok-[0-9a-f]{8}", "tok-deadbeef").as_deref(), Some("<token>"));
+
+        assert!(anonymizer_from_json(r#"{"value_patterns": [{"pattern": "(", "mode": "fixed"}]}"#)
+            .is_err());
+        assert!(anonymizer_from_json(r#"{"value_patterns": [{"pattern": "x", "mode": "map"}]}"#)
+            .is_err());
+    }
+
+    #[test]
+    fn test_anonymize_text_patterns() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "fixed", "fixed": "X"},
+                "fields": {"user": {"mode": "tokenize"}},
+                "patterns": [{"regex": "[\\w.]+@[\\w.]+", "mode": "tokenize", "tokenize": {"prefix": "E_"}}]}"#,
+        )
+        .unwrap();
+        let desc = "mail alice@corp.com and bob@corp.com now";
+        let out = anon.anonymize_text(desc);
+        let parts: Vec<&str> = out.split(' ').collect();
+        assert_eq!((parts[0], parts[2], parts[4]), ("mail", "and", "now"));
+        assert!(parts[1].starts_with("E_") && parts[3].starts_with("E_"));
+        assert_ne!(parts[1], parts[3]);
+        assert_eq!(anon.anonymize_text(desc), out);
+        assert_eq!(anon.anonymize_text("no pii"), "no pii");
+
+        // A field without an explicit rule gets the patterns before the defaults
+        assert_eq!(anon.anonymize_one("description", desc).unwrap(), out);
+        assert_eq!(anon.anonymize_one("description", "no pii").unwrap(), "X");
+        // An explicit field rule takes precedence over patterns
+        assert!(!anon.anonymize_one("user", desc).unwrap().contains("and"));
+    }
+
+    #[test]
+    fn test_concurrent_anonymize_one_is_stable() {
+        let anon = anonymizer_from_json(
+            r#"{"defaults": {"mode": "tokenize", "tokenize": {"salt": "s"}}}"#,
+        )
+        .unwrap();
+        anon.begin_delta();
+        let values: Vec<String> =
+            (0..200).map(|i| format!("10.0.{}.{}", i / 256, i % 256)).collect();
+        let per_thread: Vec<Vec<String>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| {
+                    s.spawn(|| {
+                        // Every thread hammers the same field with the same values
+                        let mut out = Vec::new();
+                        for _ in 0..5 {
+                            out = values
+                                .iter()
+                                .map(|v| anon.anonymize_one("src", v).unwrap())
+                                .collect();
+                        }
+                        out
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for tokens in &per_thread[1..] {
+            assert_eq!(tokens, &per_thread[0]);
+        }
+        // Exactly one table entry and one delta entry per original
+        assert_eq!(anon.total_pairs(), values.len());
+        assert_eq!(anon.end_delta().len(), values.len());
+        for (v, tok) in values.iter().zip(&per_thread[0]) {
+            assert_eq!(anon.table.get("src", v).as_ref(), Some(tok));
+            assert_eq!(anon.deanonymize_one("src", tok).as_ref(), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_delta_tracks_only_new_mappings() {
+        let anon =
+            anonymizer_from_json(r#"{"fields": {"f": {"mode": "fixed", "fixed": "X"}}}"#).unwrap();
+        anon.anonymize_one("f", "before");
+        anon.begin_delta();
+        anon.anonymize_one("f", "before"); // already in the table
+        anon.anonymize_one("f", "new");
+        anon.anonymize_one("f", "new");
+        anon.anonymize_one("other", "passthrough");
+        assert_eq!(
+            anon.end_delta(),
+            vec![NewMapping {
+                field: "f".to_string(),
+                original: "new".to_string(),
+                replacement: "X".to_string()
+            }]
+        );
+        // Not recording anymore
+        anon.anonymize_one("f", "later");
+        assert!(anon.end_delta().is_empty());
+        assert_eq!(anon.total_pairs(), 3);
     }
 }
@@ -0,0 +1,110 @@
+// arena.rs: batch-scoped storage for split fields
+//
+// Splitting with `split_csv_internal` allocates one String per field plus one
+// Vec per line. `FieldArena` instead appends every field of every line into a
+// single growable buffer and records (start, end) spans, so a whole batch costs
+// a handful of amortized allocations that are released together on drop.
+use crate::parser::split_fields_for_type;
+use crate::schema::LoadedSchema;
+use crate::tokenizer::csv_fields;
+
+#[derive(Default)]
+pub struct FieldArena {
+    buf: String,
+    spans: Vec<(usize, usize)>,
+    rows: Vec<(usize, usize)>, // span index range per line
+}
+
+impl FieldArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pre-size for `lines` lines totalling roughly `bytes` bytes.
+    pub fn with_capacity(lines: usize, bytes: usize) -> Self {
+        Self {
+            buf: String::with_capacity(bytes),
+            spans: Vec::with_capacity(lines * 16),
+            rows: Vec::with_capacity(lines),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Drop all rows but keep the allocated capacity for reuse.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+        self.spans.clear();
+        self.rows.clear();
+    }
+
+    /// Split a comma-delimited line into the arena; returns its row index. The
+    /// fields are those of `csv_fields(line)`, as for `split_csv_internal`.
+    pub fn push_line(&mut self, line: &str) -> usize {
+        self.push_row(csv_fields(line))
+    }
+
+    /// Like `push_line`, but honors the schema's per-type delimiter overrides
+    /// and `normalize` annotations.
+    pub fn push_line_for_type(&mut self, line: &str, schema: &LoadedSchema, t: &str) -> usize {
+        if !schema.type_to_delimiter.contains_key(t)
+            && !schema.type_to_field_normalize.contains_key(t)
+        {
+            return self.push_line(line);
+        }
+        self.push_row(split_fields_for_type(line, schema, t))
+    }
+
+    fn push_row<S: AsRef<str>>(&mut self, fields: impl IntoIterator<Item = S>) -> usize {
+        let first = self.spans.len();
+        for f in fields {
+            let start = self.buf.len();
+            self.buf.push_str(f.as_ref());
+            self.spans.push((start, self.buf.len()));
+        }
+        self.rows.push((first, self.spans.len()));
+        self.rows.len() - 1
+    }
+
+    /// Fields of row `row`, borrowed from the arena.
+    pub fn row(&self, row: usize) -> impl ExactSizeIterator<Item = &str> + '_ {
+        let (a, b) = self.rows[row];
+        self.spans[a..b].iter().map(move |&(s, e)| &self.buf[s..e])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldArena;
+    use crate::tokenizer::split_csv_internal;
+
+    #[test]
+    fn test_field_arena_matches_split() {
+        let lines = [
+            "a,b,c",
+            "a,\"b,c\",d,,e",
+            "trailing,comma,",
+            "quoted,\"\"\"q\"\"\"",
+            "",
+            "\"café\",\"🎉\"\"x\"",
+            "\"open",
+        ];
+        let mut arena = FieldArena::new();
+        for line in lines {
+            arena.push_line(line);
+        }
+        assert_eq!(arena.len(), lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            let got: Vec<&str> = arena.row(i).collect();
+            assert_eq!(got, split_csv_internal(line), "line={}", line);
+        }
+        arena.clear();
+        assert!(arena.is_empty());
+    }
+}
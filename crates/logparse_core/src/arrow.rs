@@ -0,0 +1,72 @@
+// arrow.rs: Arrow RecordBatch output for batch parsing (`arrow` feature)
+//
+// Columnar engines ingest Arrow directly, so building the arrays here skips a
+// round trip through one Python dict per line. A RecordBatch has one schema,
+// so the batch must share a single field layout; every column is a nullable
+// Utf8 array with nulls where a line is shorter than the schema.
+use std::sync::Arc;
+
+use arrow_array::builder::StringBuilder;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+
+use crate::parser::split_uniform_batch;
+use crate::schema::LoadedSchema;
+
+/// Parse `lines` in parallel into a RecordBatch with one Utf8 column per schema
+/// field. Fails on the first malformed or unknown-type line, and when lines
+/// map to different field layouts (different log types or subtype layouts).
+pub fn record_batch<S: AsRef<str> + Sync>(
+    lines: &[S],
+    schema: &LoadedSchema,
+) -> Result<RecordBatch, String> {
+    let (rows, (names, _)) = split_uniform_batch(lines, schema)?;
+    if rows.is_empty() {
+        return Ok(RecordBatch::new_empty(Arc::new(Schema::empty())));
+    }
+
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(names.len());
+    for (col, _) in names.iter().enumerate() {
+        let mut b = StringBuilder::with_capacity(rows.len(), 0);
+        for fields in &rows {
+            b.append_option(fields.get(col));
+        }
+        columns.push(Arc::new(b.finish()));
+    }
+    let fields: Vec<Field> = names.iter().map(|n| Field::new(n, DataType::Utf8, true)).collect();
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::record_batch;
+    use crate::schema::LoadedSchema;
+    use arrow_array::{Array, StringArray};
+
+    #[test]
+    fn test_record_batch_columns_and_nulls() {
+        let mut schema = LoadedSchema::default();
+        let fields = ["serial", "time", "vsys", "type", "action"].map(String::from).to_vec();
+        schema.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        schema.type_to_fields.insert("THREAT".to_string(), vec!["a".into(), "b".into()]);
+
+        let lines = ["1,t1,001,TRAFFIC,allow", "2,t2,001,TRAFFIC", "3,t3,001,TRAFFIC,deny"];
+        let batch = record_batch(&lines, &schema).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.num_columns(), 5);
+        assert_eq!(batch.schema().field(4).name(), "action");
+        for col in batch.columns() {
+            assert_eq!(col.len(), 3);
+        }
+        let action = batch.column(4).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(action.null_count(), 1);
+        assert!(action.is_valid(0) && action.is_null(1) && action.is_valid(2));
+        assert_eq!(action.value(2), "deny");
+        assert_eq!(batch.column(0).null_count(), 0);
+
+        let mixed = ["1,t1,001,TRAFFIC,allow", "2,t2,001,THREAT,x"];
+        let err = record_batch(&mixed, &schema).unwrap_err();
+        assert!(err.contains("line 0 is TRAFFIC but line 1 is THREAT"), "{}", err);
+        assert!(record_batch(&["1,t,001,NOPE"], &schema).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+// columnar.rs: column-oriented encodings for batches of parsed records
+use std::collections::HashMap;
+
+/// A dictionary-encoded column: distinct values plus one index per row.
+/// A `None` index marks a null (missing field or a row of another log type).
+#[derive(Default)]
+pub struct DictColumn {
+    pub dictionary: Vec<String>,
+    pub indices: Vec<Option<u32>>,
+    lookup: HashMap<String, u32>,
+}
+
+impl DictColumn {
+    fn push(&mut self, value: Option<&str>) {
+        let idx = value.map(|v| match self.lookup.get(v) {
+            Some(&i) => i,
+            None => {
+                let i = self.dictionary.len() as u32;
+                self.dictionary.push(v.to_string());
+                self.lookup.insert(v.to_string(), i);
+                i
+            }
+        });
+        self.indices.push(idx);
+    }
+}
+
+/// Builds dictionary-encoded columns row by row. Columns appear in first-seen
+/// order; every column always has exactly `rows` indices.
+#[derive(Default)]
+pub struct DictEncodedBatch {
+    pub rows: usize,
+    pub columns: Vec<(String, DictColumn)>,
+    positions: HashMap<String, usize>,
+}
+
+impl DictEncodedBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append one record given the schema field names and the split CSV values.
+    /// Names beyond the available values are encoded as null.
+    pub fn push_row(&mut self, names: &[String], fields: &[String]) {
+        for (i, name) in names.iter().enumerate() {
+            let pos = match self.positions.get(name) {
+                Some(&p) => p,
+                None => {
+                    let col = DictColumn { indices: vec![None; self.rows], ..Default::default() };
+                    self.columns.push((name.clone(), col));
+                    self.positions.insert(name.clone(), self.columns.len() - 1);
+                    self.columns.len() - 1
+                }
+            };
+            let col = &mut self.columns[pos].1;
+            // Duplicate names within one row keep the first value
+            if col.indices.len() == self.rows {
+                col.push(fields.get(i).map(|s| s.as_str()));
+            }
+        }
+        self.rows += 1;
+        for (_, col) in self.columns.iter_mut() {
+            if col.indices.len() < self.rows {
+                col.indices.push(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DictEncodedBatch;
+
+    #[test]
+    fn test_dict_encoded_batch() {
+        let traffic = vec!["type".to_string(), "action".to_string()];
+        let threat = vec!["type".to_string(), "threat_id".to_string()];
+        let mut b = DictEncodedBatch::new();
+        b.push_row(&traffic, &["TRAFFIC".to_string(), "allow".to_string()]);
+        b.push_row(&traffic, &["TRAFFIC".to_string(), "deny".to_string()]);
+        b.push_row(&threat, &["THREAT".to_string()]);
+        b.push_row(&traffic, &["TRAFFIC".to_string(), "allow".to_string()]);
+
+        assert_eq!(b.rows, 4);
+        let names: Vec<&str> = b.columns.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, vec!["type", "action", "threat_id"]);
+
+        let (_, ty) = &b.columns[0];
+        assert_eq!(ty.dictionary, vec!["TRAFFIC", "THREAT"]);
+        assert_eq!(ty.indices, vec![Some(0), Some(0), Some(1), Some(0)]);
+
+        let (_, action) = &b.columns[1];
+        assert_eq!(action.dictionary, vec!["allow", "deny"]);
+        assert_eq!(action.indices, vec![Some(0), Some(1), None, Some(0)]);
+
+        // Column first seen on row 2 is back-filled with nulls; missing value is null
+        let (_, threat_id) = &b.columns[2];
+        assert!(threat_id.dictionary.is_empty());
+        assert_eq!(threat_id.indices, vec![None, None, None, None]);
+    }
+}
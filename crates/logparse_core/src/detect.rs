@@ -0,0 +1,171 @@
+// detect.rs: guess the vendor of a log line from its shape
+//
+// Routers that receive mixed feeds need a schema before they can parse, so this
+// looks only at markers each format is known for: PAN-OS CSV with its log type
+// at index 3, FortiOS `key=value` records with `logid`/`devid`, and Cisco
+// `%FACILITY-SEVERITY-MNEMONIC:` message ids. Nothing is allocated unless a
+// field needs unescaping.
+use crate::tokenizer::csv_fields;
+
+/// Vendor labels returned by `detect`.
+pub const PALO_ALTO: &str = "palo_alto";
+pub const FORTINET: &str = "fortinet";
+pub const CISCO: &str = "cisco";
+
+/// PAN-OS log types found at index 3.
+const PAN_TYPES: &[&str] = &[
+    "TRAFFIC",
+    "THREAT",
+    "SYSTEM",
+    "CONFIG",
+    "HIPMATCH",
+    "HIP-MATCH",
+    "GLOBALPROTECT",
+    "USERID",
+    "DECRYPTION",
+    "AUTHENTICATION",
+    "CORRELATION",
+    "IPTAG",
+    "TUNNEL",
+    "GTP",
+    "SCTP",
+];
+
+/// Best-guess vendor of a line and how sure the guess is, from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub vendor: &'static str,
+    pub confidence: f64,
+}
+
+/// Vendor label of `line` (see `detect`), without the confidence.
+pub fn detect_source(line: &str) -> Option<&'static str> {
+    detect(line).map(|d| d.vendor)
+}
+
+/// Guess whether `line` is Palo Alto, Fortinet or Cisco. A leading syslog
+/// priority (`<134>`) is ignored. `None` when no format's markers are found.
+pub fn detect(line: &str) -> Option<Detection> {
+    let body = strip_priority(line);
+    let found = |vendor, confidence| Some(Detection { vendor, confidence });
+    if let Some(asa) = cisco_message_id(body) {
+        return found(CISCO, if asa { 0.95 } else { 0.7 });
+    }
+    if has_key(body, "logid") {
+        let fortigate = key_value(body, "devid").is_some_and(|v| v.starts_with("FG"));
+        if fortigate {
+            return found(FORTINET, 0.95);
+        }
+        if has_key(body, "type") && has_key(body, "subtype") {
+            return found(FORTINET, 0.8);
+        }
+    }
+    let mut fields = csv_fields(body);
+    let time = fields.nth(1);
+    let log_type = fields.nth(1);
+    let known_type = log_type.is_some_and(|t| PAN_TYPES.contains(&t.as_ref()));
+    let pan_time = time.is_some_and(|t| is_pan_datetime(&t));
+    match (known_type, pan_time) {
+        (true, true) => found(PALO_ALTO, 0.95),
+        (true, false) => found(PALO_ALTO, 0.75),
+        (false, true) if fields.nth(10).is_some() => found(PALO_ALTO, 0.5),
+        _ => None,
+    }
+}
+
+/// `line` without a leading syslog priority such as `<134>`.
+fn strip_priority(line: &str) -> &str {
+    let Some(rest) = line.strip_prefix('<') else { return line };
+    match rest.find('>') {
+        Some(end) if (1..=3).contains(&end) && rest[..end].bytes().all(|b| b.is_ascii_digit()) => {
+            &rest[end + 1..]
+        }
+        _ => line,
+    }
+}
+
+/// Whether `line` has a Cisco `%FACILITY-SEVERITY-MNEMONIC:` message id, and
+/// if so whether the facility is `ASA` or `FTD` (firewall) rather than another.
+fn cisco_message_id(line: &str) -> Option<bool> {
+    let bytes = line.as_bytes();
+    memchr::memchr_iter(b'%', bytes).find_map(|i| {
+        let id = &bytes[i + 1..];
+        let f = id.iter().take_while(|b| b.is_ascii_uppercase() || b.is_ascii_digit()).count();
+        let (facility, rest) = id.split_at(f);
+        let mnemonic = match rest {
+            [b'-', severity, b'-', mnemonic @ ..] if f >= 2 && severity.is_ascii_digit() => {
+                mnemonic
+            }
+            _ => return None,
+        };
+        let m = mnemonic.iter().take_while(|b| b.is_ascii_alphanumeric() || **b == b'_').count();
+        (m > 0 && mnemonic.get(m) == Some(&b':')).then_some(matches!(facility, b"ASA" | b"FTD"))
+    })
+}
+
+/// Whether `line` has `key=` at its start or after a space.
+fn has_key(line: &str, key: &str) -> bool {
+    key_value(line, key).is_some()
+}
+
+/// The value after `key=` (at the line start or after a space), without
+/// surrounding quotes, up to the next space or closing quote.
+fn key_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let mut from = 0;
+    while let Some(pos) = line[from..].find(key) {
+        let at = from + pos;
+        from = at + key.len();
+        let starts = at == 0 || line.as_bytes()[at - 1] == b' ';
+        let Some(value) = line[from..].strip_prefix('=').filter(|_| starts) else { continue };
+        return Some(match value.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or(""),
+            None => value.split(' ').next().unwrap_or(""),
+        });
+    }
+    None
+}
+
+/// `YYYY/MM/DD HH:MM:SS`, the PAN-OS receive time format.
+fn is_pan_datetime(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 19
+        && b.iter().enumerate().all(|(i, c)| match i {
+            4 | 7 => *c == b'/',
+            10 => *c == b' ',
+            13 | 16 => *c == b':',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect, detect_source, CISCO, FORTINET, PALO_ALTO};
+
+    #[test]
+    fn test_detect_vendors() {
+        let pan = "<14>Oct 12 05:07:29 PA-VM 1,2025/10/12 05:07:29,001801000000,TRAFFIC,end,2305,2025/10/12 05:07:29,10.0.0.1,10.0.0.2,0.0.0.0,0.0.0.0,allow-all";
+        let d = detect(pan).unwrap();
+        assert_eq!((d.vendor, d.confidence), (PALO_ALTO, 0.95));
+        let fixture = crate::testdata::TRAFFIC_MIXED;
+        assert_eq!(detect_source(fixture.lines().next().unwrap()), Some(PALO_ALTO));
+
+        let forti = r#"<189>date=2025-10-12 time=05:07:29 devname="FGT60E" devid="FGT60ETK18000000" logid="0000000013" type="traffic" subtype="forward" level="notice" srcip=10.0.0.1"#;
+        let d = detect(forti).unwrap();
+        assert_eq!((d.vendor, d.confidence), (FORTINET, 0.95));
+        let generic = r#"logid="0100032001" type="event" subtype="system" msg="login""#;
+        assert_eq!(detect(generic).unwrap().confidence, 0.8);
+
+        let asa = "<166>Oct 12 2025 05:07:29: %ASA-6-302013: Built outbound TCP connection 1 for outside:10.0.0.2/443";
+        assert_eq!(detect(asa).unwrap().vendor, CISCO);
+        assert_eq!(detect(asa).unwrap().confidence, 0.95);
+        assert_eq!(
+            detect("%LINK-3-UPDOWN: Interface Gi0/1, changed state to down").unwrap().confidence,
+            0.7
+        );
+
+        assert_eq!(detect_source("hello world"), None);
+        assert_eq!(detect_source("a,b,c,d,e"), None);
+        assert_eq!(detect_source("100% done -1-2: ok"), None);
+        assert_eq!(detect_source("catalogid=5 x"), None);
+    }
+}
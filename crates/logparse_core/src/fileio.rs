@@ -0,0 +1,138 @@
+// fileio.rs: open input and output files, transparently gzip-compressed
+//
+// Archived firewall logs are usually stored as `.gz`. Compression is detected
+// from the path suffix unless the caller says otherwise, so file conversions
+// behave the same on plain and gzipped data.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// True for paths ending in `.gz` (any case).
+pub fn is_gzip_path(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".gz")
+}
+
+/// Open `path` for line reading. `compressed` forces gzip decoding on or off;
+/// `None` decides by the `.gz` suffix. Concatenated gzip members (as written
+/// by log rotation that appends) are read as one stream.
+pub fn open_input(path: &str, compressed: Option<bool>) -> io::Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    if compressed.unwrap_or_else(|| is_gzip_path(path)) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(BufReader::new(file)))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+/// A buffered output file, gzip-compressed or plain. Call `finish` when done
+/// so the gzip trailer is written and write errors are reported.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl OutputFile {
+    /// Create `path`; `compressed` as for `open_input`.
+    pub fn create(path: &str, compressed: Option<bool>) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(Self::wrap(file, compressed.unwrap_or_else(|| is_gzip_path(path))))
+    }
+
+    /// Open `path` for appending, creating it if missing; `compressed` as for
+    /// `open_input`. Compressed output is appended as a new gzip member, which
+    /// `open_input` reads back as one stream with what was already there.
+    pub fn append(path: &str, compressed: Option<bool>) -> io::Result<Self> {
+        let file = File::options().append(true).create(true).open(path)?;
+        Ok(Self::wrap(BufWriter::new(file), compressed.unwrap_or_else(|| is_gzip_path(path))))
+    }
+
+    fn wrap(file: BufWriter<File>, compressed: bool) -> Self {
+        if compressed {
+            OutputFile::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            OutputFile::Plain(file)
+        }
+    }
+
+    /// Flush everything, writing the gzip trailer if compressed.
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(mut w) => w.flush(),
+            OutputFile::Gzip(gz) => gz.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(w) => w.write(buf),
+            OutputFile::Gzip(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(w) => w.flush(),
+            OutputFile::Gzip(w) => w.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_gzip_path, open_input, OutputFile};
+    use crate::ndjson::transform_lines;
+    use crate::testdata::TRAFFIC_MIXED;
+    use std::io::{BufRead, Read, Write};
+
+    #[test]
+    fn test_gzip_round_trip_matches_plaintext() {
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let plain_in = dir.join(format!("logparse_fileio_{}.csv", id));
+        let gz_in = dir.join(format!("logparse_fileio_{}.csv.gz", id));
+        let plain_out = dir.join(format!("logparse_fileio_{}.out", id));
+        let gz_out = dir.join(format!("logparse_fileio_{}.out.gz", id));
+        let path = |p: &std::path::PathBuf| p.to_str().unwrap().to_string();
+
+        std::fs::write(&plain_in, TRAFFIC_MIXED).unwrap();
+        let mut w = OutputFile::create(&path(&gz_in), None).unwrap();
+        w.write_all(TRAFFIC_MIXED.as_bytes()).unwrap();
+        w.finish().unwrap();
+        assert_ne!(std::fs::read(&gz_in).unwrap(), TRAFFIC_MIXED.as_bytes());
+
+        // Keep non-empty lines with their line number; skip blank ones
+        let convert = |input: &str, output: &str| {
+            let mut w = OutputFile::create(output, None).unwrap();
+            let n = transform_lines(open_input(input, None).unwrap(), &mut w, 4, |l| {
+                (!l.text.is_empty()).then(|| format!("{}:{}", l.line_no, l.text).into_bytes())
+            })
+            .unwrap();
+            w.finish().unwrap();
+            n
+        };
+        let n_plain = convert(&path(&plain_in), &path(&plain_out));
+        let n_gz = convert(&path(&gz_in), &path(&gz_out));
+        assert_eq!(n_plain, 9);
+        assert_eq!(n_gz, n_plain);
+
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(std::fs::File::open(&gz_out).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, std::fs::read_to_string(&plain_out).unwrap());
+
+        // Explicit flag overrides the suffix
+        let mut raw = open_input(&path(&gz_in), Some(false)).unwrap();
+        assert!(raw.fill_buf().unwrap().starts_with(&[0x1f, 0x8b]));
+        assert!(is_gzip_path("a.CSV.GZ") && !is_gzip_path("a.csv"));
+
+        for p in [plain_in, gz_in, plain_out, gz_out] {
+            let _ = std::fs::remove_file(p);
+        }
+    }
+}
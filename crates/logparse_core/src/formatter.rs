@@ -0,0 +1,307 @@
+// formatter.rs: serialize parsed records into line-oriented text formats
+use std::collections::HashMap;
+
+#[cfg(feature = "msgpack")]
+use crate::parser::{EnrichedRecord, FieldMap};
+
+/// Serialize an enriched record as a MessagePack map with the keys of the
+/// enriched JSON: `parsed` (field -> string or nil), `raw_excerpt`, `hash64`
+/// and `runtime_ns`.
+#[cfg(feature = "msgpack")]
+pub fn to_msgpack(record: &EnrichedRecord) -> Vec<u8> {
+    #[derive(serde::Serialize)]
+    struct Msgpack<'a> {
+        parsed: &'a FieldMap,
+        raw_excerpt: &'a str,
+        hash64: u64,
+        runtime_ns: u64,
+    }
+    let rec = Msgpack {
+        parsed: &record.parsed,
+        raw_excerpt: &record.raw_excerpt,
+        hash64: record.hash64,
+        runtime_ns: record.runtime_ns as u64,
+    };
+    rmp_serde::to_vec_named(&rec).expect("string maps and integers always serialize")
+}
+
+/// Serialize (key, value) pairs as a logfmt line: `key=value key2="value 2"`.
+/// Values containing spaces, `=`, quotes, or control characters are quoted and
+/// escaped; `None` values are omitted per logfmt convention.
+pub fn to_logfmt<'a, I>(pairs: I) -> String
+where
+    I: IntoIterator<Item = (&'a str, Option<&'a str>)>,
+{
+    let mut out = String::new();
+    for (key, value) in pairs {
+        let Some(v) = value else { continue };
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(key);
+        out.push('=');
+        push_logfmt_value(&mut out, v);
+    }
+    out
+}
+
+fn push_logfmt_value(out: &mut String, v: &str) {
+    let needs_quotes = v.chars().any(|c| c == ' ' || c == '=' || c == '"' || c.is_control());
+    if !needs_quotes {
+        out.push_str(v);
+        return;
+    }
+    out.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// The fixed `|`-separated prefix of a CEF line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CefHeader {
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+    pub signature_id: String,
+    pub name: String,
+    pub severity: String,
+}
+
+/// Serialize a parsed record as an ArcSight CEF line:
+/// `CEF:0|vendor|product|version|sig|name|sev|key=value ...`. Header fields
+/// escape `\` and `|`; extension values escape `\`, `=`, `|` and line breaks.
+/// Extensions are emitted in key order so output is stable; `None` values are
+/// omitted.
+pub fn to_cef(parsed: &HashMap<String, Option<String>>, header: CefHeader) -> String {
+    let mut out = String::from("CEF:0");
+    for field in [
+        &header.vendor,
+        &header.product,
+        &header.version,
+        &header.signature_id,
+        &header.name,
+        &header.severity,
+    ] {
+        out.push('|');
+        push_cef_header_value(&mut out, field);
+    }
+    out.push('|');
+    let mut keys: Vec<&String> = parsed.keys().collect();
+    keys.sort();
+    let mut first = true;
+    for key in keys {
+        let Some(v) = &parsed[key] else { continue };
+        if !first {
+            out.push(' ');
+        }
+        first = false;
+        out.push_str(key);
+        out.push('=');
+        push_cef_ext_value(&mut out, v);
+    }
+    out
+}
+
+fn push_cef_header_value(out: &mut String, v: &str) {
+    for c in v.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '|' => out.push_str("\\|"),
+            '\r' | '\n' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+}
+
+fn push_cef_ext_value(out: &mut String, v: &str) {
+    for c in v.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '=' => out.push_str("\\="),
+            '|' => out.push_str("\\|"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// The `|`-separated prefix of a LEEF 2.0 line. `delimiter` separates the
+/// attributes; it should not be `=` or `\`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeefHeader {
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+    pub event_id: String,
+    pub delimiter: char,
+}
+
+/// Serialize a parsed record as an IBM QRadar LEEF 2.0 line:
+/// `LEEF:2.0|vendor|product|version|eventid|delim|key=value<delim>...`. The
+/// delimiter hint is the character itself, or `xHH` for a tab or any other
+/// non-printable or `|` delimiter. Header fields escape `\` and `|`; attribute
+/// values escape `\`, tabs (`\t`), line breaks and the delimiter (with a
+/// backslash). Attributes are emitted in key order; `None` values are omitted.
+pub fn to_leef(parsed: &HashMap<String, Option<String>>, header: LeefHeader) -> String {
+    let mut out = String::from("LEEF:2.0");
+    for field in [&header.vendor, &header.product, &header.version, &header.event_id] {
+        out.push('|');
+        push_cef_header_value(&mut out, field);
+    }
+    out.push('|');
+    let d = header.delimiter;
+    if d.is_ascii_graphic() && d != '|' {
+        out.push(d);
+    } else {
+        out.push_str(&format!("x{:02X}", d as u32));
+    }
+    out.push('|');
+    let mut keys: Vec<&String> = parsed.keys().collect();
+    keys.sort();
+    let mut first = true;
+    for key in keys {
+        let Some(v) = &parsed[key] else { continue };
+        if !first {
+            out.push(d);
+        }
+        first = false;
+        out.push_str(key);
+        out.push('=');
+        push_leef_value(&mut out, v, d);
+    }
+    out
+}
+
+fn push_leef_value(out: &mut String, v: &str, delimiter: char) {
+    for c in v.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c if c == delimiter => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_cef, to_leef, to_logfmt, CefHeader, LeefHeader};
+    use std::collections::HashMap;
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_frames_round_trip() {
+        use super::to_msgpack;
+        use crate::ndjson::transform_frames;
+        use crate::parser::{parse_line_to_map, EnrichedRecord, FieldMap};
+        use crate::testdata::{traffic_schema, TRAFFIC_MIXED};
+
+        #[derive(serde::Deserialize)]
+        struct Decoded {
+            parsed: FieldMap,
+            raw_excerpt: String,
+            hash64: u64,
+        }
+
+        let schema = traffic_schema();
+        let input = TRAFFIC_MIXED;
+
+        let mut out = Vec::new();
+        let n = transform_frames(input.as_bytes(), &mut out, 4, |src| {
+            let rec = EnrichedRecord::parse(&src.text, &schema, 8).ok()?;
+            Some(to_msgpack(&rec))
+        })
+        .unwrap();
+        assert_eq!(n, 6);
+
+        let expected: Vec<&str> =
+            input.lines().filter(|l| parse_line_to_map(l, &schema).is_ok()).collect();
+        let mut rest = out.as_slice();
+        for line in expected {
+            let (len, frame) = rest.split_at(4);
+            let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+            let decoded: Decoded = rmp_serde::from_slice(&frame[..len]).unwrap();
+            assert_eq!(decoded.parsed, parse_line_to_map(line, &schema).unwrap());
+            assert_eq!(decoded.raw_excerpt, &line[..8]);
+            assert_eq!(decoded.hash64, crate::hash::hash64(line.as_bytes()));
+            rest = &frame[len..];
+        }
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_to_logfmt_quoting_and_nulls() {
+        let pairs = vec![
+            ("action", Some("allow")),
+            ("missing", None),
+            ("rule", Some("allow web")),
+            ("query", Some("a=b")),
+            ("msg", Some("say \"hi\"\\now")),
+            ("empty", Some("")),
+        ];
+        assert_eq!(
+            to_logfmt(pairs),
+            r#"action=allow rule="allow web" query="a=b" msg="say \"hi\"\\now" empty="#
+        );
+    }
+
+    #[test]
+    fn test_to_cef_escaping() {
+        let header = CefHeader {
+            vendor: "Palo|Alto".to_string(),
+            product: "PAN-OS".to_string(),
+            version: "10.1".to_string(),
+            signature_id: "TRAFFIC".to_string(),
+            name: "traffic\\log".to_string(),
+            severity: "5".to_string(),
+        };
+        let mut parsed: HashMap<String, Option<String>> = HashMap::new();
+        parsed.insert("query".to_string(), Some("a=b|c".to_string()));
+        parsed.insert("act".to_string(), Some("allow".to_string()));
+        parsed.insert("path".to_string(), Some("C:\\tmp\nx".to_string()));
+        parsed.insert("missing".to_string(), None);
+        assert_eq!(
+            to_cef(&parsed, header),
+            r"CEF:0|Palo\|Alto|PAN-OS|10.1|TRAFFIC|traffic\\log|5|act=allow path=C:\\tmp\nx query=a\=b\|c"
+        );
+    }
+
+    #[test]
+    fn test_to_leef_escaping() {
+        let header = |delimiter| LeefHeader {
+            vendor: "Palo|Alto".to_string(),
+            product: "PAN-OS".to_string(),
+            version: "10.1".to_string(),
+            event_id: "TRAFFIC".to_string(),
+            delimiter,
+        };
+        let mut parsed: HashMap<String, Option<String>> = HashMap::new();
+        parsed.insert("msg".to_string(), Some("a\tb^c".to_string()));
+        parsed.insert("act".to_string(), Some("allow".to_string()));
+        parsed.insert("path".to_string(), Some("C:\\tmp\nx=y".to_string()));
+        parsed.insert("missing".to_string(), None);
+        assert_eq!(
+            to_leef(&parsed, header('\t')),
+            "LEEF:2.0|Palo\\|Alto|PAN-OS|10.1|TRAFFIC|x09|act=allow\tmsg=a\\tb^c\tpath=C:\\\\tmp\\nx=y"
+        );
+        assert_eq!(
+            to_leef(&parsed, header('^')),
+            r"LEEF:2.0|Palo\|Alto|PAN-OS|10.1|TRAFFIC|^|act=allow^msg=a\tb\^c^path=C:\\tmp\nx=y"
+        );
+    }
+}
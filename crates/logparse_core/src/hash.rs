@@ -0,0 +1,172 @@
+// hash.rs: selectable algorithm for the `hash64` of enriched records
+//
+// FNV-1a is the default and needs no dependency. Pipelines that dedupe against
+// stores keyed on xxHash64 can switch the process-wide algorithm instead of
+// hashing every line a second time; xxHash64 is only compiled in with the
+// `xxhash` feature.
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HashAlgo {
+    #[default]
+    Fnv1a,
+    /// xxHash64 with seed 0
+    #[cfg(feature = "xxhash")]
+    XxHash64,
+}
+
+impl HashAlgo {
+    pub fn hash(self, bytes: &[u8]) -> u64 {
+        match self {
+            HashAlgo::Fnv1a => crate::hash64_fnv1a(bytes),
+            #[cfg(feature = "xxhash")]
+            HashAlgo::XxHash64 => xxhash_rust::xxh64::xxh64(bytes, 0),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgo::Fnv1a => "fnv1a",
+            #[cfg(feature = "xxhash")]
+            HashAlgo::XxHash64 => "xxhash64",
+        }
+    }
+
+    /// Parse "fnv1a" or "xxhash64" (case-insensitive).
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "fnv1a" => Ok(HashAlgo::Fnv1a),
+            #[cfg(feature = "xxhash")]
+            "xxhash64" => Ok(HashAlgo::XxHash64),
+            #[cfg(not(feature = "xxhash"))]
+            "xxhash64" => Err("xxhash64 requires the `xxhash` feature".to_string()),
+            other => Err(format!("Unknown hash algorithm: {} (expected fnv1a or xxhash64)", other)),
+        }
+    }
+
+    fn from_u8(v: u8) -> Self {
+        match v {
+            #[cfg(feature = "xxhash")]
+            1 => HashAlgo::XxHash64,
+            _ => HashAlgo::Fnv1a,
+        }
+    }
+
+    fn as_u8(self) -> u8 {
+        match self {
+            HashAlgo::Fnv1a => 0,
+            #[cfg(feature = "xxhash")]
+            HashAlgo::XxHash64 => 1,
+        }
+    }
+}
+
+static HASH_ALGO: AtomicU8 = AtomicU8::new(0);
+
+/// Algorithm used for `hash64` of enriched records from now on, process-wide.
+pub fn set_hash_algo(algo: HashAlgo) {
+    HASH_ALGO.store(algo.as_u8(), Ordering::Relaxed);
+}
+
+pub fn hash_algo() -> HashAlgo {
+    HashAlgo::from_u8(HASH_ALGO.load(Ordering::Relaxed))
+}
+
+/// Hash `bytes` with the current algorithm (see `set_hash_algo`).
+pub fn hash64(bytes: &[u8]) -> u64 {
+    hash_algo().hash(bytes)
+}
+
+/// Buckets `sampled` splits hashes into; rates are honoured to 1/10000.
+const SAMPLE_BUCKETS: u64 = 10_000;
+
+/// Whether the record with hash `hash64` falls in a `rate` (0.0 to 1.0)
+/// sample. Decided by `hash64 % 10000`, so a line is either always or never
+/// sampled at a given rate, and the sample at a lower rate is a subset of the
+/// one at a higher rate.
+pub fn sampled(hash64: u64, rate: f64) -> bool {
+    hash64 % SAMPLE_BUCKETS < (rate * SAMPLE_BUCKETS as f64) as u64
+}
+
+/// Deterministic UUID (version 8, RFC 9562 layout) for a record: the FNV-1a
+/// hash of the whole `line` and the hash of its `log_type`. FNV-1a is used
+/// whatever `set_hash_algo` selects, so every service derives the same id for
+/// the same line. All 64 bits of the line hash are kept; the type fills the
+/// remaining 58 free bits.
+pub fn record_id(line: &[u8], log_type: &str) -> String {
+    let h = crate::hash64_fnv1a(line);
+    let t = crate::hash64_fnv1a(log_type.as_bytes());
+    let id: u128 = ((h >> 16) as u128) << 80
+        | 0x8 << 76
+        | (((h >> 4) & 0xfff) as u128) << 64
+        | 0b10 << 62
+        | ((((h & 0xf) << 58) | (t >> 6)) as u128);
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        id >> 96,
+        (id >> 80) & 0xffff,
+        (id >> 64) & 0xffff,
+        (id >> 48) & 0xffff,
+        id & 0xffff_ffff_ffff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{record_id, sampled, HashAlgo};
+
+    #[test]
+    fn test_fnv1a_known_vectors() {
+        assert_eq!(HashAlgo::Fnv1a.hash(b""), 0xcbf29ce484222325);
+        assert_eq!(HashAlgo::Fnv1a.hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(HashAlgo::Fnv1a.hash(b"foobar"), 0x85944171f73967e8);
+        assert_eq!(HashAlgo::from_name("FNV1A"), Ok(HashAlgo::Fnv1a));
+        assert!(HashAlgo::from_name("md5").is_err());
+    }
+
+    #[test]
+    fn test_record_id_is_stable_and_distinct() {
+        let line = b"1,2025/10/12 05:07:29,001,TRAFFIC,end,10.0.0.1,10.0.0.2,allow";
+        let id = record_id(line, "TRAFFIC");
+        assert_eq!(id, record_id(line, "TRAFFIC"));
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "8");
+        assert!(matches!(&id[19..20], "8" | "9" | "a" | "b"));
+        // Leading 48 bits are the line hash
+        let h = format!("{:016x}", crate::hash64_fnv1a(line));
+        assert_eq!(id[..8], h[..8]);
+        assert_eq!(id[9..13], h[8..12]);
+
+        let other = b"1,2025/10/12 05:07:29,001,TRAFFIC,end,10.0.0.1,10.0.0.3,allow";
+        assert_ne!(record_id(other, "TRAFFIC"), id);
+        assert_ne!(record_id(line, "THREAT"), id);
+        // Pinned, so ids stay the same across releases
+        assert_eq!(id, "73136858-7084-808a-86a9-06a403103f4a");
+    }
+
+    #[test]
+    fn test_sampled_fraction() {
+        let hashes: Vec<u64> = (0..100_000)
+            .map(|i| format!("{},2025/10/12 05:07:29,001,TRAFFIC,end,10.0.{}.1", i, i % 256))
+            .map(|line| HashAlgo::Fnv1a.hash(line.as_bytes()))
+            .collect();
+        let count = |rate: f64| hashes.iter().filter(|&&h| sampled(h, rate)).count();
+        let one_percent = count(0.01);
+        assert!((800..=1200).contains(&one_percent), "{} of 100000 sampled", one_percent);
+        assert!((24_000..=26_000).contains(&count(0.25)));
+        assert_eq!(count(0.0), 0);
+        assert_eq!(count(1.0), hashes.len());
+        // A lower rate samples a subset of a higher one
+        assert!(hashes.iter().all(|&h| !sampled(h, 0.01) || sampled(h, 0.25)));
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn test_xxhash64_known_vectors() {
+        assert_eq!(HashAlgo::XxHash64.hash(b""), 0xef46db3751d8e999);
+        assert_eq!(HashAlgo::XxHash64.hash(b"a"), 0xd24ec4f1a98c6e5b);
+        assert_eq!(HashAlgo::XxHash64.hash(b"abc"), 0x44bc2cf5ad770999);
+        assert_eq!(HashAlgo::from_name("xxhash64"), Ok(HashAlgo::XxHash64));
+        assert_eq!(HashAlgo::XxHash64.name(), "xxhash64");
+    }
+}
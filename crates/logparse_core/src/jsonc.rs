@@ -0,0 +1,92 @@
+// jsonc.rs: tolerate comments and trailing commas in hand-maintained JSON configs
+//
+// Schema and anonymizer files are edited by hand, so `.jsonc`/`.json5` files may
+// carry `//` and `/* */` comments plus trailing commas. Rather than a second
+// parser, these are blanked out and the text goes through serde_json as usual,
+// so the result is identical to the equivalent strict JSON. Removed characters
+// become spaces (newlines are kept), so serde_json error positions still point
+// at the right line and column of the original file.
+
+/// True for paths whose extension opts into comment-tolerant parsing.
+pub fn is_jsonc_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".jsonc") || lower.ends_with(".json5")
+}
+
+/// Replace comments and trailing commas (before `}` or `]`) with whitespace.
+/// String literals, including escaped quotes, are left untouched.
+pub fn strip_jsonc(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let n = bytes.len();
+    let mut out: Vec<u8> = Vec::with_capacity(n);
+    // Index in `out` of a comma that may turn out to be trailing
+    let mut pending_comma: Option<usize> = None;
+    let mut i = 0usize;
+    while i < n {
+        let b = bytes[i];
+        match b {
+            b'"' => {
+                pending_comma = None;
+                let start = i;
+                i += 1;
+                while i < n && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i = (i + 1).min(n);
+                out.extend_from_slice(&bytes[start..i]);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < n && bytes[i] != b'\n' {
+                    out.push(b' ');
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let end = input[i + 2..].find("*/").map_or(n, |p| i + 2 + p + 2);
+                out.extend(bytes[i..end].iter().map(|&c| if c == b'\n' { b'\n' } else { b' ' }));
+                i = end;
+                continue;
+            }
+            b',' => pending_comma = Some(out.len()),
+            b'}' | b']' => {
+                if let Some(p) = pending_comma.take() {
+                    out[p] = b' ';
+                }
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            _ => pending_comma = None,
+        }
+        out.push(b);
+        i += 1;
+    }
+    // Only ASCII bytes were replaced by ASCII spaces, so this stays valid UTF-8
+    String::from_utf8(out).expect("comment stripping preserves UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_jsonc_path, strip_jsonc};
+
+    #[test]
+    fn test_strip_jsonc() {
+        let src = r#"{
+            // line comment with "quotes"
+            "a": "x // not a comment, /* nor this */",
+            /* block
+               comment */ "b": [1, 2, ],
+            "c": "esc \" , ]",
+        }"#;
+        let v: serde_json::Value = serde_json::from_str(&strip_jsonc(src)).unwrap();
+        assert_eq!(v["a"], "x // not a comment, /* nor this */");
+        assert_eq!(v["b"], serde_json::json!([1, 2]));
+        assert_eq!(v["c"], "esc \" , ]");
+        // Line numbers are preserved for error reporting
+        assert_eq!(strip_jsonc(src).lines().count(), src.lines().count());
+
+        assert!(is_jsonc_path("schema.JSONC"));
+        assert!(is_jsonc_path("anon.json5"));
+        assert!(!is_jsonc_path("schema.json"));
+    }
+}
@@ -1,18 +1,68 @@
 // logparse_core: pure Rust library for CSV tokenization and schema-driven parsing + anonymization primitives.
 
 pub mod anonymizer;
+pub mod arena;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod columnar;
+pub mod detect;
+pub mod fileio;
+pub mod formatter;
+pub mod hash;
+pub mod jsonc;
+pub mod ndjson;
 pub mod parser;
+pub mod reader;
 pub mod schema;
+pub mod stats;
+#[cfg(test)]
+mod testdata;
 pub mod tokenizer;
 
 // Re-export commonly used items at the crate root to preserve the public API
-pub use anonymizer::table::anonymizer_from_json;
+pub use anonymizer::table::{
+    anonymizer_from_json, anonymizer_from_json_strict, anonymizer_from_jsonc,
+};
 pub use anonymizer::{
-    AnonConfig, AnonymizerCore, Defaults, FallbackMode, FieldRule, Mode, TokenizeCfg,
+    AnonConfig, AnonymizerCore, DateShiftCfg, Defaults, FallbackMode, FieldFilter, FieldRule,
+    FieldStats, IntegrityTable, IpMaskCfg, Mode, NewMapping, TokenAlgorithm, TokenizeCfg,
+};
+pub use arena::FieldArena;
+pub use columnar::{DictColumn, DictEncodedBatch};
+pub use detect::{detect, detect_source, Detection};
+pub use fileio::{is_gzip_path, open_input, OutputFile};
+#[cfg(feature = "msgpack")]
+pub use formatter::to_msgpack;
+pub use formatter::{to_cef, to_leef, to_logfmt, CefHeader, LeefHeader};
+pub use hash::{hash64, hash_algo, record_id, sampled, set_hash_algo, HashAlgo};
+pub use jsonc::{is_jsonc_path, strip_jsonc};
+pub use ndjson::{
+    enriched_json, enriched_line, transform_frames, transform_lines, transform_lines_bounded,
+    transform_lines_from, unknown_type_json, EnrichedLineOptions, DEFAULT_CHUNK_LINES,
+    DEFAULT_MAX_LINE_BYTES,
+};
+pub use parser::{
+    coerce_value, decode_line, field_names_for_line, filter_stream, parse_epoch_ms, parse_kv_pairs,
+    parse_line_bytes, parse_line_to_map, parse_line_to_map_skipping, parse_line_to_map_strict,
+    parse_line_to_map_with_extra, parse_line_typed, parse_line_with_type_fn, raw_excerpt,
+    split_batch, split_fields_for_type, split_uniform_batch, split_with_layout, type_and_subtype,
+    EnrichedLines, EnrichedRecord, FieldDefaults, FieldMap, Layout, LineParser, ParseError,
+    SkipFields, TypedRecord, TypedValue, DEFAULT_EXCERPT_LEN, REDACTED_EXCERPT,
+};
+pub use reader::{PositionedLines, SourceLine};
+pub use schema::{
+    cached_schema_fields, cached_schema_types, ensure_schema_loaded, load_schema_from_str,
+    load_schema_internal, load_schema_jsonc, remove_cached_log_type, start_watcher, stop_watcher,
+    update_cached_log_type, FieldType, LoadedSchema, Normalize, DEFAULT_DATETIME_FORMAT,
+    DEFAULT_SCHEMA_NAME, DEFAULT_TYPE_INDEX, SCHEMA_CACHE,
+};
+pub use stats::{estimate_file, field_count_histogram, FileEstimate};
+pub use tokenizer::{
+    count_fields, csv_fields, csv_fields_with_delim, extract_field_internal, extract_field_ref,
+    extract_field_with_delim, join_csv, join_csv_with_delim, split_csv_internal, split_csv_into,
+    split_csv_spans, split_csv_strict, split_csv_with_delim, split_csv_with_flags,
+    unterminated_quote, TokenizeError,
 };
-pub use parser::parse_line_to_map;
-pub use schema::{ensure_schema_loaded, load_schema_internal, LoadedSchema, SCHEMA_CACHE};
-pub use tokenizer::{extract_field_internal, split_csv_internal};
 
 // Utility hashing function used by bindings
 pub fn hash64_fnv1a(bytes: &[u8]) -> u64 {
@@ -0,0 +1,437 @@
+// ndjson.rs: ordered, chunked parallel line transforms for file conversion
+//
+// Converting a multi-GB file one line at a time leaves all but one core idle.
+// `transform_lines` reads a chunk of lines, maps the chunk on Rayon, and writes
+// the results in input order before reading the next chunk, so output records
+// keep their line correspondence and memory stays bounded by one chunk.
+// `transform_frames` writes length-prefixed binary frames the same way.
+// `enriched_line` is the per-line conversion shared by the NDJSON entry points.
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use rayon::prelude::*;
+
+use crate::parser::{split_fields_for_type, FieldDefaults, DEFAULT_EXCERPT_LEN};
+use crate::reader::{PositionedLines, SourceLine};
+use crate::schema::LoadedSchema;
+
+/// Lines per chunk used by the file conversion bindings.
+pub const DEFAULT_CHUNK_LINES: usize = 16 * 1024;
+
+/// Longest line the file conversion bindings read by default; longer ones
+/// are skipped (see `transform_lines_bounded`).
+pub const DEFAULT_MAX_LINE_BYTES: usize = 16 * 1024 * 1024;
+
+/// The enriched NDJSON record for one split line, shaped like the enriched
+/// parse results: `parsed` (names beyond the values take their entry in
+/// `defaults`, or null), `raw_excerpt`, `hash64` and `runtime_ns`. `source`
+/// adds `line_no` and `byte_offset` provenance when set.
+pub fn enriched_json(
+    line: &str,
+    names: &[String],
+    fields: &[String],
+    defaults: FieldDefaults,
+    runtime_ns: u128,
+    source: Option<(usize, u64)>,
+    excerpt_len: usize,
+) -> serde_json::Value {
+    // Build JSON object directly using serde_json::Map to minimize allocations
+    let mut parsed = serde_json::Map::with_capacity(names.len());
+    for (i, name) in names.iter().enumerate() {
+        let value = fields.get(i).or_else(|| defaults?.get(i)?.as_ref());
+        parsed.insert(name.clone(), value.map_or(serde_json::Value::Null, |v| v.clone().into()));
+    }
+    let excerpt = crate::parser::raw_excerpt(line, excerpt_len).to_string();
+    let mut root = serde_json::Map::with_capacity(4);
+    root.insert("parsed".to_string(), serde_json::Value::Object(parsed));
+    root.insert("raw_excerpt".to_string(), serde_json::Value::String(excerpt));
+    root.insert("hash64".to_string(), crate::hash::hash64(line.as_bytes()).into());
+    root.insert("runtime_ns".to_string(), (runtime_ns as u64).into());
+    if let Some((line_no, byte_offset)) = source {
+        root.insert("line_no".to_string(), serde_json::Value::from(line_no));
+        root.insert("byte_offset".to_string(), serde_json::Value::from(byte_offset));
+    }
+    serde_json::Value::Object(root)
+}
+
+/// Options for `enriched_line`; the default writes plain records with a
+/// `DEFAULT_EXCERPT_LEN` excerpt and skips unknown types.
+#[derive(Clone, Copy, Debug)]
+pub struct EnrichedLineOptions<'a> {
+    /// Add `line_no` and `byte_offset` from the `SourceLine`.
+    pub provenance: bool,
+    pub excerpt_len: usize,
+    /// Add `record_id` (see `hash::record_id`).
+    pub record_id: bool,
+    /// Write `unknown_type_json` for lines whose type is not in the schema
+    /// instead of skipping them.
+    pub emit_unknown: bool,
+    /// When set, lines of other types are skipped before the full split.
+    pub types: Option<&'a HashSet<String>>,
+    /// Incremented for every line whose type is not in the schema.
+    pub unknown: Option<&'a AtomicUsize>,
+}
+
+impl Default for EnrichedLineOptions<'_> {
+    fn default() -> Self {
+        Self {
+            provenance: false,
+            excerpt_len: DEFAULT_EXCERPT_LEN,
+            record_id: false,
+            emit_unknown: false,
+            types: None,
+            unknown: None,
+        }
+    }
+}
+
+/// The serialized NDJSON record (without the newline) for one source line, as
+/// written by the file and stream conversions: `enriched_json` for lines of a
+/// known type, or `unknown_type_json` with `opts.emit_unknown`. Blank,
+/// malformed and overflowing lines, and unknown types otherwise, give None.
+pub fn enriched_line(
+    src: &SourceLine,
+    schema: &LoadedSchema,
+    opts: &EnrichedLineOptions,
+) -> Option<Vec<u8>> {
+    let line = src.text.as_str();
+    if line.is_empty() {
+        return None;
+    }
+    let t0 = Instant::now();
+    let t = schema.log_type(line).ok()?;
+    if opts.types.is_some_and(|types| !types.contains(&t)) {
+        return None;
+    }
+    let fields = split_fields_for_type(line, schema, &t);
+    let source = opts.provenance.then_some((src.line_no, src.byte_offset));
+    let Some(names) = schema.fields_for_record(&t, &fields) else {
+        if let Some(unknown) = opts.unknown {
+            unknown.fetch_add(1, Ordering::Relaxed);
+        }
+        if !opts.emit_unknown {
+            return None;
+        }
+        let mut rec = unknown_type_json(line, &t, opts.excerpt_len);
+        if let (Some((line_no, byte_offset)), Some(root)) = (source, rec.as_object_mut()) {
+            root.insert("line_no".to_string(), serde_json::Value::from(line_no));
+            root.insert("byte_offset".to_string(), serde_json::Value::from(byte_offset));
+        }
+        return serde_json::to_vec(&rec).ok();
+    };
+    schema.check_overflow(&t, fields.len(), names.len()).ok()?;
+    let runtime_ns = t0.elapsed().as_nanos();
+    let defaults = schema.field_defaults(&t, &fields);
+    let mut rec =
+        enriched_json(line, names, &fields, defaults, runtime_ns, source, opts.excerpt_len);
+    if let (true, Some(root)) = (opts.record_id, rec.as_object_mut()) {
+        let id = crate::hash::record_id(line.as_bytes(), &t);
+        root.insert("record_id".to_string(), serde_json::Value::String(id));
+    }
+    serde_json::to_vec(&rec).ok()
+}
+
+/// Minimal record for a line whose log type `log_type` is not in the schema,
+/// written by the file conversions in place of the parsed record when unknown
+/// types are surfaced rather than skipped.
+pub fn unknown_type_json(line: &str, log_type: &str, excerpt_len: usize) -> serde_json::Value {
+    serde_json::json!({
+        "_unknown_type": log_type,
+        "raw_excerpt": crate::parser::raw_excerpt(line, excerpt_len),
+        "hash64": crate::hash::hash64(line.as_bytes()),
+    })
+}
+
+/// Apply `f` to every line of `reader` and write each `Some` result followed by
+/// `\n`, in input order; `None` skips the line. Lines are processed
+/// `chunk_lines` at a time in parallel. Returns the number of records written.
+pub fn transform_lines<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    chunk_lines: usize,
+    f: F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&SourceLine) -> Option<Vec<u8>> + Sync,
+{
+    transform_lines_from(reader, writer, chunk_lines, 0, f)
+}
+
+/// `transform_lines` resuming after the first `skip_lines` lines of `reader`,
+/// e.g. those an interrupted run already converted. Skipped lines are still
+/// read, so line numbers and byte offsets stay those of the whole input.
+pub fn transform_lines_from<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    chunk_lines: usize,
+    skip_lines: usize,
+    f: F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&SourceLine) -> Option<Vec<u8>> + Sync,
+{
+    let mut lines = PositionedLines::new(reader);
+    transform(&mut lines, writer, chunk_lines, skip_lines, false, f).map(|(count, _)| count)
+}
+
+/// `transform_lines_from` skipping lines longer than `max_line_bytes` without
+/// buffering them (see `PositionedLines::with_max_line_bytes`). Returns the
+/// records written and the over-long lines skipped after `skip_lines`.
+pub fn transform_lines_bounded<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    chunk_lines: usize,
+    skip_lines: usize,
+    max_line_bytes: usize,
+    f: F,
+) -> io::Result<(usize, usize)>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&SourceLine) -> Option<Vec<u8>> + Sync,
+{
+    let mut lines = PositionedLines::with_max_line_bytes(reader, max_line_bytes);
+    transform(&mut lines, writer, chunk_lines, skip_lines, false, f)
+}
+
+/// `transform_lines` for binary records (e.g. MessagePack): each `Some`
+/// result is written as a frame, its length as a 4-byte big-endian integer
+/// followed by the bytes, instead of being terminated by `\n`.
+pub fn transform_frames<R, W, F>(
+    reader: R,
+    writer: &mut W,
+    chunk_lines: usize,
+    f: F,
+) -> io::Result<usize>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&SourceLine) -> Option<Vec<u8>> + Sync,
+{
+    let mut lines = PositionedLines::new(reader);
+    transform(&mut lines, writer, chunk_lines, 0, true, f).map(|(count, _)| count)
+}
+
+/// Records written and over-long lines skipped after the first `skip_lines`.
+fn transform<R, W, F>(
+    lines: &mut PositionedLines<R>,
+    writer: &mut W,
+    chunk_lines: usize,
+    skip_lines: usize,
+    framed: bool,
+    f: F,
+) -> io::Result<(usize, usize)>
+where
+    R: BufRead,
+    W: Write,
+    F: Fn(&SourceLine) -> Option<Vec<u8>> + Sync,
+{
+    let chunk_lines = chunk_lines.max(1);
+    for line in lines.by_ref().take(skip_lines) {
+        line?;
+    }
+    let skipped_before = lines.skipped_long_lines();
+    let mut chunk: Vec<SourceLine> = Vec::with_capacity(chunk_lines);
+    let mut count = 0usize;
+    loop {
+        chunk.clear();
+        for line in lines.by_ref().take(chunk_lines) {
+            chunk.push(line?);
+        }
+        if chunk.is_empty() {
+            break;
+        }
+        let out: Vec<Option<Vec<u8>>> = chunk.par_iter().map(&f).collect();
+        for record in out.into_iter().flatten() {
+            if framed {
+                let len = u32::try_from(record.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "record too large for a frame")
+                })?;
+                writer.write_all(&len.to_be_bytes())?;
+                writer.write_all(&record)?;
+            } else {
+                writer.write_all(&record)?;
+                writer.write_all(b"\n")?;
+            }
+            count += 1;
+        }
+    }
+    Ok((count, lines.skipped_long_lines() - skipped_before))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        enriched_line, transform_lines, transform_lines_bounded, transform_lines_from,
+        EnrichedLineOptions,
+    };
+    use crate::parser::parse_line_to_map;
+    use crate::reader::{PositionedLines, SourceLine};
+    use crate::schema::LoadedSchema;
+    use crate::testdata::{traffic_schema, TRAFFIC_MIXED};
+
+    /// Skips blank, malformed and unknown-type lines, like parse_file_to_ndjson.
+    fn render(schema: &LoadedSchema, src: &SourceLine) -> Option<Vec<u8>> {
+        if src.text.is_empty() {
+            return None;
+        }
+        let map = parse_line_to_map(&src.text, schema).ok()?;
+        let mut keys: Vec<_> = map.into_iter().collect();
+        keys.sort();
+        Some(format!("{}:{:?}", src.line_no, keys).into_bytes())
+    }
+
+    #[test]
+    fn test_transform_lines_matches_serial() {
+        let schema = traffic_schema();
+        let mut serial = Vec::new();
+        let mut serial_count = 0;
+        for line in PositionedLines::new(TRAFFIC_MIXED.as_bytes()) {
+            if let Some(rec) = render(&schema, &line.unwrap()) {
+                serial.extend_from_slice(&rec);
+                serial.push(b'\n');
+                serial_count += 1;
+            }
+        }
+        assert_eq!(serial_count, 6);
+
+        for chunk_lines in [1, 3, 4096] {
+            let mut parallel = Vec::new();
+            let n = transform_lines(TRAFFIC_MIXED.as_bytes(), &mut parallel, chunk_lines, |l| {
+                render(&schema, l)
+            })
+            .unwrap();
+            assert_eq!(n, serial_count);
+            assert_eq!(parallel, serial, "chunk_lines={}", chunk_lines);
+        }
+    }
+
+    #[test]
+    fn test_resume_appends_to_partial_output() {
+        use crate::fileio::OutputFile;
+        use std::io::Read;
+
+        let schema = traffic_schema();
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let path = |name: &str| {
+            dir.join(format!("logparse_resume_{}.{}", id, name)).to_str().unwrap().to_string()
+        };
+        let read = |p: &str| {
+            let mut text = String::new();
+            crate::fileio::open_input(p, None).unwrap().read_to_string(&mut text).unwrap();
+            text
+        };
+        let half: String = TRAFFIC_MIXED.split_inclusive('\n').take(5).collect();
+        for ext in ["", ".gz"] {
+            let (once, resumed) = (path(&format!("once{}", ext)), path(&format!("resumed{}", ext)));
+            let mut w = OutputFile::create(&once, None).unwrap();
+            let total =
+                transform_lines(TRAFFIC_MIXED.as_bytes(), &mut w, 3, |l| render(&schema, l));
+            w.finish().unwrap();
+
+            // A run killed after the first five input lines
+            let mut w = OutputFile::create(&resumed, None).unwrap();
+            let first = transform_lines(half.as_bytes(), &mut w, 3, |l| render(&schema, l));
+            w.finish().unwrap();
+            let mut w = OutputFile::append(&resumed, None).unwrap();
+            let rest = transform_lines_from(TRAFFIC_MIXED.as_bytes(), &mut w, 3, 5, |l| {
+                render(&schema, l)
+            });
+            w.finish().unwrap();
+
+            assert_eq!(first.unwrap() + rest.unwrap(), total.unwrap());
+            assert_eq!(read(&resumed), read(&once), "ext={:?}", ext);
+            let _ = std::fs::remove_file(&once);
+            let _ = std::fs::remove_file(&resumed);
+        }
+    }
+
+    #[test]
+    fn test_over_long_line_skipped_between_good_lines() {
+        let schema = traffic_schema();
+        let long = format!("9,t,001,TRAFFIC,end,{},10.0.0.2,allow", "1".repeat(1 << 20));
+        let input = format!(
+            "1,t,001,TRAFFIC,end,10.0.0.1,10.0.0.2,allow\n{}\n3,t,001,TRAFFIC,end,10.0.0.3,10.0.0.2,deny\n",
+            long
+        );
+        let mut out = Vec::new();
+        let (n, skipped) =
+            transform_lines_bounded(input.as_bytes(), &mut out, 2, 0, 4096, |l| render(&schema, l))
+                .unwrap();
+        assert_eq!((n, skipped), (2, 1));
+        let text = String::from_utf8(out).unwrap();
+        let line_nos: Vec<&str> = text.lines().map(|l| l.split(':').next().unwrap()).collect();
+        assert_eq!(line_nos, ["1", "3"]);
+        assert!(text.contains("10.0.0.3") && !text.contains("111"));
+    }
+
+    #[test]
+    fn test_unknown_type_lines_emitted_on_request() {
+        let schema = traffic_schema();
+        let input = "1,t,001,TRAFFIC,end,10.0.0.1,10.0.0.2,allow\n4,t,001,CONFIG,commit,admin\n";
+        for emit_unknown in [false, true] {
+            let unknown = std::sync::atomic::AtomicUsize::new(0);
+            let mut out = Vec::new();
+            let opts = EnrichedLineOptions {
+                excerpt_len: 8,
+                emit_unknown,
+                unknown: Some(&unknown),
+                ..Default::default()
+            };
+            let n = transform_lines(input.as_bytes(), &mut out, 16, |src| {
+                enriched_line(src, &schema, &opts)
+            })
+            .unwrap();
+            assert_eq!(unknown.into_inner(), 1);
+            assert_eq!(n, if emit_unknown { 2 } else { 1 });
+            let text = String::from_utf8(out).unwrap();
+            if emit_unknown {
+                let last: serde_json::Value =
+                    serde_json::from_str(text.lines().last().unwrap()).unwrap();
+                assert_eq!(last["_unknown_type"], "CONFIG");
+                assert_eq!(last["raw_excerpt"], "4,t,001,");
+                assert_eq!(last["hash64"], crate::hash::hash64(b"4,t,001,CONFIG,commit,admin"));
+            } else {
+                assert!(!text.contains("CONFIG"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_enriched_line_options() {
+        let schema = traffic_schema();
+        let src = |line_no: usize, text: &str| SourceLine {
+            line_no,
+            byte_offset: 100,
+            text: text.to_string(),
+        };
+        let traffic = src(3, TRAFFIC_MIXED.lines().next().unwrap());
+        let threat = src(4, TRAFFIC_MIXED.lines().nth(1).unwrap());
+        let json = |bytes: Vec<u8>| serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+
+        let plain = json(enriched_line(&traffic, &schema, &Default::default()).unwrap());
+        assert_eq!(plain["parsed"]["dst"], "10.0.0.2");
+        assert!(plain.get("line_no").is_none() && plain.get("record_id").is_none());
+        assert!(enriched_line(&src(1, ""), &schema, &Default::default()).is_none());
+
+        let only_traffic: std::collections::HashSet<String> = ["TRAFFIC".to_string()].into();
+        let opts = EnrichedLineOptions {
+            provenance: true,
+            record_id: true,
+            types: Some(&only_traffic),
+            ..Default::default()
+        };
+        assert!(enriched_line(&threat, &schema, &opts).is_none());
+        let rec = json(enriched_line(&traffic, &schema, &opts).unwrap());
+        assert_eq!((rec["line_no"].as_u64(), rec["byte_offset"].as_u64()), (Some(3), Some(100)));
+        assert_eq!(rec["record_id"], crate::hash::record_id(traffic.text.as_bytes(), "TRAFFIC"));
+        assert_eq!(rec["parsed"], plain["parsed"]);
+    }
+}
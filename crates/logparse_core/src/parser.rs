@@ -1,32 +1,808 @@
 // parser.rs: map a CSV log line to a key->value map using a loaded schema
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::time::Instant;
 
-use crate::schema::LoadedSchema;
-use crate::tokenizer::{extract_field_internal, split_csv_internal};
+use chrono::{DateTime, NaiveDateTime};
+use rayon::prelude::*;
+
+use crate::ndjson::{enriched_line, transform_lines, EnrichedLineOptions, DEFAULT_CHUNK_LINES};
+use crate::reader::{PositionedLines, SourceLine};
+use crate::schema::{FieldType, LoadedSchema, DEFAULT_DATETIME_FORMAT};
+use crate::tokenizer::{
+    count_fields, csv_fields, field_start_offset, set_slot, split_csv_into, unterminated_quote,
+};
+
+/// Split `line` into fields for log type `t`. Types with a delimiter override in
+/// the schema keep the comma for the leading fields up to and including the
+/// type discriminator (at `schema.type_index`), and split the remainder with
+/// their own delimiter. Values are normalized as the schema's `normalize`
+/// annotations ask.
+pub fn split_fields_for_type(line: &str, schema: &LoadedSchema, t: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    split_fields_for_type_into(line, schema, t, &mut fields);
+    fields
+}
+
+/// `split_fields_for_type` into a reused buffer, as `split_csv_into` with
+/// `from` 0. Returns the number of fields.
+fn split_fields_for_type_into(
+    line: &str,
+    schema: &LoadedSchema,
+    t: &str,
+    out: &mut Vec<String>,
+) -> usize {
+    let n = match schema.type_to_delimiter.get(t) {
+        Some(&delim) => split_with_delimiter_override(line, schema.type_index, delim, out),
+        None => split_csv_into(line, b',', out, 0),
+    };
+    schema.normalize_record(t, &mut out[..n]);
+    n
+}
+
+/// Comma-split the fields up to and including the type at `type_index`, and
+/// the rest of the line with `delim`.
+fn split_with_delimiter_override(
+    line: &str,
+    type_index: usize,
+    delim: u8,
+    out: &mut Vec<String>,
+) -> usize {
+    let Some(start) = field_start_offset(line, type_index + 1) else {
+        return split_csv_into(line, b',', out, 0);
+    };
+    // `start` is just past the comma that terminates the type field
+    let n = split_csv_into(&line[..start - 1], b',', out, 0);
+    let rest = &line[start..];
+    if rest.is_empty() {
+        set_slot(out, n, "");
+        n + 1
+    } else {
+        split_csv_into(rest, delim, out, n)
+    }
+}
+
+/// Field names for `line` of log type `t`, honoring subtype layouts. The line
+/// is only split when the type declares any.
+pub fn field_names_for_line<'a>(
+    line: &str,
+    schema: &'a LoadedSchema,
+    t: &str,
+) -> Option<&'a Vec<String>> {
+    if !schema.type_subtype_to_fields.contains_key(t) {
+        return schema.type_to_fields.get(t);
+    }
+    schema.fields_for_record(t, &split_fields_for_type(line, schema, t))
+}
+
+/// Field names and, if declared, field types of one record.
+pub type Layout<'s> = (&'s [String], Option<&'s [FieldType]>);
+
+/// Split `line` and resolve its layout. Subtype layouts are untyped; declared
+/// types follow the type-level list.
+pub fn split_with_layout<'s>(
+    line: &str,
+    schema: &'s LoadedSchema,
+) -> Result<(Vec<String>, Layout<'s>), String> {
+    split_with_defaults(line, schema).map(|(fields, layout, _)| (fields, layout))
+}
+
+/// `split_with_layout` plus the record's schema defaults.
+fn split_with_defaults<'s>(
+    line: &str,
+    schema: &'s LoadedSchema,
+) -> Result<(Vec<String>, Layout<'s>, FieldDefaults<'s>), String> {
+    let t = schema.log_type_ref(line)?;
+    let t: &str = &t;
+    if !schema.type_to_fields.contains_key(t) {
+        return Err(format!("Unknown log type in schema: {}", t));
+    }
+    let fields = split_fields_for_type(line, schema, t);
+    let layout = record_layout(schema, t, &fields);
+    schema.check_overflow(t, fields.len(), layout.0.len())?;
+    let defaults = schema.field_defaults(t, &fields);
+    Ok((fields, layout, defaults))
+}
+
+/// Layout of a record of known log type `t` split into `fields`.
+fn record_layout<'s>(schema: &'s LoadedSchema, t: &str, fields: &[String]) -> Layout<'s> {
+    let subtype = fields.get(schema.type_index + 1).map(|s| s.as_str());
+    match schema.subtype_fields(t, subtype) {
+        Some(sub) => (sub, None),
+        None => (&schema.type_to_fields[t], schema.type_to_field_types.get(t).map(|v| &v[..])),
+    }
+}
+
+/// Type and subtype discriminators of a line already split by
+/// `split_with_layout`: the fields at the schema's type index and the one
+/// after it, as `extract_field_internal` reads them from the raw line.
+pub fn type_and_subtype<'a>(
+    fields: &'a [String],
+    schema: &LoadedSchema,
+) -> (Option<&'a str>, Option<&'a str>) {
+    let at = |i: usize| fields.get(i).map(|s| s.as_str());
+    (at(schema.type_index), at(schema.type_index + 1))
+}
+
+/// `split_with_layout` for every line in parallel on Rayon, in input order.
+/// Only fields are produced; callers that want excerpts or hashes add them.
+pub fn split_batch<'s, S: AsRef<str> + Sync>(
+    lines: &[S],
+    schema: &'s LoadedSchema,
+) -> Vec<Result<(Vec<String>, Layout<'s>), String>> {
+    lines.par_iter().map(|line| split_with_layout(line.as_ref(), schema)).collect()
+}
+
+/// `split_batch` for outputs with one set of columns, such as Arrow batches or
+/// DataFrames: fails on the first malformed or unknown-type line, and when
+/// lines map to different field layouts (different log types or subtype
+/// layouts). An empty batch has an empty layout.
+pub fn split_uniform_batch<'s, S: AsRef<str> + Sync>(
+    lines: &[S],
+    schema: &'s LoadedSchema,
+) -> Result<(Vec<Vec<String>>, Layout<'s>), String> {
+    let rows = split_batch(lines, schema).into_iter().collect::<Result<Vec<_>, String>>()?;
+    let Some((first_fields, layout)) = rows.first() else {
+        return Ok((Vec::new(), (&[], None)));
+    };
+    let layout = *layout;
+    let type_of = |fields: &[String]| fields.get(schema.type_index).cloned().unwrap_or_default();
+    for (i, (fields, (other, _))) in rows.iter().enumerate() {
+        if *other != layout.0 {
+            return Err(format!(
+                "Batch mixes log types with different fields: line 0 is {} but line {} is {}",
+                type_of(first_fields),
+                i,
+                type_of(fields)
+            ));
+        }
+    }
+    Ok((rows.into_iter().map(|(fields, _)| fields).collect(), layout))
+}
+
+/// Field name -> value; `None` when the line is shorter than the schema.
+pub type FieldMap = HashMap<String, Option<String>>;
 
 pub fn parse_line_to_map(
     line: &str,
     schema: &LoadedSchema,
 ) -> Result<HashMap<String, Option<String>>, String> {
-    let t = extract_field_internal(line, 3)
-        .ok_or_else(|| "Could not extract log type at index 3".to_string())?;
+    parse_line_to_map_with_extra(line, schema).map(|(map, _)| map)
+}
+
+/// Fields to leave out of a parsed record.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkipFields {
+    /// Fields present in the line with an empty value
+    pub empty: bool,
+    /// Fields missing because the line is shorter than the schema (`None`)
+    pub null: bool,
+}
+
+impl SkipFields {
+    /// Whether a field with raw value `value` stays in the record.
+    pub fn keeps(self, value: Option<&str>) -> bool {
+        match value {
+            None => !self.null,
+            Some("") => !self.empty,
+            Some(_) => true,
+        }
+    }
+}
+
+/// `parse_line_to_map` without the fields `skip` selects.
+pub fn parse_line_to_map_skipping(
+    line: &str,
+    schema: &LoadedSchema,
+    skip: SkipFields,
+) -> Result<FieldMap, String> {
+    let mut map = parse_line_to_map(line, schema)?;
+    map.retain(|_, v| skip.keeps(v.as_deref()));
+    Ok(map)
+}
+
+/// Like `parse_line_to_map`, but also returns, in order, the overflow values at
+/// indexes beyond the schema's field names instead of dropping them.
+pub fn parse_line_to_map_with_extra(
+    line: &str,
+    schema: &LoadedSchema,
+) -> Result<(FieldMap, Vec<String>), String> {
+    let t = schema.log_type_ref(line)?;
     let field_names = schema
         .type_to_fields
-        .get(&t)
+        .get(t.as_ref())
         .ok_or_else(|| format!("Unknown log type in schema: {}", t))?;
-    let fields = split_csv_internal(line);
+    let fields = split_fields_for_type(line, schema, &t);
+    let field_names = schema.fields_for_record(&t, &fields).unwrap_or(field_names);
+    schema.check_overflow(&t, fields.len(), field_names.len())?;
+    let defaults = schema.field_defaults(&t, &fields);
+    let mut fields = fields.into_iter();
     let mut map_out: HashMap<String, Option<String>> = HashMap::new();
     for (i, name) in field_names.iter().enumerate() {
-        let v = if i < fields.len() { Some(fields[i].clone()) } else { None };
-        map_out.insert(name.clone(), v);
+        map_out.insert(name.clone(), fields.next().or_else(|| default_value(defaults, i)));
+    }
+    Ok((map_out, fields.collect()))
+}
+
+/// A record's `LoadedSchema::field_defaults`.
+pub type FieldDefaults<'s> = Option<&'s [Option<String>]>;
+
+/// Schema default for field `i` of a record too short to reach it.
+fn default_value(defaults: FieldDefaults, i: usize) -> Option<String> {
+    defaults?.get(i)?.clone()
+}
+
+/// Line parser for single-threaded hot loops. It keeps the split fields of the
+/// last line and refills them in place, so after the first few lines parsing
+/// lines of a similar shape allocates nothing beyond what the caller asks for.
+/// `S` holds the schema as for `EnrichedLines`.
+pub struct LineParser<S> {
+    schema: S,
+    fields: Vec<String>,
+    len: usize,
+}
+
+impl<S: Borrow<LoadedSchema>> LineParser<S> {
+    pub fn new(schema: S) -> Self {
+        Self { schema, fields: Vec::new(), len: 0 }
+    }
+
+    pub fn schema(&self) -> &LoadedSchema {
+        self.schema.borrow()
+    }
+
+    /// `split_with_layout` into the parser's buffer. The fields stay valid
+    /// until the next call.
+    pub fn split(&mut self, line: &str) -> Result<(&[String], Layout<'_>), String> {
+        self.split_with_defaults(line).map(|(fields, layout, _)| (fields, layout))
+    }
+
+    /// `split` plus the record's `LoadedSchema::field_defaults`, for callers
+    /// that fill in missing trailing fields themselves.
+    pub fn split_with_defaults(
+        &mut self,
+        line: &str,
+    ) -> Result<(&[String], Layout<'_>, FieldDefaults<'_>), String> {
+        let schema = self.schema.borrow();
+        let t = schema.log_type_ref(line)?;
+        if !schema.type_to_fields.contains_key(t.as_ref()) {
+            return Err(format!("Unknown log type in schema: {}", t));
+        }
+        self.len = split_fields_for_type_into(line, schema, &t, &mut self.fields);
+        let fields = &self.fields[..self.len];
+        let layout = record_layout(schema, &t, fields);
+        schema.check_overflow(&t, fields.len(), layout.0.len())?;
+        Ok((fields, layout, schema.field_defaults(&t, fields)))
+    }
+
+    /// `parse_line_to_map` into `out`, which is reused too: values of keys
+    /// already present are overwritten in place and keys the line's layout
+    /// does not have are removed. On error `out` is left unchanged.
+    pub fn parse_into(&mut self, line: &str, out: &mut FieldMap) -> Result<(), String> {
+        let (fields, (names, _), defaults) = self.split_with_defaults(line)?;
+        for (i, name) in names.iter().enumerate() {
+            let default = defaults.and_then(|d| d.get(i)?.as_deref());
+            let value = fields.get(i).map(|s| s.as_str()).or(default);
+            match (out.get_mut(name.as_str()), value) {
+                (Some(Some(slot)), Some(v)) => {
+                    slot.clear();
+                    slot.push_str(v);
+                }
+                (Some(slot), v) => *slot = v.map(str::to_string),
+                (None, v) => {
+                    out.insert(name.clone(), v.map(str::to_string));
+                }
+            }
+        }
+        // Every name is now a key, so extra keys are left from another layout
+        if out.len() > names.len() {
+            out.retain(|k, _| names.contains(k));
+        }
+        Ok(())
+    }
+}
+
+/// Like `parse_line_to_map`, but the log type is computed by `type_fn` from the
+/// raw line and its comma-split fields instead of being read from the field at
+/// `schema.type_index` (3 by default). Use it for formats whose discriminator
+/// is derived, e.g. from two fields or a prefix of one. The returned key is
+/// looked up in the schema as usual; `None` fails the line.
+pub fn parse_line_with_type_fn<F>(
+    line: &str,
+    schema: &LoadedSchema,
+    type_fn: F,
+) -> Result<FieldMap, String>
+where
+    F: Fn(&str, &[&str]) -> Option<String>,
+{
+    let split: Vec<Cow<str>> = csv_fields(line).collect();
+    let refs: Vec<&str> = split.iter().map(|f| f.as_ref()).collect();
+    let t = type_fn(line, &refs).ok_or_else(|| "Could not determine log type".to_string())?;
+    let t = schema.folded_type(&t).cloned().unwrap_or(t);
+    let field_names = schema
+        .type_to_fields
+        .get(&t)
+        .ok_or_else(|| format!("Unknown log type in schema: {}", t))?;
+    // Delimiter overrides still split relative to the type index
+    let fields: Vec<String> = if schema.type_to_delimiter.contains_key(&t) {
+        split_fields_for_type(line, schema, &t)
+    } else {
+        let mut fields: Vec<String> = split.into_iter().map(Cow::into_owned).collect();
+        schema.normalize_record(&t, &mut fields);
+        fields
+    };
+    let field_names = schema.fields_for_record(&t, &fields).unwrap_or(field_names);
+    schema.check_overflow(&t, fields.len(), field_names.len())?;
+    let defaults = schema.field_defaults(&t, &fields);
+    let mut fields = fields.into_iter();
+    Ok(field_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), fields.next().or_else(|| default_value(defaults, i))))
+        .collect())
+}
+
+/// Error from `parse_line_to_map_strict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The line could not be mapped at all (no type field, unknown type)
+    Invalid(String),
+    /// The line has a different number of fields than the schema declares
+    FieldCountMismatch { log_type: String, expected: usize, actual: usize },
+    /// Strict UTF-8 mode: the raw line is not valid UTF-8 from byte `offset`
+    InvalidUtf8 { offset: usize },
+    /// The line is malformed at byte `offset`, in field `field_index` (the
+    /// last field reached when the line ends too early)
+    Malformed { offset: usize, field_index: usize, message: String },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Invalid(msg) => f.write_str(msg),
+            ParseError::FieldCountMismatch { log_type, expected, actual } => write!(
+                f,
+                "Field count mismatch for log type {}: expected {}, got {}",
+                log_type, expected, actual
+            ),
+            ParseError::InvalidUtf8 { offset } => {
+                write!(f, "Invalid UTF-8 in line at byte offset {}", offset)
+            }
+            ParseError::Malformed { offset, field_index, message } => {
+                write!(f, "{} (field {}, byte offset {})", message, field_index, offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `ParseError::Malformed` for a quoted field of `line` that is never closed,
+/// splitting as `split_fields_for_type` does for log type `t` (or on commas
+/// throughout when the type is not known).
+fn unterminated_quote_error(
+    line: &str,
+    schema: &LoadedSchema,
+    t: Option<&str>,
+) -> Option<ParseError> {
+    let delim = t.and_then(|t| schema.type_to_delimiter.get(t));
+    let start = delim.and_then(|_| field_start_offset(line, schema.type_index + 1));
+    let (field_index, offset) = match (delim, start) {
+        (Some(&delim), Some(start)) => unterminated_quote(&line[..start - 1], b',').or_else(|| {
+            let (i, at) = unterminated_quote(&line[start..], delim)?;
+            Some((schema.type_index + 1 + i, start + at))
+        }),
+        _ => unterminated_quote(line, b','),
+    }?;
+    let message = "Unterminated quoted field".to_string();
+    Some(ParseError::Malformed { offset, field_index, message })
+}
+
+/// Like `parse_line_to_map`, but fails when the line has fewer or more fields
+/// than the schema declares for its log type instead of padding with `None` or
+/// dropping the extras, and on quoted fields that are never closed.
+/// Malformed lines report where the problem is (`ParseError::Malformed`).
+pub fn parse_line_to_map_strict(
+    line: &str,
+    schema: &LoadedSchema,
+) -> Result<HashMap<String, Option<String>>, ParseError> {
+    let Ok(t) = schema.log_type(line) else {
+        return Err(unterminated_quote_error(line, schema, None).unwrap_or_else(|| {
+            ParseError::Malformed {
+                offset: line.len(),
+                field_index: count_fields(line).saturating_sub(1),
+                message: format!("Line ends before the log type at index {}", schema.type_index),
+            }
+        }));
+    };
+    if let Some(e) = unterminated_quote_error(line, schema, Some(&t)) {
+        return Err(e);
+    }
+    let field_names = schema
+        .type_to_fields
+        .get(&t)
+        .ok_or_else(|| ParseError::Invalid(format!("Unknown log type in schema: {}", t)))?;
+    let fields = split_fields_for_type(line, schema, &t);
+    let field_names = schema.fields_for_record(&t, &fields).unwrap_or(field_names);
+    if fields.len() != field_names.len() {
+        return Err(ParseError::FieldCountMismatch {
+            log_type: t,
+            expected: field_names.len(),
+            actual: fields.len(),
+        });
+    }
+    Ok(field_names.iter().cloned().zip(fields.into_iter().map(Some)).collect())
+}
+
+/// A field value coerced according to the schema's declared field type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedValue {
+    Null,
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// Output of `parse_line_typed`.
+#[derive(Debug, Default)]
+pub struct TypedRecord {
+    pub values: HashMap<String, TypedValue>,
+    /// Fields whose value could not be coerced to the declared type; their
+    /// raw string is kept in `values`
+    pub coercion_failures: Vec<String>,
+}
+
+/// Coerce `raw` to `ty`. Empty values become `Null`; `None` means the value
+/// does not parse as the declared type.
+pub fn coerce_value(raw: &str, ty: FieldType) -> Option<TypedValue> {
+    if raw.is_empty() {
+        return Some(TypedValue::Null);
+    }
+    match ty {
+        FieldType::String | FieldType::Datetime => Some(TypedValue::Str(raw.to_string())),
+        FieldType::Int => raw.trim().parse().ok().map(TypedValue::Int),
+        FieldType::Float => raw.trim().parse().ok().map(TypedValue::Float),
+        FieldType::Bool => match raw.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Some(TypedValue::Bool(true)),
+            "false" | "no" | "0" => Some(TypedValue::Bool(false)),
+            _ => None,
+        },
+    }
+}
+
+/// Parse `raw` with the strftime-style `format` and return Unix epoch
+/// milliseconds. Formats without a UTC offset (`%z`) are read as UTC.
+pub fn parse_epoch_ms(raw: &str, format: &str) -> Option<i64> {
+    let raw = raw.trim();
+    if let Ok(dt) = DateTime::parse_from_str(raw, format) {
+        return Some(dt.timestamp_millis());
+    }
+    NaiveDateTime::parse_from_str(raw, format).ok().map(|dt| dt.and_utc().timestamp_millis())
+}
+
+/// Like `parse_line_to_map`, but values are coerced to the schema's declared
+/// field types (`"type"` in an object field definition). Missing fields take
+/// their schema default, coerced the same way, or are `Null`. A value that fails coercion falls back to `Str` and is listed in
+/// `coercion_failures` rather than failing the line. `datetime` fields keep
+/// their raw string and add `<name>_epoch_ms`, which is left out when the
+/// timestamp does not match the field's `format`.
+pub fn parse_line_typed(line: &str, schema: &LoadedSchema) -> Result<TypedRecord, String> {
+    let t = schema.log_type(line)?;
+    let field_names = schema
+        .type_to_fields
+        .get(&t)
+        .ok_or_else(|| format!("Unknown log type in schema: {}", t))?;
+    let fields = split_fields_for_type(line, schema, &t);
+    let subtype = fields.get(schema.type_index + 1).map(|s| s.as_str());
+    // Subtype layouts are untyped: their fields don't line up with the
+    // type-level declarations
+    let (field_names, types, formats) = match schema.subtype_fields(&t, subtype) {
+        Some(names) => (names, None, None),
+        None => {
+            (field_names, schema.type_to_field_types.get(&t), schema.type_to_field_formats.get(&t))
+        }
+    };
+    schema.check_overflow(&t, fields.len(), field_names.len())?;
+    let defaults = schema.field_defaults(&t, &fields);
+    let mut fields = fields.into_iter();
+    let mut out = TypedRecord::default();
+    for (i, name) in field_names.iter().enumerate() {
+        let ty = types.and_then(|ts| ts.get(i).copied()).unwrap_or_default();
+        let raw = fields.next().or_else(|| defaults?.get(i)?.clone());
+        let value = match raw {
+            None => TypedValue::Null,
+            Some(raw) if ty == FieldType::Datetime && !raw.is_empty() => {
+                let format = formats
+                    .and_then(|fs| fs.get(i))
+                    .and_then(|f| f.as_deref())
+                    .unwrap_or(DEFAULT_DATETIME_FORMAT);
+                match parse_epoch_ms(&raw, format) {
+                    Some(ms) => {
+                        out.values.insert(format!("{}_epoch_ms", name), TypedValue::Int(ms));
+                    }
+                    None => out.coercion_failures.push(name.clone()),
+                }
+                TypedValue::Str(raw)
+            }
+            Some(raw) => coerce_value(&raw, ty).unwrap_or_else(|| {
+                out.coercion_failures.push(name.clone());
+                TypedValue::Str(raw)
+            }),
+        };
+        out.values.insert(name.clone(), value);
+    }
+    Ok(out)
+}
+
+/// Decode a raw byte line. With `strict_utf8`, invalid UTF-8 fails with
+/// `ParseError::InvalidUtf8` and the offset of the first bad byte, so callers
+/// can quarantine the line instead of altering it. Otherwise invalid sequences
+/// are replaced with U+FFFD.
+pub fn decode_line(line: &[u8], strict_utf8: bool) -> Result<Cow<'_, str>, ParseError> {
+    if strict_utf8 {
+        std::str::from_utf8(line)
+            .map(Cow::Borrowed)
+            .map_err(|e| ParseError::InvalidUtf8 { offset: e.valid_up_to() })
+    } else {
+        Ok(String::from_utf8_lossy(line))
+    }
+}
+
+/// `parse_line_to_map` for a raw byte line; see `decode_line` for `strict_utf8`.
+pub fn parse_line_bytes(
+    line: &[u8],
+    schema: &LoadedSchema,
+    strict_utf8: bool,
+) -> Result<FieldMap, ParseError> {
+    let text = decode_line(line, strict_utf8)?;
+    parse_line_to_map(&text, schema).map_err(ParseError::Invalid)
+}
+
+/// Parse a schema-less `key1=val1 key2="val 2"` line. Tokens are separated by
+/// whitespace and split on their first `=`, so values may contain `=`. A
+/// value starting with `"` runs to the closing quote and may contain spaces
+/// and the escapes `to_logfmt` writes (`\"`, `\\`, `\n`, `\r`, `\t`). A bare
+/// key without `=` maps to an empty string. A repeated key keeps its last value.
+pub fn parse_kv_pairs(line: &str) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| c != '=' && !c.is_whitespace()) {
+            key.push(c);
+        }
+        let mut value = String::new();
+        if chars.next_if_eq(&'=').is_some() {
+            if chars.next_if_eq(&'"').is_some() {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('r') => value.push('\r'),
+                            Some('t') => value.push('\t'),
+                            Some(c) => value.push(c),
+                            None => value.push('\\'),
+                        },
+                        c => value.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    value.push(c);
+                }
+            }
+        }
+        out.insert(key, value);
+    }
+    out
+}
+
+/// Default `raw_excerpt` length, in bytes, of enriched parse results.
+pub const DEFAULT_EXCERPT_LEN: usize = 256;
+
+/// The first `max_len` bytes of `line`, cut back to a char boundary so a
+/// multibyte character is never split; `0` means the whole line.
+pub fn raw_excerpt(line: &str, max_len: usize) -> &str {
+    if max_len == 0 || max_len >= line.len() {
+        return line;
+    }
+    let mut end = max_len;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// `raw_excerpt` of records redacted by `EnrichedRecord::parse_redacting_unknown`.
+pub const REDACTED_EXCERPT: &str = "[REDACTED]";
+
+/// A parsed line plus the raw-line metadata returned by the enriched parse
+/// functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnrichedRecord {
+    pub parsed: FieldMap,
+    /// Leading bytes of the line; see `raw_excerpt`
+    pub raw_excerpt: String,
+    /// FNV-1a hash of the whole line
+    pub hash64: u64,
+    pub runtime_ns: u128,
+}
+
+impl EnrichedRecord {
+    /// Wrap the already parsed fields of `line`; `excerpt_len` as for `raw_excerpt`.
+    pub fn new(line: &str, parsed: FieldMap, runtime_ns: u128, excerpt_len: usize) -> Self {
+        Self {
+            parsed,
+            raw_excerpt: raw_excerpt(line, excerpt_len).to_string(),
+            hash64: crate::hash::hash64(line.as_bytes()),
+            runtime_ns,
+        }
+    }
+
+    /// Parse `line` as `parse_line_to_map` does, timing the parse.
+    pub fn parse(line: &str, schema: &LoadedSchema, excerpt_len: usize) -> Result<Self, String> {
+        Self::parse_with_layout(line, schema, excerpt_len).map(|(rec, _)| rec)
+    }
+
+    /// `parse`, also returning the layout the record was parsed with, e.g. to
+    /// emit its fields in schema order.
+    pub fn parse_with_layout<'s>(
+        line: &str,
+        schema: &'s LoadedSchema,
+        excerpt_len: usize,
+    ) -> Result<(Self, Layout<'s>), String> {
+        let t0 = Instant::now();
+        let (fields, layout, defaults) = split_with_defaults(line, schema)?;
+        let mut fields = fields.into_iter();
+        let parsed = layout
+            .0
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), fields.next().or_else(|| default_value(defaults, i))))
+            .collect();
+        Ok((Self::new(line, parsed, t0.elapsed().as_nanos(), excerpt_len), layout))
+    }
+
+    /// `parse`, except that a line whose log type is not in the schema gives
+    /// a record with no fields and `REDACTED_EXCERPT` as its excerpt instead
+    /// of an error. Its `hash64` is still that of the whole line, so unknown
+    /// lines can be counted and deduplicated without exposing their content.
+    /// Malformed lines still fail.
+    pub fn parse_redacting_unknown(
+        line: &str,
+        schema: &LoadedSchema,
+        excerpt_len: usize,
+    ) -> Result<Self, String> {
+        let t0 = Instant::now();
+        if schema.has_unknown_type(line) {
+            return Ok(Self::redacted(line, t0.elapsed().as_nanos()));
+        }
+        Self::parse(line, schema, excerpt_len)
+    }
+
+    /// A record for `line` exposing only its hash: no fields, and
+    /// `REDACTED_EXCERPT` in place of the excerpt.
+    pub fn redacted(line: &str, runtime_ns: u128) -> Self {
+        Self {
+            parsed: FieldMap::new(),
+            raw_excerpt: REDACTED_EXCERPT.to_string(),
+            hash64: crate::hash::hash64(line.as_bytes()),
+            runtime_ns,
+        }
+    }
+
+    /// Parse a raw byte line (see `decode_line` for `strict_utf8`). `hash64`
+    /// covers the original bytes, so it matches the hash of the undecoded
+    /// line even when invalid sequences were replaced.
+    pub fn parse_bytes(
+        line: &[u8],
+        schema: &LoadedSchema,
+        excerpt_len: usize,
+        strict_utf8: bool,
+    ) -> Result<Self, ParseError> {
+        let t0 = Instant::now();
+        let text = decode_line(line, strict_utf8)?;
+        let parsed = parse_line_to_map(&text, schema).map_err(ParseError::Invalid)?;
+        // Not `new`, which would hash the decoded text as well
+        Ok(Self {
+            parsed,
+            raw_excerpt: raw_excerpt(&text, excerpt_len).to_string(),
+            hash64: crate::hash::hash64(line),
+            runtime_ns: t0.elapsed().as_nanos(),
+        })
+    }
+}
+
+/// Enriched records for the lines of a reader, read one line at a time.
+/// Blank, malformed and unknown-type lines are skipped, as in the file
+/// conversions. `S` is anything that holds the schema, e.g. `&LoadedSchema`
+/// or an `Arc<LoadedSchema>` for an iterator that must own it.
+pub struct EnrichedLines<R, S> {
+    lines: PositionedLines<R>,
+    schema: S,
+    excerpt_len: usize,
+}
+
+impl<R: BufRead, S: Borrow<LoadedSchema>> EnrichedLines<R, S> {
+    pub fn new(reader: R, schema: S, excerpt_len: usize) -> Self {
+        Self { lines: PositionedLines::new(reader), schema, excerpt_len }
+    }
+
+    pub fn schema(&self) -> &LoadedSchema {
+        self.schema.borrow()
+    }
+
+    /// The next item along with the layout its record was parsed with, which
+    /// borrows the schema until the following call.
+    pub fn next_with_layout(
+        &mut self,
+    ) -> Option<io::Result<(SourceLine, EnrichedRecord, Layout<'_>)>> {
+        let schema = self.schema.borrow();
+        for src in self.lines.by_ref() {
+            let src = match src {
+                Ok(src) => src,
+                Err(e) => return Some(Err(e)),
+            };
+            if src.text.is_empty() {
+                continue;
+            }
+            if let Ok((rec, layout)) =
+                EnrichedRecord::parse_with_layout(&src.text, schema, self.excerpt_len)
+            {
+                return Some(Ok((src, rec, layout)));
+            }
+        }
+        None
+    }
+}
+
+impl<R: BufRead, S: Borrow<LoadedSchema>> Iterator for EnrichedLines<R, S> {
+    type Item = io::Result<(SourceLine, EnrichedRecord)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_with_layout().map(|item| item.map(|(src, rec, _)| (src, rec)))
     }
-    Ok(map_out)
+}
+
+/// Write an enriched NDJSON record (see `ndjson::enriched_line`) for every
+/// line of `reader` to `writer`, in input order, as `parse_file_to_ndjson`
+/// does for files: blank, malformed and unknown-type lines are skipped. Lines
+/// are parsed in parallel, `DEFAULT_CHUNK_LINES` at a time, so the input does
+/// not need to fit in memory. Returns the number of records written; the
+/// writer is flushed.
+pub fn filter_stream<R: BufRead, W: Write>(
+    reader: R,
+    writer: &mut W,
+    schema: &LoadedSchema,
+) -> io::Result<usize> {
+    let opts = EnrichedLineOptions::default();
+    let count = transform_lines(reader, writer, DEFAULT_CHUNK_LINES, |src| {
+        enriched_line(src, schema, &opts)
+    })?;
+    writer.flush()?;
+    Ok(count)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_line_to_map;
+    use super::{
+        field_names_for_line, filter_stream, parse_kv_pairs, parse_line_bytes, parse_line_to_map,
+        parse_line_to_map_skipping, parse_line_to_map_strict, parse_line_to_map_with_extra,
+        parse_line_typed, parse_line_with_type_fn, raw_excerpt, split_batch, split_fields_for_type,
+        split_uniform_batch, split_with_layout, type_and_subtype, EnrichedLines, EnrichedRecord,
+        FieldMap, LineParser, ParseError, SkipFields, TypedValue, DEFAULT_EXCERPT_LEN,
+        REDACTED_EXCERPT,
+    };
     use crate::schema::LoadedSchema;
+    use crate::testdata::{traffic_schema, TRAFFIC_MIXED};
     use std::collections::HashMap;
 
     #[test]
@@ -37,7 +813,7 @@ mod tests {
             "TRAFFIC".to_string(),
             vec!["f0".to_string(), "f1".to_string(), "f2".to_string(), "f3".to_string()],
         );
-        let loaded = LoadedSchema { path: "mem".to_string(), mtime: None, type_to_fields };
+        let loaded = LoadedSchema { path: "mem".to_string(), type_to_fields, ..Default::default() };
         let line = "x,y,z,TRAFFIC,sub,foo,bar,baz";
         let map = parse_line_to_map(line, &loaded).expect("parse map");
         assert_eq!(map.get("f0").unwrap().as_deref(), Some("x"));
@@ -45,4 +821,666 @@ mod tests {
         assert_eq!(map.get("f2").unwrap().as_deref(), Some("z"));
         assert_eq!(map.get("f3").unwrap().as_deref(), Some("TRAFFIC"));
     }
+
+    #[test]
+    fn test_parse_line_to_map_skipping() {
+        let mut loaded = LoadedSchema::default();
+        let fields = ["a", "b", "c", "type", "d", "e"].map(String::from).to_vec();
+        loaded.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        // "b" and "d" are empty, "e" is missing
+        let line = "x,,z,TRAFFIC,";
+        let keys = |empty, null| {
+            let map =
+                parse_line_to_map_skipping(line, &loaded, SkipFields { empty, null }).unwrap();
+            let mut keys: Vec<String> = map.into_keys().collect();
+            keys.sort();
+            keys.join(",")
+        };
+        assert_eq!(keys(false, false), "a,b,c,d,e,type");
+        assert_eq!(keys(true, false), "a,c,e,type");
+        assert_eq!(keys(false, true), "a,b,c,d,type");
+        assert_eq!(keys(true, true), "a,c,type");
+    }
+
+    #[test]
+    fn test_split_fields_for_type_with_delimiter_override() {
+        let mut loaded = LoadedSchema::default();
+        loaded.type_to_delimiter.insert("LEGACY".to_string(), b'|');
+        assert_eq!(
+            split_fields_for_type("x,y,z,LEGACY,a|b,c|d", &loaded, "LEGACY"),
+            vec!["x", "y", "z", "LEGACY", "a", "b,c", "d"]
+        );
+        assert_eq!(
+            split_fields_for_type("x,y,z,LEGACY,", &loaded, "LEGACY"),
+            vec!["x", "y", "z", "LEGACY", ""]
+        );
+        // Types without an override use the plain comma split
+        assert_eq!(
+            split_fields_for_type("x,y,z,TRAFFIC,a|b", &loaded, "TRAFFIC"),
+            vec!["x", "y", "z", "TRAFFIC", "a|b"]
+        );
+    }
+
+    #[test]
+    fn test_parse_line_to_map_strict() {
+        let mut loaded = LoadedSchema::default();
+        loaded.type_to_fields.insert(
+            "TRAFFIC".to_string(),
+            vec![
+                "f0".to_string(),
+                "f1".to_string(),
+                "f2".to_string(),
+                "type".to_string(),
+                "a".to_string(),
+            ],
+        );
+        let map = parse_line_to_map_strict("x,y,z,TRAFFIC,allow", &loaded).unwrap();
+        assert_eq!(map.get("a").unwrap().as_deref(), Some("allow"));
+
+        let mismatch = |line: &str| parse_line_to_map_strict(line, &loaded).unwrap_err();
+        assert_eq!(
+            mismatch("x,y,z,TRAFFIC"),
+            ParseError::FieldCountMismatch {
+                log_type: "TRAFFIC".to_string(),
+                expected: 5,
+                actual: 4
+            }
+        );
+        assert_eq!(
+            mismatch("x,y,z,TRAFFIC,allow,extra"),
+            ParseError::FieldCountMismatch {
+                log_type: "TRAFFIC".to_string(),
+                expected: 5,
+                actual: 6
+            }
+        );
+        assert!(matches!(mismatch("x,y,z,OTHER,a"), ParseError::Invalid(_)));
+        // The lenient variant still pads and truncates
+        assert!(parse_line_to_map("x,y,z,TRAFFIC", &loaded).is_ok());
+    }
+
+    #[test]
+    fn test_parse_line_to_map_strict_reports_position() {
+        let mut loaded = LoadedSchema::default();
+        let fields = ["f0", "f1", "f2", "type", "a"].map(String::from).to_vec();
+        loaded.type_to_fields.insert("TRAFFIC".to_string(), fields);
+
+        // Too short to reach the type at index 3
+        let err = parse_line_to_map_strict("x,y", &loaded).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Malformed {
+                offset: 3,
+                field_index: 1,
+                message: "Line ends before the log type at index 3".to_string()
+            }
+        );
+        assert!(err.to_string().ends_with("(field 1, byte offset 3)"));
+
+        let malformed = |line: &str| match parse_line_to_map_strict(line, &loaded) {
+            Err(ParseError::Malformed { offset, field_index, .. }) => (offset, field_index),
+            other => panic!("expected Malformed for {:?}, got {:?}", line, other),
+        };
+        assert_eq!(malformed("x,y,z,TRAFFIC,\"allow"), (14, 4));
+        // The open quote swallows the type field
+        assert_eq!(malformed("x,\"y,z,TRAFFIC,allow"), (2, 1));
+        // Escaped quotes inside a closed field are fine
+        assert!(parse_line_to_map_strict("x,y,z,TRAFFIC,\"a\"\"b\"", &loaded).is_ok());
+    }
+
+    #[test]
+    fn test_parse_line_to_map_with_extra() {
+        let mut loaded = LoadedSchema::default();
+        loaded.type_to_fields.insert(
+            "TRAFFIC".to_string(),
+            vec!["f0".to_string(), "f1".to_string(), "f2".to_string(), "type".to_string()],
+        );
+        // Two fields longer than the schema
+        let (map, extra) =
+            parse_line_to_map_with_extra("x,y,z,TRAFFIC,new1,new2", &loaded).unwrap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("type").unwrap().as_deref(), Some("TRAFFIC"));
+        assert_eq!(extra, vec!["new1", "new2"]);
+
+        let (map, extra) = parse_line_to_map_with_extra("x,y,z,TRAFFIC", &loaded).unwrap();
+        assert!(extra.is_empty());
+        assert_eq!(map, parse_line_to_map("x,y,z,TRAFFIC", &loaded).unwrap());
+    }
+
+    #[test]
+    fn test_type_index_from_schema_fixture() {
+        let root = serde_json::from_str(include_str!("../testdata/asa_type_index0.schema.json"))
+            .expect("fixture json");
+        let schema = crate::schema::build_schema(root).expect("fixture schema");
+        assert_eq!(schema.type_index, 0);
+
+        let map = parse_line_to_map("106023,tcp,10.0.0.1,10.0.0.2,outside_in", &schema).unwrap();
+        assert_eq!(map.get("acl").unwrap().as_deref(), Some("outside_in"));
+        let map =
+            parse_line_to_map("302013,outbound,TCP,10.0.0.1,5000,8.8.8.8,53", &schema).unwrap();
+        assert_eq!(map.get("dst_port").unwrap().as_deref(), Some("53"));
+        // Index 3 is no longer the discriminator
+        let err = parse_line_to_map("x,y,z,106023", &schema).unwrap_err();
+        assert_eq!(err, "Unknown log type in schema: x");
+
+        // Schemas without type_index keep the PAN-OS default
+        assert_eq!(LoadedSchema::default().type_index, 3);
+    }
+
+    #[test]
+    fn test_parse_line_bytes_strict_utf8() {
+        let mut loaded = LoadedSchema::default();
+        loaded.type_to_fields.insert(
+            "TRAFFIC".to_string(),
+            vec![
+                "f0".to_string(),
+                "f1".to_string(),
+                "f2".to_string(),
+                "type".to_string(),
+                "u".to_string(),
+            ],
+        );
+        let bad: &[u8] = b"x,y,z,TRAFFIC,caf\xe9";
+        assert_eq!(
+            parse_line_bytes(bad, &loaded, true).unwrap_err(),
+            ParseError::InvalidUtf8 { offset: 17 }
+        );
+        let lossy = parse_line_bytes(bad, &loaded, false).unwrap();
+        assert_eq!(lossy.get("u").unwrap().as_deref(), Some("caf\u{fffd}"));
+
+        let good = "x,y,z,TRAFFIC,café".as_bytes();
+        assert_eq!(parse_line_bytes(good, &loaded, true).unwrap()["u"].as_deref(), Some("café"));
+    }
+
+    #[test]
+    fn test_parse_line_typed() {
+        let root = serde_json::from_str(
+            r#"{"log_types": {"t": {"type_value": "TRAFFIC", "fields": [
+                "f0", {"name": "port", "type": "int"}, {"name": "blocked", "type": "bool"},
+                "type", {"name": "bytes", "type": "int"}, {"name": "ratio", "type": "float"},
+                {"name": "missing", "type": "int"}, {"name": "retries", "type": "int", "default": "0"}
+            ]}}}"#,
+        )
+        .unwrap();
+        let schema = crate::schema::build_schema(root).unwrap();
+        let rec = parse_line_typed("x,443,true,TRAFFIC,12k,0.5", &schema).unwrap();
+        assert_eq!(rec.values["f0"], TypedValue::Str("x".to_string()));
+        assert_eq!(rec.values["port"], TypedValue::Int(443));
+        assert_eq!(rec.values["blocked"], TypedValue::Bool(true));
+        assert_eq!(rec.values["ratio"], TypedValue::Float(0.5));
+        assert_eq!(rec.values["missing"], TypedValue::Null);
+        // Unparseable int keeps the raw string and is reported
+        assert_eq!(rec.values["bytes"], TypedValue::Str("12k".to_string()));
+        assert_eq!(rec.coercion_failures, vec!["bytes"]);
+
+        // A short line takes the default, coerced to the field's type
+        assert_eq!(rec.values["retries"], TypedValue::Int(0));
+        let rec = parse_line_typed("x,443,true,TRAFFIC,1,0.5,7,3", &schema).unwrap();
+        assert_eq!(rec.values["retries"], TypedValue::Int(3));
+    }
+
+    #[test]
+    fn test_parse_line_typed_datetime() {
+        let root = serde_json::from_str(
+            r#"{"log_types": {"t": {"type_value": "TRAFFIC", "fields": [
+                "f0", {"name": "receive_time", "type": "datetime"}, "f2", "type",
+                {"name": "start", "type": "datetime", "format": "%d-%m-%Y %H:%M:%S %z"}
+            ]}}}"#,
+        )
+        .unwrap();
+        let schema = crate::schema::build_schema(root).unwrap();
+        let line = "x,2025/10/12 05:07:29,y,TRAFFIC,12-10-2025 07:07:29 +0200";
+        let rec = parse_line_typed(line, &schema).unwrap();
+        assert_eq!(rec.values["receive_time"], TypedValue::Str("2025/10/12 05:07:29".to_string()));
+        assert_eq!(rec.values["receive_time_epoch_ms"], TypedValue::Int(1_760_245_649_000));
+        assert_eq!(rec.values["start_epoch_ms"], TypedValue::Int(1_760_245_649_000));
+        assert!(rec.coercion_failures.is_empty());
+
+        // Malformed timestamp keeps the raw value and omits the epoch key
+        let rec = parse_line_typed("x,yesterday,y,TRAFFIC,", &schema).unwrap();
+        assert_eq!(rec.values["receive_time"], TypedValue::Str("yesterday".to_string()));
+        assert!(!rec.values.contains_key("receive_time_epoch_ms"));
+        assert_eq!(rec.values["start"], TypedValue::Null);
+        assert_eq!(rec.coercion_failures, vec!["receive_time"]);
+    }
+
+    #[test]
+    fn test_parse_line_with_type_fn() {
+        let mut loaded = LoadedSchema::default();
+        loaded.type_to_fields.insert(
+            "ASA-302013".to_string(),
+            vec!["tag".to_string(), "proto".to_string(), "src".to_string()],
+        );
+        // Type derived from a prefix of the first field
+        let by_prefix = |_: &str, f: &[&str]| {
+            f.first().and_then(|s| s.strip_prefix('%')).map(|s| s.to_string())
+        };
+        let map = parse_line_with_type_fn("%ASA-302013,tcp,10.0.0.1", &loaded, by_prefix).unwrap();
+        assert_eq!(map["proto"].as_deref(), Some("tcp"));
+        assert_eq!(map["src"].as_deref(), Some("10.0.0.1"));
+        assert!(parse_line_with_type_fn("nope,tcp", &loaded, by_prefix).is_err());
+
+        // Derived from two fields
+        loaded.type_to_fields.insert("A/B".to_string(), vec!["x".to_string()]);
+        let joined = |_: &str, f: &[&str]| Some(format!("{}/{}", f.get(1)?, f.get(2)?));
+        let map = parse_line_with_type_fn("v,A,B", &loaded, joined).unwrap();
+        assert_eq!(map["x"].as_deref(), Some("v"));
+    }
+
+    #[test]
+    fn test_parse_kv_pairs() {
+        let map = parse_kv_pairs(
+            r#"  src=10.0.0.1 msg="allow web traffic" url=/q?a=b&c=d flag note="say \"hi\"\n" empty="" "#,
+        );
+        let get = |k: &str| map.get(k).map(|s| s.as_str());
+        assert_eq!(get("src"), Some("10.0.0.1"));
+        // Quoted values keep their spaces and unescape
+        assert_eq!(get("msg"), Some("allow web traffic"));
+        assert_eq!(get("note"), Some("say \"hi\"\n"));
+        assert_eq!(get("empty"), Some(""));
+        // Only the first `=` separates key and value
+        assert_eq!(get("url"), Some("/q?a=b&c=d"));
+        // Bare flags map to an empty string
+        assert_eq!(get("flag"), Some(""));
+        assert_eq!(map.len(), 6);
+
+        // Round trip through to_logfmt
+        let pairs = [("a", Some("x y")), ("b", Some("p=q")), ("c", Some("tab\there"))];
+        let parsed = parse_kv_pairs(&crate::formatter::to_logfmt(pairs));
+        for (k, v) in pairs {
+            assert_eq!(parsed[k], v.unwrap());
+        }
+        assert!(parse_kv_pairs("   ").is_empty());
+    }
+
+    #[test]
+    fn test_raw_excerpt_respects_char_boundaries() {
+        // 255 ASCII bytes, then "é" (2 bytes) straddling the 256-byte cap
+        let line = format!("{}é,tail", "a".repeat(255));
+        assert!(!line.is_char_boundary(DEFAULT_EXCERPT_LEN));
+        let ex = raw_excerpt(&line, DEFAULT_EXCERPT_LEN);
+        assert_eq!(ex.len(), 255);
+        assert!(ex.bytes().all(|b| b == b'a'));
+        assert_eq!(raw_excerpt(&line, 257), format!("{}é", "a".repeat(255)));
+        assert_eq!(raw_excerpt(&line, 0), line);
+        assert_eq!(raw_excerpt("short", 64), "short");
+    }
+
+    #[test]
+    fn test_raw_excerpt_three_byte_char_at_cap() {
+        // "€" is 3 bytes: bytes 255..258 straddle the cap
+        let line = format!("{}€€", "x".repeat(255));
+        for cap in [256, 257] {
+            assert_eq!(raw_excerpt(&line, cap), "x".repeat(255));
+        }
+        assert_eq!(raw_excerpt(&line, 258), format!("{}€", "x".repeat(255)));
+    }
+
+    #[test]
+    fn test_subtype_layouts_select_field_names() {
+        let root = serde_json::from_str(include_str!("../testdata/threat_subtypes.schema.json"))
+            .expect("fixture parses");
+        let schema = crate::schema::build_schema(root).unwrap();
+        let url = "1,2025/10/12 05:07:29,001,THREAT,url,10.0.0.1,10.0.0.2,example.com/x,news";
+        let vuln = "1,2025/10/12 05:07:29,001,THREAT,vulnerability,10.0.0.1,10.0.0.2,30001,high";
+        let other = "1,2025/10/12 05:07:29,001,THREAT,spyware,10.0.0.1,10.0.0.2,c2.example";
+
+        // Field 7 has a different name per subtype
+        let m = parse_line_to_map(url, &schema).unwrap();
+        assert_eq!(m["url"].as_deref(), Some("example.com/x"));
+        assert_eq!(m["category"].as_deref(), Some("news"));
+        assert!(!m.contains_key("threat_id"));
+        let m = parse_line_to_map(vuln, &schema).unwrap();
+        assert_eq!(m["threat_id"].as_deref(), Some("30001"));
+        assert!(!m.contains_key("url"));
+        // Subtypes without a layout use the type-level list
+        let m = parse_line_to_map(other, &schema).unwrap();
+        assert_eq!(m["misc"].as_deref(), Some("c2.example"));
+        assert_eq!(field_names_for_line(url, &schema, "THREAT").unwrap()[7], "url");
+
+        // A plain `subtypes` list is documentation only
+        assert!(!schema.type_subtype_to_fields.contains_key("TRAFFIC"));
+        let m = parse_line_to_map("1,t,001,TRAFFIC,end,a,b", &schema).unwrap();
+        assert_eq!(m["dst"].as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_enriched_record() {
+        let mut schema = LoadedSchema::default();
+        let fields = ["f0", "f1", "f2", "type", "action"].map(String::from).to_vec();
+        schema.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        let line = "a,b,c,TRAFFIC,allow";
+        let rec = EnrichedRecord::parse(line, &schema, 8).unwrap();
+        assert_eq!(rec.parsed["action"].as_deref(), Some("allow"));
+        assert_eq!(rec.parsed.len(), 5);
+        assert_eq!(rec.raw_excerpt, "a,b,c,TR");
+        assert_eq!(rec.hash64, crate::hash64_fnv1a(line.as_bytes()));
+        assert!(EnrichedRecord::parse("a,b,c,NOPE", &schema, 8).is_err());
+
+        let manual = EnrichedRecord::new(line, rec.parsed.clone(), 7, 0);
+        assert_eq!(manual.raw_excerpt, line);
+        assert_eq!(manual.runtime_ns, 7);
+    }
+
+    #[test]
+    fn test_redacting_unknown_keeps_hash() {
+        let mut schema = LoadedSchema::default();
+        let fields = ["f0", "f1", "f2", "type", "action"].map(String::from).to_vec();
+        schema.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        let unknown = "10.0.0.1,secret,c,NOPE,deny";
+        let rec = EnrichedRecord::parse_redacting_unknown(unknown, &schema, 0).unwrap();
+        assert_eq!(rec.raw_excerpt, REDACTED_EXCERPT);
+        assert!(rec.parsed.is_empty());
+        assert_eq!(rec.hash64, crate::hash64_fnv1a(unknown.as_bytes()));
+
+        let known = EnrichedRecord::parse_redacting_unknown("a,b,c,TRAFFIC,allow", &schema, 0);
+        assert_eq!(known.unwrap().parsed["action"].as_deref(), Some("allow"));
+        assert!(EnrichedRecord::parse_redacting_unknown("a,b", &schema, 0).is_err());
+    }
+
+    #[test]
+    fn test_enriched_record_from_invalid_utf8_bytes() {
+        let mut schema = LoadedSchema::default();
+        let fields = ["f0", "f1", "f2", "type", "msg"].map(String::from).to_vec();
+        schema.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        let line: &[u8] = b"a,b,c,TRAFFIC,bad \xff\xfe text";
+        let rec = EnrichedRecord::parse_bytes(line, &schema, 0, false).unwrap();
+        assert_eq!(rec.parsed["type"].as_deref(), Some("TRAFFIC"));
+        assert_eq!(rec.parsed["msg"].as_deref(), Some("bad \u{fffd}\u{fffd} text"));
+        assert_eq!(rec.hash64, crate::hash64_fnv1a(line));
+        assert_ne!(rec.hash64, crate::hash64_fnv1a(rec.raw_excerpt.as_bytes()));
+        assert_eq!(
+            EnrichedRecord::parse_bytes(line, &schema, 0, true).unwrap_err(),
+            ParseError::InvalidUtf8 { offset: 18 }
+        );
+    }
+
+    #[test]
+    fn test_split_batch_matches_per_line_parse() {
+        let schema = traffic_schema();
+        let fixture: Vec<&str> = TRAFFIC_MIXED.lines().collect();
+        let lines: Vec<String> = (0..10_000)
+            .map(|i| fixture[i % fixture.len()].replacen("10.0.0", &format!("10.{}.0", i % 251), 1))
+            .collect();
+
+        let batch = split_batch(&lines, &schema);
+        assert_eq!(batch.len(), lines.len());
+        for (line, got) in lines.iter().zip(batch) {
+            match (got, parse_line_to_map(line, &schema)) {
+                (Ok((values, (names, types))), Ok(want)) => {
+                    assert!(types.is_none());
+                    let mut values = values.into_iter();
+                    let got: HashMap<String, Option<String>> =
+                        names.iter().map(|n| (n.clone(), values.next())).collect();
+                    assert_eq!(got, want, "line={:?}", line);
+                }
+                (Err(got), Err(want)) => assert_eq!(got, want),
+                (got, want) => panic!("{:?}: {:?} vs {:?}", line, got.is_ok(), want.is_ok()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_uniform_batch_rejects_mixed_layouts() {
+        let mut schema = LoadedSchema::default();
+        let fields = ["serial", "time", "vsys", "type", "action"].map(String::from).to_vec();
+        schema.type_to_fields.insert("TRAFFIC".to_string(), fields);
+        schema.type_to_fields.insert("THREAT".to_string(), vec!["a".into(), "b".into()]);
+
+        let (rows, (names, _)) =
+            split_uniform_batch(&["1,t1,001,TRAFFIC,allow", "2,t2,001,TRAFFIC"], &schema).unwrap();
+        assert_eq!(names.len(), 5);
+        assert_eq!(
+            rows,
+            vec![vec!["1", "t1", "001", "TRAFFIC", "allow"], vec!["2", "t2", "001", "TRAFFIC"]]
+        );
+
+        let err = split_uniform_batch(&["1,t1,001,TRAFFIC,allow", "2,t2,001,THREAT,x"], &schema)
+            .unwrap_err();
+        assert!(err.contains("line 0 is TRAFFIC but line 1 is THREAT"), "{}", err);
+        let (rows, (names, _)) = split_uniform_batch::<&str>(&[], &schema).unwrap();
+        assert!(rows.is_empty() && names.is_empty());
+    }
+
+    #[test]
+    fn test_type_and_subtype_match_raw_extraction() {
+        let schema = traffic_schema();
+        let mut checked = 0;
+        for line in TRAFFIC_MIXED.lines() {
+            let Ok((fields, _)) = split_with_layout(line, &schema) else { continue };
+            let (t, st) = type_and_subtype(&fields, &schema);
+            assert_eq!(t.map(String::from), crate::tokenizer::extract_field_internal(line, 3));
+            assert_eq!(st.map(String::from), crate::tokenizer::extract_field_internal(line, 4));
+            checked += 1;
+        }
+        assert_eq!(checked, 6);
+    }
+
+    #[test]
+    fn test_enriched_lines_skips_unparseable() {
+        let schema = traffic_schema();
+        let fixture = TRAFFIC_MIXED;
+
+        let got: Vec<(usize, String, String)> = EnrichedLines::new(fixture.as_bytes(), &schema, 0)
+            .map(|r| {
+                let (src, rec) = r.unwrap();
+                assert_eq!(rec.raw_excerpt, src.text);
+                let p = |k: &str| rec.parsed[k].clone().unwrap();
+                (src.line_no, p("serial"), p("dst"))
+            })
+            .collect();
+        let want = [
+            (1, "1", "10.0.0.2"),
+            (2, "2", "evil.example"),
+            (5, "3", "10.0.0.5"),
+            (7, "5", "10.0.0.7"),
+            (9, "6", "a,b.exe"),
+            (10, "7", "10.0.0.10"),
+        ];
+        let want: Vec<_> =
+            want.iter().map(|&(n, s, d)| (n, s.to_string(), d.to_string())).collect();
+        assert_eq!(got, want);
+
+        // Records laid out by a subtype come with that layout
+        let mut schema = schema;
+        let url = ["serial", "time", "vsys", "type", "subtype", "src", "url", "verdict"];
+        let url: Vec<String> = url.map(String::from).to_vec();
+        schema
+            .type_subtype_to_fields
+            .insert("THREAT".to_string(), [("url".to_string(), url.clone())].into());
+        let mut lines = EnrichedLines::new(fixture.as_bytes(), &schema, 0);
+        let mut url_lines = 0;
+        while let Some(item) = lines.next_with_layout() {
+            let (src, rec, (names, types)) = item.unwrap();
+            assert!(types.is_none());
+            assert_eq!(rec.parsed.len(), names.len());
+            assert!(names.iter().all(|n| rec.parsed.contains_key(n)));
+            if names == url {
+                assert_eq!(src.line_no, 2);
+                assert_eq!(rec.parsed["url"].as_deref(), Some("evil.example"));
+                url_lines += 1;
+            }
+        }
+        assert_eq!(url_lines, 1);
+    }
+
+    #[test]
+    fn test_filter_stream_writes_enriched_ndjson() {
+        use std::io::Cursor;
+
+        let mut schema = traffic_schema();
+        // TRAFFIC lines stop before `rule`, which has a default
+        let traffic = schema.type_to_fields.get_mut("TRAFFIC").unwrap();
+        traffic.push("rule".to_string());
+        let mut defaults = vec![None; traffic.len()];
+        defaults[8] = Some("none".to_string());
+        schema.type_to_field_defaults.insert("TRAFFIC".to_string(), defaults);
+        let fixture = TRAFFIC_MIXED;
+
+        let mut out = Cursor::new(Vec::new());
+        let n = filter_stream(Cursor::new(fixture), &mut out, &schema).unwrap();
+        assert_eq!(n, 6);
+        let text = String::from_utf8(out.into_inner()).unwrap();
+        let records: Vec<serde_json::Value> =
+            text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), n);
+        let serials: Vec<&str> =
+            records.iter().map(|r| r["parsed"]["serial"].as_str().unwrap()).collect();
+        assert_eq!(serials, ["1", "2", "3", "5", "6", "7"]);
+        let first = fixture.lines().next().unwrap();
+        assert_eq!(records[0]["raw_excerpt"], first);
+        assert_eq!(records[0]["hash64"], crate::hash::hash64(first.as_bytes()));
+        assert!(records[0]["runtime_ns"].is_u64());
+        assert_eq!(records[0]["parsed"]["rule"], "none");
+        assert!(records[1]["parsed"].get("rule").is_none());
+    }
+
+    #[test]
+    fn test_strict_fields_rejects_overflow() {
+        let root = serde_json::from_str(
+            r#"{"strict_fields": true, "log_types": {
+                "t": {"type_value": "TRAFFIC", "fields": ["f0", "f1", "f2", "type", "action"]}
+            }}"#,
+        )
+        .unwrap();
+        let schema = crate::schema::build_schema(root).unwrap();
+        let exact = "x,y,z,TRAFFIC,allow";
+        assert_eq!(parse_line_to_map(exact, &schema).unwrap()["action"].as_deref(), Some("allow"));
+        assert!(split_with_layout(exact, &schema).is_ok());
+        // Short lines are still padded
+        assert!(parse_line_to_map("x,y,z,TRAFFIC", &schema).is_ok());
+
+        let long = "x,y,z,TRAFFIC,allow,new1,new2";
+        let err = parse_line_to_map(long, &schema).unwrap_err();
+        assert_eq!(
+            err,
+            "Log type TRAFFIC: 2 fields beyond the 5 named in the schema (strict_fields)"
+        );
+        assert_eq!(split_with_layout(long, &schema).unwrap_err(), err);
+        assert_eq!(parse_line_to_map_with_extra(long, &schema).unwrap_err(), err);
+        assert_eq!(parse_line_typed(long, &schema).unwrap_err(), err);
+    }
+
+    #[test]
+    fn test_case_insensitive_types() {
+        let json = |flag: bool| {
+            format!(
+                r#"{{"case_insensitive_types": {}, "log_types": {{
+                    "t": {{"type_value": "TRAFFIC", "fields": ["f0", "f1", "f2", "type", "action"]}}
+                }}}}"#,
+                flag
+            )
+        };
+        let line = "x,y,z,Traffic,allow";
+        let exact = crate::schema::load_schema_from_str(&json(false)).unwrap();
+        assert!(parse_line_to_map(line, &exact).unwrap_err().contains("Unknown log type"));
+
+        let mut schema = crate::schema::load_schema_from_str(&json(true)).unwrap();
+        let map = parse_line_to_map(line, &schema).unwrap();
+        assert_eq!(map["type"].as_deref(), Some("Traffic"));
+        assert_eq!(map["action"].as_deref(), Some("allow"));
+        assert_eq!(schema.log_type(line).unwrap(), "TRAFFIC");
+        assert!(split_with_layout("x,y,z,\"traffic\",allow", &schema).is_ok());
+        assert!(parse_line_to_map("x,y,z,TRAFFIC,allow", &schema).is_ok());
+
+        // Types added at runtime are indexed too
+        schema.update_log_type("Threat", &["a".to_string()]).unwrap();
+        assert_eq!(schema.log_type("1,2,3,THREAT").unwrap(), "Threat");
+        schema.remove_log_type("Threat");
+        assert_eq!(schema.log_type("1,2,3,THREAT").unwrap(), "THREAT");
+
+        // Hot-patching a case variant of an existing type is refused, not redirected
+        let err = schema.update_log_type("Traffic", &["a".to_string()]).unwrap_err();
+        assert!(err.contains("TRAFFIC and Traffic differ only in case"), "{}", err);
+        assert!(!schema.type_to_fields.contains_key("Traffic"));
+        assert_eq!(schema.log_type(line).unwrap(), "TRAFFIC");
+        assert!(schema.update_log_type("TRAFFIC", &["a".to_string()]).unwrap());
+
+        let clash = r#"{"case_insensitive_types": true, "log_types": {
+            "a": {"type_value": "TRAFFIC", "fields": ["f0"]},
+            "b": {"type_value": "Traffic", "fields": ["f0"]}
+        }}"#;
+        let err = crate::schema::load_schema_from_str(clash).err().unwrap();
+        assert!(err.contains("differ only in case"), "{}", err);
+    }
+
+    #[test]
+    fn test_normalize_annotations() {
+        let root = serde_json::from_str(
+            r#"{"log_types": {"t": {"type_value": "TRAFFIC", "fields": [
+                "f0", {"name": "host", "normalize": "lowercase"}, {"name": "user", "normalize": "trim"},
+                "type", {"name": "action", "normalize": "lowercase_trim"}, "note"
+            ]}}}"#,
+        )
+        .unwrap();
+        let schema = crate::schema::build_schema(root).unwrap();
+        let line = "1,FW-Edge.Example.COM,  alice ,TRAFFIC, Allow , Kept As Is ";
+        let map = parse_line_to_map(line, &schema).unwrap();
+        let get = |k: &str| map[k].as_deref().unwrap();
+        assert_eq!(get("host"), "fw-edge.example.com");
+        assert_eq!(get("user"), "alice");
+        assert_eq!(get("action"), "allow");
+        // Unannotated fields are untouched
+        assert_eq!(get("note"), " Kept As Is ");
+        // The dict fast path splits through the same code
+        let (fields, _) = split_with_layout(line, &schema).unwrap();
+        assert_eq!(fields[1], "fw-edge.example.com");
+        assert_eq!(fields[2], "alice");
+        let mut arena = crate::arena::FieldArena::new();
+        let row = arena.push_line_for_type(line, &schema, "TRAFFIC");
+        assert_eq!(arena.row(row).nth(1), Some("fw-edge.example.com"));
+    }
+
+    #[test]
+    fn test_defaults_fill_missing_trailing_fields() {
+        let root = serde_json::from_str(
+            r#"{"log_types": {"t": {"type_value": "TRAFFIC", "fields": [
+                "f0", "f1", "f2", "type", {"name": "bytes", "default": "0"}, "action"
+            ]}}}"#,
+        )
+        .unwrap();
+        let schema = crate::schema::build_schema(root).unwrap();
+        let map = parse_line_to_map("1,2,3,TRAFFIC", &schema).unwrap();
+        assert_eq!(map["bytes"].as_deref(), Some("0"));
+        assert_eq!(map["action"], None);
+        // A value present in the line, even empty, is kept
+        let map = parse_line_to_map("1,2,3,TRAFFIC,,deny", &schema).unwrap();
+        assert_eq!(map["bytes"].as_deref(), Some(""));
+
+        let mut parser = LineParser::new(&schema);
+        let mut out = FieldMap::new();
+        parser.parse_into("1,2,3,TRAFFIC", &mut out).unwrap();
+        assert_eq!(out, parse_line_to_map("1,2,3,TRAFFIC", &schema).unwrap());
+    }
+
+    #[test]
+    fn test_line_parser_matches_parse_line_to_map() {
+        let root = serde_json::from_str(include_str!("../testdata/threat_subtypes.schema.json"))
+            .expect("fixture parses");
+        let mut schema = crate::schema::build_schema(root).unwrap();
+        let legacy = ["a", "b", "c", "type", "x", "y"].map(String::from);
+        schema.update_log_type("LEGACY", &legacy).unwrap();
+        schema.type_to_delimiter.insert("LEGACY".to_string(), b'|');
+        let lines = [
+            "1,2025/10/12 05:07:29,001,THREAT,url,10.0.0.1,10.0.0.2,example.com/x,news",
+            "1,2025/10/12 05:07:29,001,TRAFFIC,end,10.0.0.1,10.0.0.2,extra",
+            "1,2025/10/12 05:07:29,001,THREAT,vulnerability,10.0.0.1,\"10.0.0.2\",30001",
+            "1,2,3,LEGACY,p|q",
+            "1,2,3,LEGACY,",
+            "1,2,3,TRAFFIC",
+        ];
+        let mut parser = LineParser::new(&schema);
+        let mut map = FieldMap::new();
+        for line in lines {
+            parser.parse_into(line, &mut map).unwrap();
+            assert_eq!(map, parse_line_to_map(line, &schema).unwrap(), "{}", line);
+            let (fields, layout) = parser.split(line).unwrap();
+            let (want_fields, want_layout) = split_with_layout(line, &schema).unwrap();
+            assert_eq!((fields, layout), (&want_fields[..], want_layout));
+        }
+        let before = map.clone();
+        let err = parser.parse_into("1,2,3,CONFIG,x", &mut map).unwrap_err();
+        assert_eq!(err, "Unknown log type in schema: CONFIG");
+        assert_eq!(map, before);
+    }
 }
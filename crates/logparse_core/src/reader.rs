@@ -0,0 +1,163 @@
+// reader.rs: line reading that keeps track of each line's position in the source
+//
+// Lines are read with a manual `fill_buf` loop rather than `read_line`, so a
+// corrupt input with a multi-GB "line" and no newline can be skipped without
+// ever buffering more than `max_line_bytes` of it.
+use std::io::{self, BufRead};
+
+use memchr::memchr;
+
+/// One line of input plus where it came from.
+pub struct SourceLine {
+    /// 1-based line number, counting every line including blank ones
+    pub line_no: usize,
+    /// Offset of the line's first byte; each line advances it by its full
+    /// length including the `\n` or `\r\n` terminator
+    pub byte_offset: u64,
+    /// The line without its terminator (same as `BufRead::lines`)
+    pub text: String,
+}
+
+/// Like `BufRead::lines`, but each item also carries its line number and byte offset.
+pub struct PositionedLines<R> {
+    reader: R,
+    line_no: usize,
+    offset: u64,
+    max_line_bytes: usize,
+    skipped_long: usize,
+}
+
+impl<R: BufRead> PositionedLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_max_line_bytes(reader, usize::MAX)
+    }
+
+    /// Lines longer than `max_line_bytes` (not counting the terminator) are
+    /// skipped, and counted in `skipped_long_lines`, without being buffered.
+    /// They still advance the line number and byte offset of later lines.
+    pub fn with_max_line_bytes(reader: R, max_line_bytes: usize) -> Self {
+        Self { reader, line_no: 0, offset: 0, max_line_bytes, skipped_long: 0 }
+    }
+
+    /// Number of over-long lines skipped so far.
+    pub fn skipped_long_lines(&self) -> usize {
+        self.skipped_long
+    }
+
+    /// Read one line into `buf`, keeping at most `max_line_bytes + 1` bytes of
+    /// it (room for a `\r` before the `\n`). Returns the bytes consumed
+    /// including the terminator, and whether anything was dropped.
+    fn read_line_bounded(&mut self, buf: &mut Vec<u8>) -> io::Result<(usize, bool)> {
+        let keep = self.max_line_bytes.saturating_add(1);
+        let (mut read, mut overflow) = (0usize, false);
+        loop {
+            let chunk = match self.reader.fill_buf() {
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if chunk.is_empty() {
+                return Ok((read, overflow));
+            }
+            let (end, done) = match memchr(b'\n', chunk) {
+                Some(i) => (i, true),
+                None => (chunk.len(), false),
+            };
+            let room = keep - buf.len().min(keep);
+            overflow |= end > room;
+            buf.extend_from_slice(&chunk[..end.min(room)]);
+            let used = if done { end + 1 } else { end };
+            self.reader.consume(used);
+            read += used;
+            if done {
+                return Ok((read, overflow));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PositionedLines<R> {
+    type Item = io::Result<SourceLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut buf = Vec::new();
+            let (read, overflow) = match self.read_line_bounded(&mut buf) {
+                Ok((0, _)) => return None,
+                Ok(r) => r,
+                Err(e) => return Some(Err(e)),
+            };
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+            }
+            self.line_no += 1;
+            let byte_offset = self.offset;
+            self.offset += read as u64;
+            if overflow || buf.len() > self.max_line_bytes {
+                self.skipped_long += 1;
+                continue;
+            }
+            // Same error as `read_line` for input that is not UTF-8
+            let text = match String::from_utf8(buf) {
+                Ok(text) => text,
+                Err(_) => {
+                    let msg = "stream did not contain valid UTF-8";
+                    return Some(Err(io::Error::new(io::ErrorKind::InvalidData, msg)));
+                }
+            };
+            return Some(Ok(SourceLine { line_no: self.line_no, byte_offset, text }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PositionedLines;
+
+    #[test]
+    fn test_positioned_lines() {
+        let data = "a,b\r\n\nccc\nlast";
+        let got: Vec<(usize, u64, String)> = PositionedLines::new(data.as_bytes())
+            .map(|l| l.map(|l| (l.line_no, l.byte_offset, l.text)).unwrap())
+            .collect();
+        assert_eq!(
+            got,
+            vec![
+                (1, 0, "a,b".to_string()),
+                (2, 5, String::new()),
+                (3, 6, "ccc".to_string()),
+                (4, 10, "last".to_string()),
+            ]
+        );
+        assert_eq!(&data[10..], "last");
+    }
+
+    #[test]
+    fn test_over_long_lines_are_skipped() {
+        let long = "x".repeat(100_000);
+        let data = format!("a,b\r\n{}\nccc\n{}\r\nlast", long, "y".repeat(11));
+        // A small read buffer, so the long line spans many fill_buf calls
+        let reader = std::io::BufReader::with_capacity(64, data.as_bytes());
+        let mut lines = PositionedLines::with_max_line_bytes(reader, 10);
+        let got: Vec<(usize, u64, String)> = lines
+            .by_ref()
+            .map(|l| l.map(|l| (l.line_no, l.byte_offset, l.text)).unwrap())
+            .collect();
+        let ccc = 5 + long.len() as u64 + 1;
+        assert_eq!(
+            got,
+            vec![
+                (1, 0, "a,b".to_string()),
+                (3, ccc, "ccc".to_string()),
+                (5, ccc + 4 + 13, "last".to_string()),
+            ]
+        );
+        assert_eq!(lines.skipped_long_lines(), 2);
+
+        // Exactly at the limit, with or without `\r`, is kept
+        let exact = "0123456789\r\n0123456789";
+        let n = PositionedLines::with_max_line_bytes(exact.as_bytes(), 10).count();
+        assert_eq!(n, 2);
+        assert!(PositionedLines::new(&b"ok\n\xff\n"[..]).nth(1).unwrap().is_err());
+    }
+}
@@ -1,44 +1,168 @@
 // schema.rs: schema types and cache/loader
 use once_cell::sync::Lazy;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
-use std::sync::RwLock;
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
 
+use crate::jsonc::{is_jsonc_path, strip_jsonc};
+use crate::tokenizer::{extract_field_internal, extract_field_ref};
+
+/// Either `{"palo_alto_syslog_fields": {"log_types": ...}}` or the vendor-neutral
+/// `{"log_types": ...}`; the Palo Alto key wins when both are present.
 #[derive(Deserialize)]
 pub struct SchemaRoot {
     #[serde(rename = "palo_alto_syslog_fields", default)]
-    pub palo_alto_syslog_fields: PaloAltoSyslogFields,
+    pub palo_alto_syslog_fields: Option<PaloAltoSyslogFields>,
+    #[serde(flatten)]
+    pub generic: PaloAltoSyslogFields,
 }
 
 #[derive(Deserialize, Default)]
 pub struct PaloAltoSyslogFields {
+    /// 0-based position of the log type discriminator; defaults to 3 (PAN-OS)
+    #[serde(default)]
+    pub type_index: Option<usize>,
     #[serde(default)]
     pub log_types: HashMap<String, LogTypeDef>,
+    /// Output name for a field, keyed by its sanitized name; applies to every
+    /// log type and subtype layout
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Reject lines with more fields than their log type names
+    #[serde(default)]
+    pub strict_fields: bool,
+    /// Match log types ignoring case, e.g. `Traffic` lines as `TRAFFIC`
+    #[serde(default)]
+    pub case_insensitive_types: bool,
 }
 
 #[derive(Deserialize)]
 pub struct LogTypeDef {
-    pub type_value: String,
+    /// Entries without one are skipped with a warning rather than failing the load
+    #[serde(default)]
+    pub type_value: Option<String>,
     #[allow(dead_code)]
     pub description: Option<String>,
-    #[allow(dead_code)]
+    /// Expected number of fields; a mismatch is reported in `LoadedSchema::warnings`
     pub field_count: Option<usize>,
     pub fields: Vec<FieldDef>,
+    /// Single-character delimiter for the fields after the type discriminator
+    /// (which is always located using the default comma).
+    #[serde(default)]
+    pub delimiter: Option<String>,
+    #[serde(default)]
+    pub subtypes: Option<Subtypes>,
+}
+
+/// `subtypes` of a log type: a plain list of names (documentation only), or a
+/// map from the subtype value (the field right after the type) to that
+/// subtype's own field layout.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Subtypes {
+    Names(#[allow(dead_code)] Vec<String>),
+    Layouts(HashMap<String, SubtypeDef>),
+}
+
+#[derive(Deserialize)]
+pub struct SubtypeDef {
+    pub fields: Vec<FieldDef>,
 }
 
 #[derive(Deserialize)]
 #[serde(untagged)]
 pub enum FieldDef {
     Str(String),
-    Obj { name: String },
+    Obj {
+        name: String,
+        /// Absolute 0-based position; unpinned fields fill the remaining slots in order
+        #[serde(default)]
+        index: Option<usize>,
+        /// Coerce values to this type in typed parsing (default: string)
+        #[serde(rename = "type", default)]
+        ty: Option<FieldType>,
+        /// strftime-style pattern for `datetime` fields (default `DEFAULT_DATETIME_FORMAT`)
+        #[serde(default)]
+        format: Option<String>,
+        /// Rewrite values while parsing (default: kept verbatim)
+        #[serde(default)]
+        normalize: Option<Normalize>,
+        /// Value reported when the line is too short to reach the field
+        /// (default: `None`)
+        #[serde(default)]
+        default: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+    /// Kept as the raw string; typed parsing adds `<name>_epoch_ms`
+    Datetime,
+}
+
+/// Value rewrite applied to a field while parsing.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalize {
+    Lowercase,
+    /// Strip leading and trailing whitespace
+    Trim,
+    LowercaseTrim,
+}
+
+impl Normalize {
+    /// Rewrite `value` in place; ASCII values are not reallocated.
+    pub fn apply(self, value: &mut String) {
+        if matches!(self, Normalize::Trim | Normalize::LowercaseTrim) {
+            value.truncate(value.trim_end().len());
+            let lead = value.len() - value.trim_start().len();
+            value.drain(..lead);
+        }
+        if matches!(self, Normalize::Lowercase | Normalize::LowercaseTrim) {
+            if value.is_ascii() {
+                value.make_ascii_lowercase();
+            } else {
+                *value = value.to_lowercase();
+            }
+        }
+    }
+}
+
+/// PAN-OS timestamp layout, e.g. `2025/10/12 05:07:29`
+pub const DEFAULT_DATETIME_FORMAT: &str = "%Y/%m/%d %H:%M:%S";
+
+/// One resolved field position: sanitized name, value type, datetime format,
+/// value normalization and default.
+#[derive(Clone, Debug)]
+struct FieldSlot {
+    name: String,
+    ty: FieldType,
+    format: Option<String>,
+    normalize: Option<Normalize>,
+    default: Option<String>,
+}
+
+impl FieldSlot {
+    fn plain(name: String) -> Self {
+        Self { name, ty: FieldType::String, format: None, normalize: None, default: None }
+    }
 }
 
 pub(crate) fn sanitize_identifier(name: &str) -> String {
     let mut s = name.trim().to_lowercase();
-    s = s.replace(' ', "_").replace('/', "_").replace('-', "_");
+    s = s.replace([' ', '/', '-'], "_");
     let mut out = String::with_capacity(s.len());
     for ch in s.chars() {
         if ch.is_ascii_alphanumeric() || ch == '_' {
@@ -55,48 +179,501 @@ pub(crate) fn sanitize_identifier(name: &str) -> String {
     out
 }
 
+/// Position of the log type in PAN-OS syslog lines, used unless the schema sets `type_index`.
+pub const DEFAULT_TYPE_INDEX: usize = 3;
+
+#[derive(Clone)]
 pub struct LoadedSchema {
+    /// Source file; empty for schemas loaded from a string
     pub path: String,
     pub mtime: Option<SystemTime>,
     pub type_to_fields: HashMap<String, Vec<String>>, // key: type_value
+    pub type_to_delimiter: HashMap<String, u8>,       // only types overriding the comma
+    /// Per-field value types, parallel to `type_to_fields`; only for log types
+    /// that declare at least one non-string field
+    pub type_to_field_types: HashMap<String, Vec<FieldType>>,
+    /// Per-field datetime formats, parallel to `type_to_fields`; only for log
+    /// types with an explicit `format` on some field
+    pub type_to_field_formats: HashMap<String, Vec<Option<String>>>,
+    /// Per-field value normalization, parallel to `type_to_fields`; only for
+    /// log types with a `normalize` annotation on some field
+    pub type_to_field_normalize: HashMap<String, Vec<Option<Normalize>>>,
+    /// Per-field defaults for missing trailing fields, parallel to
+    /// `type_to_fields`; only for log types with a `default` on some field
+    pub type_to_field_defaults: HashMap<String, Vec<Option<String>>>,
+    /// type_value -> subtype value -> field names, for log types whose layout
+    /// depends on the field after the type discriminator
+    pub type_subtype_to_fields: HashMap<String, HashMap<String, Vec<String>>>,
+    /// Field index holding the log type discriminator
+    pub type_index: usize,
+    /// Lines with more fields than their layout names fail to parse instead
+    /// of having the overflow dropped
+    pub strict_fields: bool,
+    /// Look up log types ignoring case when there is no exact match
+    pub case_insensitive_types: bool,
+    /// Lowercased type_value -> type_value; only with `case_insensitive_types`
+    pub type_lowercase: HashMap<String, String>,
+    /// Sanitized field name -> output name, from the schema's `aliases`; also
+    /// applied to field lists added by `update_log_type`
+    pub aliases: HashMap<String, String>,
+    /// Problems fixed up while loading, e.g. renamed duplicate field names
+    pub warnings: Vec<String>,
+}
+
+impl Default for LoadedSchema {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            mtime: None,
+            type_to_fields: HashMap::new(),
+            type_to_delimiter: HashMap::new(),
+            type_to_field_types: HashMap::new(),
+            type_to_field_formats: HashMap::new(),
+            type_to_field_normalize: HashMap::new(),
+            type_to_field_defaults: HashMap::new(),
+            type_subtype_to_fields: HashMap::new(),
+            type_index: DEFAULT_TYPE_INDEX,
+            strict_fields: false,
+            case_insensitive_types: false,
+            type_lowercase: HashMap::new(),
+            aliases: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
 }
 
-pub static SCHEMA_CACHE: Lazy<RwLock<Option<LoadedSchema>>> = Lazy::new(|| RwLock::new(None));
+impl LoadedSchema {
+    /// Build a schema from JSON text; see `load_schema_from_str`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        load_schema_from_str(json)
+    }
+
+    /// Load a schema file; see `load_schema_internal`. Neither constructor
+    /// touches `SCHEMA_CACHE`: every parse function takes the schema by
+    /// reference, so callers can own one per thread or instance.
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        load_schema_internal(path)
+    }
+
+    /// Extract the log type of `line` from the schema's `type_index`. With
+    /// `case_insensitive_types` this is the schema key it matches, e.g.
+    /// `TRAFFIC` for a line whose type field is `Traffic`.
+    pub fn log_type(&self, line: &str) -> Result<String, String> {
+        let t = extract_field_internal(line, self.type_index)
+            .ok_or_else(|| format!("Could not extract log type at index {}", self.type_index))?;
+        Ok(self.folded_type(&t).cloned().unwrap_or(t))
+    }
 
-fn build_type_to_fields(root: SchemaRoot) -> HashMap<String, Vec<String>> {
-    let mut map: HashMap<String, Vec<String>> = HashMap::new();
-    for (_name, def) in root.palo_alto_syslog_fields.log_types.into_iter() {
-        let mut fields: Vec<String> = Vec::new();
-        for f in def.fields.into_iter() {
-            let raw = match f {
-                FieldDef::Str(s) => s,
-                FieldDef::Obj { name } => name,
-            };
-            let key = sanitize_identifier(&raw);
-            fields.push(key);
+    /// `log_type` borrowing the type from `line` when it is unquoted, for
+    /// lookups that do not need to keep it.
+    pub fn log_type_ref<'l>(&self, line: &'l str) -> Result<Cow<'l, str>, String> {
+        let t = extract_field_ref(line, self.type_index)
+            .ok_or_else(|| format!("Could not extract log type at index {}", self.type_index))?;
+        Ok(match self.folded_type(&t) {
+            Some(key) => Cow::Owned(key.clone()),
+            None => t,
+        })
+    }
+
+    /// Whether `line` has a log type at `type_index` that the schema does not
+    /// know; false for lines too short to have one.
+    pub fn has_unknown_type(&self, line: &str) -> bool {
+        self.log_type_ref(line).is_ok_and(|t| !self.type_to_fields.contains_key(t.as_ref()))
+    }
+
+    /// With `case_insensitive_types`, the log type that `t` matches ignoring
+    /// case when `t` is not a key itself. Exact keys are checked first, so
+    /// lines using the schema's own spelling never lowercase anything.
+    pub fn folded_type(&self, t: &str) -> Option<&String> {
+        if !self.case_insensitive_types || self.type_to_fields.contains_key(t) {
+            return None;
         }
-        map.insert(def.type_value, fields);
+        self.type_lowercase.get(&t.to_lowercase())
+    }
+
+    /// The key to look log type `t` up by: `t`, or the log type it matches
+    /// ignoring case (see `folded_type`).
+    pub fn resolve_type<'a>(&'a self, t: &'a str) -> &'a str {
+        self.folded_type(t).map_or(t, |k| k.as_str())
+    }
+
+    /// Field names produced for log type `type_value`, in parse order.
+    pub fn fields_for_type(&self, type_value: &str) -> Option<&[String]> {
+        self.type_to_fields.get(type_value).map(|f| f.as_slice())
+    }
+
+    /// Subtype-specific field names of log type `t` for `subtype`, if the
+    /// schema declares a layout for it.
+    pub fn subtype_fields(&self, t: &str, subtype: Option<&str>) -> Option<&Vec<String>> {
+        self.type_subtype_to_fields.get(t)?.get(subtype?)
+    }
+
+    /// Field names for a line of type `t` split into `values`: the layout of
+    /// its subtype (the value after the type discriminator) when the schema
+    /// declares one, else the type-level list.
+    pub fn fields_for_record(&self, t: &str, values: &[String]) -> Option<&Vec<String>> {
+        let subtype = values.get(self.type_index + 1).map(|s| s.as_str());
+        self.subtype_fields(t, subtype).or_else(|| self.type_to_fields.get(t))
+    }
+
+    /// Apply the `normalize` annotations of log type `t` to the split `values`
+    /// of one record. Like declared types, they follow the type-level field
+    /// list, so records laid out by a subtype are left as they are.
+    pub fn normalize_record(&self, t: &str, values: &mut [String]) {
+        let Some(rules) = self.type_to_field_normalize.get(t) else { return };
+        let subtype = values.get(self.type_index + 1).map(|s| s.as_str());
+        if self.subtype_fields(t, subtype).is_some() {
+            return;
+        }
+        for (value, rule) in values.iter_mut().zip(rules) {
+            if let Some(rule) = rule {
+                rule.apply(value);
+            }
+        }
+    }
+
+    /// Defaults of log type `t` for a record split into `values`, parallel to
+    /// its field names: what to report instead of `None` for the fields the
+    /// line is too short to reach. Like declared types they follow the
+    /// type-level field list, so subtype layouts have none.
+    pub fn field_defaults(&self, t: &str, values: &[String]) -> Option<&[Option<String>]> {
+        let subtype = values.get(self.type_index + 1).map(|s| s.as_str());
+        self.subtype_defaults(t, subtype)
+    }
+
+    /// `field_defaults` given the record's `subtype` value instead of all its
+    /// fields, for callers that do not hold them as Strings.
+    pub fn subtype_defaults(&self, t: &str, subtype: Option<&str>) -> Option<&[Option<String>]> {
+        let defaults = self.type_to_field_defaults.get(t)?;
+        match self.subtype_fields(t, subtype) {
+            Some(_) => None,
+            None => Some(defaults),
+        }
+    }
+
+    /// With `strict_fields`, the error for a record of log type `t` split into
+    /// `values` fields when its layout names fewer.
+    pub fn check_overflow(&self, t: &str, values: usize, names: usize) -> Result<(), String> {
+        if self.strict_fields && values > names {
+            return Err(format!(
+                "Log type {}: {} fields beyond the {} named in the schema (strict_fields)",
+                t,
+                values - names,
+                names
+            ));
+        }
+        Ok(())
+    }
+
+    /// All known `type_value` keys, sorted.
+    pub fn type_values(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = self.type_to_fields.keys().map(|t| t.as_str()).collect();
+        types.sort_unstable();
+        types
+    }
+
+    /// Replace (or add) the field list of one log type in place. Names are
+    /// sanitized, deduplicated and aliased exactly as when loading from JSON;
+    /// all fields become strings and any subtype layouts of the type are
+    /// dropped, so `fields` is used for every line. Returns true if the type
+    /// already existed. On an alias collision, or with `case_insensitive_types`
+    /// a type differing only in case from an existing one, the schema is left
+    /// unchanged.
+    pub fn update_log_type(&mut self, type_value: &str, fields: &[String]) -> Result<bool, String> {
+        let mut fields: Vec<String> = fields.iter().map(|f| sanitize_identifier(f)).collect();
+        let mut warnings = Vec::new();
+        dedupe_field_names(type_value, &mut fields, &mut warnings);
+        apply_aliases(type_value, &mut fields, &self.aliases)?;
+        if self.case_insensitive_types {
+            let folded = self.type_lowercase.get(&type_value.to_lowercase());
+            if let Some(other) = folded.filter(|t| *t != type_value) {
+                return Err(case_clash(other, type_value));
+            }
+        }
+        self.warnings.extend(warnings);
+        self.type_subtype_to_fields.remove(type_value);
+        self.type_to_field_types.remove(type_value);
+        self.type_to_field_formats.remove(type_value);
+        self.type_to_field_normalize.remove(type_value);
+        self.type_to_field_defaults.remove(type_value);
+        if self.case_insensitive_types {
+            self.type_lowercase.insert(type_value.to_lowercase(), type_value.to_string());
+        }
+        Ok(self.type_to_fields.insert(type_value.to_string(), fields).is_some())
+    }
+
+    /// Drop one log type (and its delimiter override). Returns true if it existed.
+    pub fn remove_log_type(&mut self, type_value: &str) -> bool {
+        self.type_to_delimiter.remove(type_value);
+        self.type_subtype_to_fields.remove(type_value);
+        self.type_to_field_types.remove(type_value);
+        self.type_to_field_formats.remove(type_value);
+        self.type_to_field_normalize.remove(type_value);
+        self.type_to_field_defaults.remove(type_value);
+        let folded = type_value.to_lowercase();
+        if self.type_lowercase.get(&folded).is_some_and(|t| t == type_value) {
+            self.type_lowercase.remove(&folded);
+        }
+        self.type_to_fields.remove(type_value).is_some()
     }
-    map
+}
+
+/// Name under which the single-schema API (`ensure_schema_loaded`, the
+/// watcher, the `cached_*` helpers) keeps its schema in `SCHEMA_CACHE`.
+pub const DEFAULT_SCHEMA_NAME: &str = "default";
+
+/// Loaded schemas by caller-chosen name, so services that parse several log
+/// formats keep all of them loaded instead of reloading on every switch.
+pub static SCHEMA_CACHE: Lazy<RwLock<HashMap<String, LoadedSchema>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn case_clash(a: &str, b: &str) -> String {
+    format!("Log types {} and {} differ only in case (case_insensitive_types)", a, b)
+}
+
+fn parse_delimiter(type_value: &str, d: &str) -> Result<u8, String> {
+    match d.as_bytes() {
+        [b] if b.is_ascii() && *b != b'"' => Ok(*b),
+        _ => Err(format!(
+            "Invalid delimiter for log type {}: expected one ASCII character other than '\"', got {:?}",
+            type_value, d
+        )),
+    }
+}
+
+/// Rename repeated names within one log type (`src_ip`, `src_ip_2`, ...), e.g.
+/// "Src IP" and "src-ip" which sanitize alike and would otherwise overwrite
+/// each other in parsed output. Each rename is recorded in `warnings`.
+fn dedupe_field_names(type_value: &str, names: &mut [String], warnings: &mut Vec<String>) {
+    let mut seen: HashSet<String> = HashSet::with_capacity(names.len());
+    for i in 0..names.len() {
+        if seen.insert(names[i].clone()) {
+            continue;
+        }
+        // Skip suffixes taken by any field, including ones further along
+        let renamed = (2..)
+            .map(|n| format!("{}_{}", names[i], n))
+            .find(|c| !seen.contains(c) && !names.contains(c))
+            .expect("unbounded suffix search");
+        warnings.push(format!(
+            "Log type {}: duplicate field name {} at index {} renamed to {}",
+            type_value, names[i], i, renamed
+        ));
+        seen.insert(renamed.clone());
+        names[i] = renamed;
+    }
+}
+
+/// Rename the fields of one layout that have an alias. An alias may not
+/// take the name of another field in the layout, or of another alias.
+fn apply_aliases(
+    label: &str,
+    names: &mut [String],
+    aliases: &HashMap<String, String>,
+) -> Result<(), String> {
+    if aliases.is_empty() {
+        return Ok(());
+    }
+    let original = names.to_vec();
+    for name in names.iter_mut() {
+        if let Some(alias) = aliases.get(name.as_str()) {
+            *name = alias.clone();
+        }
+    }
+    for (i, name) in names.iter().enumerate() {
+        if *name == original[i] {
+            continue;
+        }
+        if let Some(j) = (0..names.len()).find(|&j| j != i && names[j] == *name) {
+            return Err(format!(
+                "Log type {}: alias {} for field {} collides with field {}",
+                label, name, original[i], original[j]
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Resolve field order for one log type. Fields with an explicit `index` are
+/// pinned to that position; the rest flow, in order, into the free slots. Slots
+/// left empty (a pin beyond the field count) are named `_field_<index>`.
+/// Largest position a field may pin itself to with `"index"`; anything past it is
+/// a typo rather than a real layout, and would allocate the gap up to it.
+const MAX_PINNED_INDEX: usize = 65_535;
+
+fn layout_fields(type_value: &str, defs: Vec<FieldDef>) -> Result<Vec<FieldSlot>, String> {
+    let total = defs.len();
+    let mut pinned: Vec<(usize, FieldSlot)> = Vec::new();
+    let mut unpinned: Vec<FieldSlot> = Vec::with_capacity(total);
+    for f in defs.into_iter() {
+        match f {
+            FieldDef::Str(s) => unpinned.push(FieldSlot::plain(sanitize_identifier(&s))),
+            FieldDef::Obj { name, index, ty, format, normalize, default } => {
+                let slot = FieldSlot {
+                    name: sanitize_identifier(&name),
+                    ty: ty.unwrap_or_default(),
+                    format,
+                    normalize,
+                    default,
+                };
+                match index {
+                    Some(i) if i > MAX_PINNED_INDEX => {
+                        return Err(format!(
+                            "Log type {}: index {} of field {} out of range (max {})",
+                            type_value, i, slot.name, MAX_PINNED_INDEX
+                        ));
+                    }
+                    Some(i) => pinned.push((i, slot)),
+                    None => unpinned.push(slot),
+                }
+            }
+        }
+    }
+    if pinned.is_empty() {
+        return Ok(unpinned);
+    }
+    let len = pinned.iter().filter_map(|(i, _)| i.checked_add(1)).max().unwrap_or(0).max(total);
+    let mut slots: Vec<Option<FieldSlot>> = vec![None; len];
+    for (i, field) in pinned {
+        if let Some(existing) = &slots[i] {
+            return Err(format!(
+                "Log type {}: fields {} and {} are both pinned to index {}",
+                type_value, existing.name, field.name, i
+            ));
+        }
+        slots[i] = Some(field);
+    }
+    let mut rest = unpinned.into_iter();
+    Ok(slots
+        .into_iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            slot.or_else(|| rest.next())
+                .unwrap_or_else(|| FieldSlot::plain(format!("_field_{}", i)))
+        })
+        .collect())
+}
+
+pub(crate) fn build_schema(root: SchemaRoot) -> Result<LoadedSchema, String> {
+    let mut schema = LoadedSchema::default();
+    let body = root.palo_alto_syslog_fields.unwrap_or(root.generic);
+    if let Some(i) = body.type_index {
+        schema.type_index = i;
+    }
+    schema.strict_fields = body.strict_fields;
+    let mut aliases = HashMap::with_capacity(body.aliases.len());
+    for (field, alias) in body.aliases {
+        if alias.is_empty() {
+            return Err(format!("Alias for field {} is empty", field));
+        }
+        aliases.insert(sanitize_identifier(&field), alias);
+    }
+    for (name, def) in body.log_types.into_iter() {
+        let Some(type_value) = def.type_value else {
+            schema.warnings.push(format!("Log type {}: no type_value; entry skipped", name));
+            continue;
+        };
+        if let Some(d) = def.delimiter.as_deref() {
+            let b = parse_delimiter(&type_value, d)?;
+            if b != b',' {
+                schema.type_to_delimiter.insert(type_value.clone(), b);
+            }
+        }
+        let slots = layout_fields(&type_value, def.fields)?;
+        if let Some(n) = def.field_count.filter(|&n| n != slots.len()) {
+            schema.warnings.push(format!(
+                "Log type {}: field_count is {} but {} fields are defined",
+                type_value,
+                n,
+                slots.len()
+            ));
+        }
+        if slots.iter().any(|f| f.ty != FieldType::String) {
+            let types = slots.iter().map(|f| f.ty).collect();
+            schema.type_to_field_types.insert(type_value.clone(), types);
+        }
+        if slots.iter().any(|f| f.format.is_some()) {
+            let formats = slots.iter().map(|f| f.format.clone()).collect();
+            schema.type_to_field_formats.insert(type_value.clone(), formats);
+        }
+        if slots.iter().any(|f| f.normalize.is_some()) {
+            let rules = slots.iter().map(|f| f.normalize).collect();
+            schema.type_to_field_normalize.insert(type_value.clone(), rules);
+        }
+        if slots.iter().any(|f| f.default.is_some()) {
+            let defaults = slots.iter().map(|f| f.default.clone()).collect();
+            schema.type_to_field_defaults.insert(type_value.clone(), defaults);
+        }
+        let mut fields: Vec<String> = slots.into_iter().map(|f| f.name).collect();
+        dedupe_field_names(&type_value, &mut fields, &mut schema.warnings);
+        apply_aliases(&type_value, &mut fields, &aliases)?;
+        if let Some(Subtypes::Layouts(layouts)) = def.subtypes {
+            let mut by_subtype = HashMap::with_capacity(layouts.len());
+            for (subtype, sub) in layouts {
+                let label = format!("{}/{}", type_value, subtype);
+                let mut names: Vec<String> =
+                    layout_fields(&label, sub.fields)?.into_iter().map(|f| f.name).collect();
+                dedupe_field_names(&label, &mut names, &mut schema.warnings);
+                apply_aliases(&label, &mut names, &aliases)?;
+                by_subtype.insert(subtype, names);
+            }
+            schema.type_subtype_to_fields.insert(type_value.clone(), by_subtype);
+        }
+        schema.type_to_fields.insert(type_value, fields);
+    }
+    schema.aliases = aliases;
+    schema.case_insensitive_types = body.case_insensitive_types;
+    if schema.case_insensitive_types {
+        let types: Vec<String> = schema.type_values().into_iter().map(String::from).collect();
+        for t in types {
+            if let Some(other) = schema.type_lowercase.insert(t.to_lowercase(), t.clone()) {
+                return Err(case_clash(&other, &t));
+            }
+        }
+    }
+    // Log types come from a HashMap; keep the warning order stable
+    schema.warnings.sort();
+    Ok(schema)
 }
 
 fn read_mtime(path: &Path) -> Option<SystemTime> {
     fs::metadata(path).ok().and_then(|m| m.modified().ok())
 }
 
+/// Load a schema file. `.jsonc` and `.json5` files may contain comments and
+/// trailing commas; see `load_schema_jsonc` to opt in for other extensions.
 pub fn load_schema_internal(schema_path: &str) -> Result<LoadedSchema, String> {
-    let data = fs::read_to_string(schema_path)
-        .map_err(|e| format!("Failed to read schema {}: {}", schema_path, e))?;
+    load_schema_with(schema_path, is_jsonc_path(schema_path))
+}
+
+/// Load a schema file allowing comments and trailing commas, whatever its extension.
+pub fn load_schema_jsonc(schema_path: &str) -> Result<LoadedSchema, String> {
+    load_schema_with(schema_path, true)
+}
+
+/// Build a schema from JSON text held in memory. The result has an empty
+/// `path` and no `mtime`, so `ensure_schema_loaded` never reloads it.
+pub fn load_schema_from_str(json: &str) -> Result<LoadedSchema, String> {
     let root: SchemaRoot =
-        serde_json::from_str(&data).map_err(|e| format!("Failed to parse schema JSON: {}", e))?;
-    let type_to_fields = build_type_to_fields(root);
-    let mtime = read_mtime(Path::new(schema_path));
-    Ok(LoadedSchema { path: schema_path.to_string(), mtime, type_to_fields })
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse schema JSON: {}", e))?;
+    build_schema(root)
+}
+
+fn load_schema_with(schema_path: &str, jsonc: bool) -> Result<LoadedSchema, String> {
+    let mut data = fs::read_to_string(schema_path)
+        .map_err(|e| format!("Failed to read schema {}: {}", schema_path, e))?;
+    if jsonc {
+        data = strip_jsonc(&data);
+    }
+    let mut schema = load_schema_from_str(&data)?;
+    schema.path = schema_path.to_string();
+    schema.mtime = read_mtime(Path::new(schema_path));
+    Ok(schema)
 }
 
 pub fn ensure_schema_loaded(schema_path: &str) -> Result<(), String> {
     let mut guard = SCHEMA_CACHE.write().unwrap();
-    let need_reload = match guard.as_ref() {
+    let need_reload = match guard.get(DEFAULT_SCHEMA_NAME) {
         None => true,
         Some(ls) => {
             if ls.path != schema_path {
@@ -109,14 +686,107 @@ pub fn ensure_schema_loaded(schema_path: &str) -> Result<(), String> {
     };
     if need_reload {
         let loaded = load_schema_internal(schema_path)?;
-        *guard = Some(loaded);
+        guard.insert(DEFAULT_SCHEMA_NAME.to_string(), loaded);
     }
     Ok(())
 }
 
+/// Patch one log type of the cached schema under the write lock. Readers either
+/// see the schema before or after the patch, never a partial entry. Note that a
+/// later `ensure_schema_loaded` reload from disk discards in-memory patches.
+pub fn update_cached_log_type(type_value: &str, fields: &[String]) -> Result<bool, String> {
+    let mut guard = SCHEMA_CACHE.write().unwrap();
+    let schema = guard.get_mut(DEFAULT_SCHEMA_NAME).ok_or("No schema loaded")?;
+    schema.update_log_type(type_value, fields)
+}
+
+/// Remove one log type from the cached schema under the write lock.
+pub fn remove_cached_log_type(type_value: &str) -> Result<bool, String> {
+    let mut guard = SCHEMA_CACHE.write().unwrap();
+    let schema = guard.get_mut(DEFAULT_SCHEMA_NAME).ok_or("No schema loaded")?;
+    Ok(schema.remove_log_type(type_value))
+}
+
+struct Watcher {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+static WATCHER: Lazy<Mutex<Option<Watcher>>> = Lazy::new(|| Mutex::new(None));
+
+/// Load `path` into `SCHEMA_CACHE` now, then poll its mtime every `interval` on
+/// a background thread and swap in the new schema when it changes. Parse paths
+/// can then read the cache directly instead of calling `ensure_schema_loaded`
+/// (one stat per call). A reload that fails (e.g. a half-written file) keeps
+/// the current schema and is retried on the next tick. Replaces any running
+/// watcher.
+pub fn start_watcher(path: &str, interval: Duration) -> Result<(), String> {
+    stop_watcher();
+    let loaded = load_schema_internal(path)?;
+    let mut seen = loaded.mtime;
+    SCHEMA_CACHE.write().unwrap().insert(DEFAULT_SCHEMA_NAME.to_string(), loaded);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_flag = Arc::clone(&stop);
+    let path = path.to_string();
+    let handle = std::thread::spawn(move || loop {
+        std::thread::park_timeout(interval);
+        if stop_flag.load(Ordering::Acquire) {
+            break;
+        }
+        let current = read_mtime(Path::new(&path));
+        if current == seen {
+            continue;
+        }
+        if let Ok(schema) = load_schema_internal(&path) {
+            seen = schema.mtime;
+            // Built outside the lock; readers see the old or new schema whole
+            SCHEMA_CACHE.write().unwrap().insert(DEFAULT_SCHEMA_NAME.to_string(), schema);
+        }
+    });
+    *WATCHER.lock().unwrap() = Some(Watcher { stop, handle });
+    Ok(())
+}
+
+/// Stop the background watcher, waiting for its thread to exit. The cached
+/// schema is kept. Returns false if no watcher was running.
+pub fn stop_watcher() -> bool {
+    let Some(w) = WATCHER.lock().unwrap().take() else {
+        return false;
+    };
+    w.stop.store(true, Ordering::Release);
+    w.handle.thread().unpark();
+    let _ = w.handle.join();
+    true
+}
+
+/// Field names of one log type in the cached schema; `None` if the type is
+/// unknown or no schema is loaded.
+pub fn cached_schema_fields(type_value: &str) -> Option<Vec<String>> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    guard.get(DEFAULT_SCHEMA_NAME)?.fields_for_type(type_value).map(|f| f.to_vec())
+}
+
+/// Sorted `type_value` keys of the cached schema; empty if none is loaded.
+pub fn cached_schema_types() -> Vec<String> {
+    let guard = SCHEMA_CACHE.read().unwrap();
+    guard
+        .get(DEFAULT_SCHEMA_NAME)
+        .map_or_else(Vec::new, |s| s.type_values().into_iter().map(str::to_string).collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::sanitize_identifier;
+    use super::{
+        build_schema, cached_schema_fields, cached_schema_types, layout_fields,
+        load_schema_from_str, load_schema_internal, sanitize_identifier, start_watcher,
+        stop_watcher, FieldDef, SchemaRoot, DEFAULT_SCHEMA_NAME, SCHEMA_CACHE,
+    };
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant, SystemTime};
+
+    /// Serializes the tests that swap the global `SCHEMA_CACHE`.
+    static CACHE_LOCK: Mutex<()> = Mutex::new(());
 
     #[test]
     fn test_sanitize_identifier() {
@@ -125,4 +795,293 @@ mod tests {
         assert_eq!(sanitize_identifier("9bad"), "_9bad");
         assert_eq!(sanitize_identifier(""), "_");
     }
+
+    #[test]
+    fn test_per_type_delimiter() {
+        let root: SchemaRoot = serde_json::from_str(
+            r#"{"palo_alto_syslog_fields": {"log_types": {
+                "t": {"type_value": "TRAFFIC", "fields": ["a"]},
+                "l": {"type_value": "LEGACY", "fields": ["a"], "delimiter": "|"}
+            }}}"#,
+        )
+        .unwrap();
+        let schema = build_schema(root).unwrap();
+        assert_eq!(schema.type_to_delimiter.get("LEGACY"), Some(&b'|'));
+        assert!(!schema.type_to_delimiter.contains_key("TRAFFIC"));
+
+        let bad: SchemaRoot = serde_json::from_str(
+            r#"{"palo_alto_syslog_fields": {"log_types": {
+                "l": {"type_value": "LEGACY", "fields": ["a"], "delimiter": "||"}
+            }}}"#,
+        )
+        .unwrap();
+        assert!(build_schema(bad).is_err());
+    }
+
+    #[test]
+    fn test_layout_fields_with_pinned_indexes() {
+        let defs: Vec<FieldDef> = serde_json::from_str(
+            r#"[{"name": "a"}, "b", {"name": "Type", "index": 3}, {"name": "c"}, {"name": "z", "index": 6}]"#,
+        )
+        .unwrap();
+        let names: Vec<String> =
+            layout_fields("T", defs).unwrap().into_iter().map(|f| f.name).collect();
+        assert_eq!(names, vec!["a", "b", "c", "type", "_field_4", "_field_5", "z"]);
+
+        let conflict: Vec<FieldDef> =
+            serde_json::from_str(r#"[{"name": "a", "index": 1}, {"name": "b", "index": 1}]"#)
+                .unwrap();
+        let err = layout_fields("T", conflict).unwrap_err();
+        assert!(err.contains("index 1"), "{}", err);
+
+        // Overflowing and merely huge pins are rejected rather than allocated
+        for i in [u64::MAX, 1_000_000_000, 65_536] {
+            let defs = format!(r#"[{{"name": "a", "index": {}}}]"#, i);
+            let err = layout_fields("T", serde_json::from_str(&defs).unwrap()).unwrap_err();
+            assert!(err.contains(&format!("index {} of field a out of range", i)), "{}", err);
+        }
+        let defs = r#"["a", {"name": "last", "index": 65535}]"#;
+        assert_eq!(layout_fields("T", serde_json::from_str(defs).unwrap()).unwrap().len(), 65_536);
+    }
+
+    #[test]
+    fn test_update_and_remove_log_type() {
+        let root: SchemaRoot = serde_json::from_str(
+            r#"{"palo_alto_syslog_fields": {"log_types": {
+                "t": {"type_value": "TRAFFIC", "fields": ["a", "b", "c", "type", "sub"],
+                      "subtypes": {"url": {"fields": ["a", "b", "old", "type", "sub"]}}},
+                "l": {"type_value": "LEGACY", "fields": ["a"], "delimiter": "|"}
+            }}}"#,
+        )
+        .unwrap();
+        let mut schema = build_schema(root).unwrap();
+        let fields = ["Src IP", "b", "new", "type", "sub"].map(String::from);
+        assert!(schema.update_log_type("TRAFFIC", &fields).unwrap());
+        assert_eq!(schema.type_to_fields["TRAFFIC"], vec!["src_ip", "b", "new", "type", "sub"]);
+        // The old subtype layout no longer overrides the new field list
+        let map = crate::parser::parse_line_to_map("1,2,3,TRAFFIC,url", &schema).unwrap();
+        assert_eq!(map["new"].as_deref(), Some("3"));
+        assert!(!map.contains_key("old"));
+        assert!(!schema.update_log_type("NEW", &["x".to_string()]).unwrap());
+        assert!(schema.remove_log_type("LEGACY"));
+        assert!(!schema.type_to_delimiter.contains_key("LEGACY"));
+        assert!(!schema.remove_log_type("LEGACY"));
+        assert_eq!(schema.type_to_fields.len(), 2);
+    }
+
+    #[test]
+    fn test_generic_schema_shape_matches_palo_alto() {
+        let types = r#"{"type_index": 0, "log_types": {
+            "t": {"type_value": "TRAFFIC", "fields": ["Src IP", {"name": "x", "index": 3}]},
+            "l": {"type_value": "LEGACY", "fields": ["a"], "delimiter": "|"}
+        }}"#;
+        let palo: SchemaRoot =
+            serde_json::from_str(&format!(r#"{{"palo_alto_syslog_fields": {}}}"#, types)).unwrap();
+        let generic: SchemaRoot = serde_json::from_str(types).unwrap();
+        let (palo, generic) = (build_schema(palo).unwrap(), build_schema(generic).unwrap());
+        assert_eq!(generic.type_to_fields, palo.type_to_fields);
+        assert_eq!(generic.type_to_delimiter, palo.type_to_delimiter);
+        assert_eq!(generic.type_index, 0);
+        assert_eq!(palo.type_index, 0);
+        assert_eq!(generic.type_to_fields["TRAFFIC"], vec!["src_ip", "_field_1", "_field_2", "x"]);
+    }
+
+    #[test]
+    fn test_cached_schema_introspection() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        assert_eq!(cached_schema_fields("302013"), None);
+        assert!(cached_schema_types().is_empty());
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/asa_type_index0.schema.json");
+        SCHEMA_CACHE
+            .write()
+            .unwrap()
+            .insert(DEFAULT_SCHEMA_NAME.to_string(), load_schema_internal(path).unwrap());
+        assert_eq!(cached_schema_types(), vec!["106023", "302013"]);
+        assert_eq!(
+            cached_schema_fields("106023").unwrap(),
+            vec!["message_id", "protocol", "src_ip", "dst_ip", "acl"]
+        );
+        assert_eq!(cached_schema_fields("TRAFFIC"), None);
+        SCHEMA_CACHE.write().unwrap().remove(DEFAULT_SCHEMA_NAME);
+    }
+
+    #[test]
+    fn test_named_schemas_coexist() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/");
+        for (name, file) in [("asa", "asa_type_index0"), ("pan", "threat_subtypes")] {
+            let loaded = load_schema_internal(&format!("{}{}.schema.json", dir, file)).unwrap();
+            SCHEMA_CACHE.write().unwrap().insert(name.to_string(), loaded);
+        }
+        let asa = "106023,tcp,10.0.0.1,10.0.0.2,outside_in";
+        let pan = "1,2025/10/12 05:07:29,001,THREAT,url,10.0.0.1,10.0.0.2,example.com/x,news";
+        // Alternate between the two without reloading either
+        for _ in 0..2 {
+            let guard = SCHEMA_CACHE.read().unwrap();
+            let m = crate::parser::parse_line_to_map(asa, &guard["asa"]).unwrap();
+            assert_eq!(m["acl"].as_deref(), Some("outside_in"));
+            let m = crate::parser::parse_line_to_map(pan, &guard["pan"]).unwrap();
+            assert_eq!(m["url"].as_deref(), Some("example.com/x"));
+            assert!(crate::parser::parse_line_to_map(asa, &guard["pan"]).is_err());
+        }
+        // The single-schema API only sees the default name
+        assert!(cached_schema_types().is_empty());
+        let mut guard = SCHEMA_CACHE.write().unwrap();
+        guard.remove("asa");
+        guard.remove("pan");
+    }
+
+    #[test]
+    fn test_load_schema_from_str() {
+        let schema =
+            load_schema_from_str(include_str!("../testdata/asa_type_index0.schema.json")).unwrap();
+        assert!(schema.path.is_empty() && schema.mtime.is_none());
+        let map =
+            crate::parser::parse_line_to_map("106023,tcp,10.0.0.1,10.0.0.2,outside_in", &schema)
+                .unwrap();
+        assert_eq!(map["acl"].as_deref(), Some("outside_in"));
+        let err = load_schema_from_str("{not json").err().unwrap();
+        assert!(err.contains("Failed to parse"), "{}", err);
+    }
+
+    #[test]
+    fn test_watcher_reloads_changed_schema() {
+        let _lock = CACHE_LOCK.lock().unwrap();
+        let path = std::env::temp_dir().join(format!("logparse_watch_{}.json", std::process::id()));
+        let schema = |fields: &str| {
+            format!(
+                r#"{{"log_types": {{"t": {{"type_value": "TRAFFIC", "fields": {}}}}}}}"#,
+                fields
+            )
+        };
+        std::fs::write(&path, schema(r#"["a"]"#)).unwrap();
+        start_watcher(path.to_str().unwrap(), Duration::from_millis(10)).unwrap();
+        assert_eq!(cached_schema_fields("TRAFFIC").unwrap(), vec!["a"]);
+
+        std::fs::write(&path, schema(r#"["a", "b"]"#)).unwrap();
+        // Force a distinct mtime on filesystems with coarse timestamps
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while cached_schema_fields("TRAFFIC").unwrap().len() != 2 {
+            assert!(Instant::now() < deadline, "schema was not reloaded");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        assert!(stop_watcher());
+        assert!(!stop_watcher());
+        SCHEMA_CACHE.write().unwrap().remove(DEFAULT_SCHEMA_NAME);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_duplicate_field_names_are_renamed() {
+        let root: SchemaRoot = serde_json::from_str(
+            r#"{"log_types": {"t": {"type_value": "TRAFFIC",
+                "fields": ["Src IP", "src-ip", "src_ip_2", "SRC IP"]}}}"#,
+        )
+        .unwrap();
+        let schema = build_schema(root).unwrap();
+        assert_eq!(
+            schema.type_to_fields["TRAFFIC"],
+            vec!["src_ip", "src_ip_3", "src_ip_2", "src_ip_4"]
+        );
+        assert_eq!(schema.warnings.len(), 2);
+        assert!(schema.warnings[0].contains("src_ip at index 1 renamed to src_ip_3"));
+
+        let line = "10.0.0.1,10.0.0.2,x,TRAFFIC";
+        let map = crate::parser::parse_line_to_map(line, &schema).unwrap();
+        assert_eq!(map["src_ip"].as_deref(), Some("10.0.0.1"));
+        assert_eq!(map["src_ip_3"].as_deref(), Some("10.0.0.2"));
+    }
+
+    #[test]
+    fn test_field_aliases() {
+        let schema = load_schema_from_str(
+            r#"{"aliases": {"Src IP": "source_address", "subtype": "sub"},
+                "log_types": {"t": {"type_value": "TRAFFIC",
+                    "fields": ["serial", "src_ip", "dst_ip", "type", "subtype"],
+                    "subtypes": {"url": {"fields": ["serial", "src_ip", "x", "type", "subtype"]}}}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            schema.type_to_fields["TRAFFIC"],
+            vec!["serial", "source_address", "dst_ip", "type", "sub"]
+        );
+        let map =
+            crate::parser::parse_line_to_map("1,10.0.0.1,10.0.0.2,TRAFFIC,end", &schema).unwrap();
+        assert_eq!(map["source_address"].as_deref(), Some("10.0.0.1"));
+        assert!(!map.contains_key("src_ip"));
+        let map = crate::parser::parse_line_to_map("1,10.0.0.1,u,TRAFFIC,url", &schema).unwrap();
+        assert_eq!(map["source_address"].as_deref(), Some("10.0.0.1"));
+
+        let err = load_schema_from_str(
+            r#"{"aliases": {"src_ip": "dst_ip"},
+                "log_types": {"t": {"type_value": "TRAFFIC", "fields": ["src_ip", "dst_ip"]}}}"#,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err,
+            "Log type TRAFFIC: alias dst_ip for field src_ip collides with field dst_ip"
+        );
+        let err = load_schema_from_str(
+            r#"{"aliases": {"a": "x", "b": "x"},
+                "log_types": {"t": {"type_value": "TRAFFIC", "fields": ["a", "b"]}}}"#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.ends_with("alias x for field a collides with field b"), "{}", err);
+
+        // Partial reloads rename the same way
+        let mut schema = schema;
+        let fields = ["serial", "Src IP", "dst_ip", "type"].map(String::from);
+        assert!(schema.update_log_type("TRAFFIC", &fields).unwrap());
+        assert_eq!(
+            schema.type_to_fields["TRAFFIC"],
+            vec!["serial", "source_address", "dst_ip", "type"]
+        );
+        let clash = ["src_ip", "source_address"].map(String::from);
+        let err = schema.update_log_type("TRAFFIC", &clash).unwrap_err();
+        assert!(err.contains("collides with field source_address"), "{}", err);
+        assert_eq!(schema.type_to_fields["TRAFFIC"][1], "source_address");
+    }
+
+    #[test]
+    fn test_field_count_mismatch_warns() {
+        let root: SchemaRoot = serde_json::from_str(
+            r#"{"log_types": {
+                "t": {"type_value": "TRAFFIC", "field_count": 5, "fields": ["a", "b", "c", "type"]},
+                "c": {"type_value": "CONFIG", "field_count": 4, "fields": ["a", "b", "c", "type"]},
+                "p": {"type_value": "PINNED", "field_count": 6,
+                      "fields": ["a", {"name": "last", "index": 5}]}
+            }}"#,
+        )
+        .unwrap();
+        let schema = build_schema(root).unwrap();
+        assert_eq!(
+            schema.warnings,
+            vec!["Log type TRAFFIC: field_count is 5 but 4 fields are defined".to_string()]
+        );
+        // The schema still loads with the fields as listed
+        assert_eq!(schema.type_to_fields["TRAFFIC"].len(), 4);
+    }
+
+    #[test]
+    fn test_entry_without_type_value_is_skipped() {
+        let root: SchemaRoot = serde_json::from_str(
+            r#"{"log_types": {
+                "traffic": {"type_value": "TRAFFIC", "fields": ["a", "b", "c", "type"]},
+                "draft": {"description": "not finished", "fields": ["a"]}
+            }}"#,
+        )
+        .unwrap();
+        let schema = build_schema(root).unwrap();
+        assert_eq!(schema.type_values(), vec!["TRAFFIC"]);
+        assert_eq!(
+            schema.warnings,
+            vec!["Log type draft: no type_value; entry skipped".to_string()]
+        );
+    }
 }
@@ -0,0 +1,138 @@
+// stats.rs: cheap file-level statistics computed without a full parse
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::tokenizer::{count_fields, extract_field_internal};
+
+/// Result of sampling the head of a file; see `estimate_file`.
+pub struct FileEstimate {
+    pub file_bytes: u64,
+    pub sampled_lines: usize,
+    /// Mean bytes per sampled line, including the line terminator
+    pub avg_line_bytes: f64,
+    /// Exact when the sample reached end of file, else file_bytes / avg_line_bytes
+    pub estimated_lines: u64,
+    pub sampled_to_eof: bool,
+    /// Distinct values at the type index among the sampled lines, sorted
+    pub distinct_types: Vec<String>,
+}
+
+/// Read at most `sample_n` lines from the start of `path` and extrapolate the
+/// total line count from the file size. Only the sample is read. Log types are
+/// collected from field `type_index` (see `LoadedSchema::type_index`).
+pub fn estimate_file(
+    path: &str,
+    sample_n: usize,
+    type_index: usize,
+) -> Result<FileEstimate, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let file_bytes = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut reader = BufReader::new(file);
+
+    let mut buf = String::new();
+    let mut sampled_lines = 0usize;
+    let mut sampled_bytes = 0u64;
+    let mut sampled_to_eof = false;
+    let mut types: BTreeSet<String> = BTreeSet::new();
+    while sampled_lines < sample_n {
+        buf.clear();
+        let read = reader.read_line(&mut buf).map_err(|e| e.to_string())?;
+        if read == 0 {
+            sampled_to_eof = true;
+            break;
+        }
+        sampled_lines += 1;
+        sampled_bytes += read as u64;
+        let line = buf.trim_end_matches(['\n', '\r']);
+        if let Some(t) = extract_field_internal(line, type_index) {
+            types.insert(t);
+        }
+    }
+    if !sampled_to_eof && sampled_bytes == file_bytes {
+        sampled_to_eof = true;
+    }
+
+    let avg_line_bytes =
+        if sampled_lines > 0 { sampled_bytes as f64 / sampled_lines as f64 } else { 0.0 };
+    let estimated_lines = if sampled_to_eof {
+        sampled_lines as u64
+    } else {
+        (file_bytes as f64 / avg_line_bytes).round() as u64
+    };
+    Ok(FileEstimate {
+        file_bytes,
+        sampled_lines,
+        avg_line_bytes,
+        estimated_lines,
+        sampled_to_eof,
+        distinct_types: types.into_iter().collect(),
+    })
+}
+
+/// Count comma-separated (quote-aware) fields on every line of `path` and
+/// return field count -> number of lines. Off-by-one spikes next to the
+/// dominant width usually mean delimiters leaking into unquoted data. Blank
+/// lines are counted under 0.
+pub fn field_count_histogram(path: &str) -> Result<BTreeMap<usize, u64>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut reader = BufReader::new(file);
+    let mut hist: BTreeMap<usize, u64> = BTreeMap::new();
+    let mut buf = String::new();
+    loop {
+        buf.clear();
+        if reader.read_line(&mut buf).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+        let line = buf.trim_end_matches(['\n', '\r']);
+        *hist.entry(count_fields(line)).or_insert(0) += 1;
+    }
+    Ok(hist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_file, field_count_histogram};
+
+    #[test]
+    fn test_estimate_file() {
+        let path =
+            std::env::temp_dir().join(format!("logparse_estimate_{}.csv", std::process::id()));
+        // 100 lines of exactly 20 bytes each (19 + newline), alternating types
+        let mut data = String::new();
+        for i in 0..100 {
+            data.push_str(if i % 2 == 0 {
+                "1,2,serial0,TRAFFIC\n"
+            } else {
+                "1,2,serial00,THREAT\n"
+            });
+        }
+        std::fs::write(&path, &data).unwrap();
+        let p = path.to_str().unwrap();
+
+        let est = estimate_file(p, 10, 3).unwrap();
+        assert_eq!(est.file_bytes, 2000);
+        assert_eq!(est.sampled_lines, 10);
+        assert!(!est.sampled_to_eof);
+        assert_eq!(est.avg_line_bytes, 20.0);
+        assert_eq!(est.estimated_lines, 100);
+        assert_eq!(est.distinct_types, vec!["THREAT", "TRAFFIC"]);
+
+        let full = estimate_file(p, 1000, 3).unwrap();
+        assert!(full.sampled_to_eof);
+        assert_eq!(full.estimated_lines, 100);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_field_count_histogram() {
+        let path =
+            std::env::temp_dir().join(format!("logparse_histogram_{}.csv", std::process::id()));
+        std::fs::write(&path, "a,b,c\r\n1,2,3\n\"x,y\",2,3\nshort,row\n\na,b,c,d\n").unwrap();
+        let hist = field_count_histogram(path.to_str().unwrap()).unwrap();
+        let got: Vec<(usize, u64)> = hist.into_iter().collect();
+        assert_eq!(got, vec![(0, 1), (2, 1), (3, 3), (4, 1)]);
+        std::fs::remove_file(&path).ok();
+    }
+}
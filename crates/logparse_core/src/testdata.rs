@@ -0,0 +1,15 @@
+// Shared fixtures for unit tests: the mixed TRAFFIC/THREAT capture in
+// testdata/ and the schema it is parsed against.
+use crate::schema::LoadedSchema;
+
+pub(crate) const TRAFFIC_MIXED: &str = include_str!("../testdata/traffic_mixed.csv");
+
+/// TRAFFIC and THREAT with the same eight-field layout, as in `TRAFFIC_MIXED`.
+pub(crate) fn traffic_schema() -> LoadedSchema {
+    let mut schema = LoadedSchema::default();
+    let fields = ["serial", "time", "vsys", "type", "subtype", "src", "dst", "action"];
+    let fields: Vec<String> = fields.map(String::from).to_vec();
+    schema.type_to_fields.insert("TRAFFIC".to_string(), fields.clone());
+    schema.type_to_fields.insert("THREAT".to_string(), fields);
+    schema
+}
@@ -1,134 +1,473 @@
 // tokenizer.rs: CSV extraction and splitting utilities
+//
+// The `*_with_delim` variants (and `unterminated_quote`, `split_csv_into`)
+// accept any ASCII byte other than `"` as the delimiter and panic on others:
+// a non-ASCII byte can match inside a multibyte character, and fields are
+// sliced from the `&str` at delimiter positions.
 use memchr::{memchr, memchr_iter};
+use std::borrow::Cow;
+use std::fmt;
 
 pub fn extract_field_internal(line: &str, target_idx: usize) -> Option<String> {
+    extract_field_with_delim(line, target_idx, b',')
+}
+
+/// Like `extract_field_internal`, but an unquoted field is borrowed from
+/// `line` instead of copied; quoted fields are unescaped into an owned String.
+/// Suited to lookups such as routing on the log type, done on every line.
+pub fn extract_field_ref(line: &str, target_idx: usize) -> Option<Cow<'_, str>> {
+    extract_field_ref_with_delim(line, target_idx, b',')
+}
+
+/// Quote-aware extraction of field `target_idx` using `delim` as the separator.
+/// Panics unless `delim` is ASCII and not `"`.
+pub fn extract_field_with_delim(line: &str, target_idx: usize, delim: u8) -> Option<String> {
+    extract_field_ref_with_delim(line, target_idx, delim).map(Cow::into_owned)
+}
+
+fn extract_field_ref_with_delim(line: &str, target_idx: usize, delim: u8) -> Option<Cow<'_, str>> {
+    assert_delim(delim);
     let bytes = line.as_bytes();
-    let mut i = 0usize;
     let n = bytes.len();
-    let mut idx = 0usize;
+    let mut i = 0usize;
 
-    while idx <= target_idx && i <= n {
+    // Fields before the target are stepped over without being materialized
+    for _ in 0..target_idx {
         if i >= n {
-            if idx == target_idx {
-                return Some(String::new());
-            } else {
-                return None;
-            }
+            return None;
         }
-        let mut field = String::new();
-        if bytes[i] == b'"' {
+        i = skip_field(bytes, i, delim);
+        if i < n && bytes[i] == delim {
             i += 1;
-            while i < n {
-                let b = bytes[i];
-                if b == b'"' {
-                    if i + 1 < n && bytes[i + 1] == b'"' {
-                        field.push('"');
-                        i += 2;
-                        continue;
-                    } else {
-                        i += 1;
-                        break;
-                    }
-                } else {
-                    field.push(b as char);
-                    i += 1;
-                }
-            }
-            while i < n && bytes[i] != b',' {
-                i += 1;
-            }
+        }
+    }
+    if i >= n {
+        return Some(Cow::Borrowed(""));
+    }
+    if bytes[i] == b'"' {
+        let (close, _) = skip_quoted(bytes, i);
+        Some(Cow::Owned(line[i + 1..close].replace("\"\"", "\"")))
+    } else {
+        let end = memchr(delim, &bytes[i..]).map_or(n, |pos| i + pos);
+        match std::str::from_utf8(&bytes[i..end]) {
+            Ok(s) => Some(Cow::Borrowed(s)),
+            Err(_) => Some(Cow::Owned(bytes[i..end].iter().map(|&b| b as char).collect())),
+        }
+    }
+}
+
+/// Position just past the field starting at `i` (its delimiter, or the end of
+/// the line), following the quote rules of `extract_field_with_delim`: `""`
+/// inside quotes is an escaped quote, stray bytes between the closing quote
+/// and the delimiter are skipped with the field, and an unterminated quote
+/// runs to the end of the line.
+fn skip_field(bytes: &[u8], mut i: usize, delim: u8) -> usize {
+    let n = bytes.len();
+    if bytes[i] == b'"' {
+        i = skip_quoted(bytes, i).1;
+    }
+    memchr(delim, &bytes[i..]).map_or(n, |pos| i + pos)
+}
+
+/// Panic unless `delim` is an ASCII byte other than `"` (see the module docs).
+#[track_caller]
+fn assert_delim(delim: u8) {
+    assert!(
+        delim.is_ascii() && delim != b'"',
+        "delimiter must be an ASCII byte other than '\"', got {:#04x}",
+        delim
+    );
+}
+
+/// For the quoted field opened at `bytes[i]`: the index of its closing quote
+/// and the position just past it, stepping over `""` escapes. Every quote
+/// between the two is half of an escape. An unterminated quote runs to the
+/// end of the line, giving `(len, len)`.
+fn skip_quoted(bytes: &[u8], i: usize) -> (usize, usize) {
+    let n = bytes.len();
+    let mut j = i + 1;
+    loop {
+        let Some(pos) = memchr(b'"', &bytes[j..]) else { return (n, n) };
+        j += pos;
+        if j + 1 < n && bytes[j + 1] == b'"' {
+            j += 2;
         } else {
-            if let Some(pos) = memchr(b',', &bytes[i..]) {
-                let end = i + pos;
-                match std::str::from_utf8(&bytes[i..end]) {
-                    Ok(s) => field.push_str(s),
-                    Err(_) => field.extend((&bytes[i..end]).iter().map(|&b| b as char)),
-                }
-                i = end;
-            } else {
-                match std::str::from_utf8(&bytes[i..]) {
-                    Ok(s) => field.push_str(s),
-                    Err(_) => field.extend((&bytes[i..]).iter().map(|&b| b as char)),
-                }
-                i = n;
+            return (j, j + 1);
+        }
+    }
+}
+
+pub fn split_csv_internal(line: &str) -> Vec<String> {
+    split_csv_with_delim(line, b',')
+}
+
+/// Quote-aware split using `delim` as the field separator (e.g. `b'|'` or `b'\t'`).
+/// Panics unless `delim` is ASCII and not `"`.
+pub fn split_csv_with_delim(line: &str, delim: u8) -> Vec<String> {
+    let mut out = Vec::new();
+    split_csv_into(line, delim, &mut out, 0);
+    out
+}
+
+/// Error from `split_csv_strict`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenizeError {
+    /// The quoted field opened at byte `offset` is never closed
+    UnterminatedQuote { offset: usize },
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenizeError::UnterminatedQuote { offset } => {
+                write!(f, "Unterminated quoted field opened at byte offset {}", offset)
             }
         }
-        if i < n && bytes[i] == b',' {
-            i += 1;
+    }
+}
+
+impl std::error::Error for TokenizeError {}
+
+/// Like `split_csv_internal`, but fails on a quoted field that is never
+/// closed instead of running it to the end of the line.
+pub fn split_csv_strict(line: &str) -> Result<Vec<String>, TokenizeError> {
+    match unterminated_quote(line, b',') {
+        Some((_, offset)) => Err(TokenizeError::UnterminatedQuote { offset }),
+        None => Ok(split_csv_internal(line)),
+    }
+}
+
+/// Write the fields of `line` (as `split_csv_with_delim`) into `out` starting
+/// at slot `from`, overwriting the Strings already there in place so a buffer
+/// kept across lines stops allocating once it has grown to fit them. Returns
+/// the index just past the last field written; slots after it keep stale
+/// values for reuse by later lines.
+pub fn split_csv_into(line: &str, delim: u8, out: &mut Vec<String>, from: usize) -> usize {
+    if out.len() <= from {
+        // Pre-reserve capacity based on delimiter count to reduce reallocations
+        let approx_fields = memchr_iter(delim, line.as_bytes()).count() + 1;
+        out.reserve(approx_fields.max(8));
+    }
+    let mut n = from;
+    for field in csv_fields_with_delim(line, delim) {
+        set_slot(out, n, &field);
+        n += 1;
+    }
+    n
+}
+
+/// Set `out[i]` to `value`, reusing the String there if there is one.
+/// Slots are filled in order, so `i` is at most `out.len()`.
+pub(crate) fn set_slot(out: &mut Vec<String>, i: usize, value: &str) {
+    match out.get_mut(i) {
+        Some(slot) => {
+            slot.clear();
+            slot.push_str(value);
         }
-        if idx == target_idx {
-            return Some(field);
+        None => out.push(value.to_string()),
+    }
+}
+
+/// Zero-copy, quote-aware field iterator over a CSV line. Fields borrow from
+/// the line unless they contain escaped quotes (`""`), which must be unescaped
+/// into an owned String. Yields exactly the fields of `split_csv_internal`.
+pub struct CsvFields<'a> {
+    line: &'a str,
+    delim: u8,
+    pos: usize,
+    done: bool,
+}
+
+pub fn csv_fields(line: &str) -> CsvFields<'_> {
+    csv_fields_with_delim(line, b',')
+}
+
+/// `csv_fields` split on `delim`; panics unless it is ASCII and not `"`.
+pub fn csv_fields_with_delim(line: &str, delim: u8) -> CsvFields<'_> {
+    assert_delim(delim);
+    CsvFields { line, delim, pos: 0, done: false }
+}
+
+impl<'a> Iterator for CsvFields<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        self.next_span().map(|(_, _, value)| value)
+    }
+}
+
+impl<'a> CsvFields<'a> {
+    /// The next field with the byte range it occupies in the line, quotes
+    /// included, delimiter excluded.
+    fn next_span(&mut self) -> Option<(usize, usize, Cow<'a, str>)> {
+        if self.done {
+            return None;
         }
-        idx += 1;
+        let bytes = self.line.as_bytes();
+        let n = bytes.len();
+        let i = self.pos;
+        if i >= n {
+            self.done = true;
+            // A trailing delimiter ends the line with one more empty field
+            return if n > 0 && bytes[n - 1] == self.delim {
+                Some((n, n, Cow::Borrowed("")))
+            } else {
+                None
+            };
+        }
+        let (value, end) = if bytes[i] == b'"' {
+            let (close, j) = skip_quoted(bytes, i);
+            // Anything between the closing quote and the delimiter is dropped
+            let end = memchr(self.delim, &bytes[j..]).map_or(n, |p| j + p);
+            let raw = &self.line[i + 1..close];
+            let value = match memchr(b'"', raw.as_bytes()) {
+                Some(_) => Cow::Owned(raw.replace("\"\"", "\"")),
+                None => Cow::Borrowed(raw),
+            };
+            (value, end)
+        } else {
+            let end = memchr(self.delim, &bytes[i..]).map_or(n, |p| i + p);
+            (Cow::Borrowed(&self.line[i..end]), end)
+        };
+        self.pos = if end < n { end + 1 } else { n };
+        Some((i, end, value))
     }
-    None
 }
 
-pub fn split_csv_internal(line: &str) -> Vec<String> {
+/// Like `split_csv_internal`, but with each field's byte range in `line`:
+/// `(start, end, value)`, where `&line[start..end]` is the field's raw text
+/// including any quotes (and anything after the closing quote), without the
+/// delimiter. The empty field after a trailing comma is `(len, len, "")`.
+pub fn split_csv_spans(line: &str) -> Vec<(usize, usize, String)> {
+    let mut fields = csv_fields(line);
+    std::iter::from_fn(|| fields.next_span())
+        .map(|(start, end, value)| (start, end, value.into_owned()))
+        .collect()
+}
+
+/// Number of fields in `line`, same as `split_csv_internal(line).len()`
+/// (including the empty field after a trailing comma), without allocating.
+pub fn count_fields(line: &str) -> usize {
     let bytes = line.as_bytes();
+    let n = bytes.len();
     let mut i = 0usize;
+    let mut count = 0usize;
+    while i < n {
+        count += 1;
+        i = skip_field(bytes, i, b',') + 1;
+    }
+    if n > 0 && bytes[n - 1] == b',' {
+        count += 1;
+    }
+    count
+}
+
+/// First quoted field of `line` whose closing quote never comes (the lenient
+/// splitters run it to the end of the line), as its field index and the byte
+/// offset of its opening quote. Panics unless `delim` is ASCII and not `"`.
+pub fn unterminated_quote(line: &str, delim: u8) -> Option<(usize, usize)> {
+    assert_delim(delim);
+    let bytes = line.as_bytes();
     let n = bytes.len();
-    // Pre-reserve capacity based on comma count to reduce reallocations
-    let approx_fields = memchr_iter(b',', bytes).count() + 1;
-    let mut out: Vec<String> = Vec::with_capacity(approx_fields.max(8));
+    let mut i = 0usize;
+    let mut field = 0usize;
+    while i < n {
+        let mut j = i;
+        if bytes[i] == b'"' {
+            let (close, next) = skip_quoted(bytes, i);
+            if close == n {
+                return Some((field, i));
+            }
+            j = next;
+        }
+        i = j + memchr(delim, &bytes[j..])? + 1;
+        field += 1;
+    }
+    None
+}
 
-    while i <= n {
+/// Byte offset at which field `idx` starts (comma-delimited, quote-aware), or
+/// `None` when the line has fewer than `idx + 1` fields.
+pub fn field_start_offset(line: &str, idx: usize) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let n = bytes.len();
+    let mut i = 0usize;
+    for _ in 0..idx {
         if i >= n {
-            if n > 0 && bytes.get(n.wrapping_sub(1)) == Some(&b',') {
-                out.push(String::new());
-            }
-            break;
+            return None;
         }
-        // Small initial capacity helps for short fields and avoids many growth steps
-        let mut field = String::with_capacity(16);
-        if bytes[i] == b'"' {
-            i += 1;
-            while i < n {
-                let b = bytes[i];
-                if b == b'"' {
-                    if i + 1 < n && bytes[i + 1] == b'"' {
-                        field.push('"');
-                        i += 2;
+        let end = skip_field(bytes, i, b',');
+        if end == n {
+            return None;
+        }
+        i = end + 1;
+    }
+    Some(i)
+}
+
+/// Join `fields` into one CSV line, the inverse of `split_csv_internal`:
+/// `split_csv_internal(&join_csv(f)) == f` for any `f`. Fields containing the
+/// delimiter, a quote or a line break are quoted, with quotes doubled.
+pub fn join_csv(fields: &[impl AsRef<str>]) -> String {
+    join_csv_with_delim(fields, b',')
+}
+
+/// `join_csv` with `delim` as the separator, the inverse of `split_csv_with_delim`.
+/// Panics unless `delim` is ASCII and not `"`.
+pub fn join_csv_with_delim(fields: &[impl AsRef<str>], delim: u8) -> String {
+    assert_delim(delim);
+    let mut out = String::with_capacity(fields.iter().map(|f| f.as_ref().len() + 1).sum());
+    for (i, field) in fields.iter().enumerate() {
+        let field = field.as_ref();
+        if i > 0 {
+            out.push(delim as char);
+        }
+        let special = field.bytes().any(|b| b == delim || matches!(b, b'"' | b'\n' | b'\r'));
+        // A lone empty field would otherwise read back as no fields at all
+        if special || (fields.len() == 1 && field.is_empty()) {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out
+}
+
+/// Like `split_csv_internal`, but pairs each unescaped value with whether the
+/// field was quoted in the source line, so writers can round-trip quoting.
+pub fn split_csv_with_flags(line: &str) -> Vec<(String, bool)> {
+    let mut fields = csv_fields(line);
+    std::iter::from_fn(|| fields.next_span())
+        .map(|(start, _, value)| (value.into_owned(), line.as_bytes().get(start) == Some(&b'"')))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        count_fields, csv_fields, extract_field_internal, extract_field_ref,
+        extract_field_with_delim, field_start_offset, join_csv, join_csv_with_delim,
+        split_csv_internal, split_csv_spans, split_csv_strict, split_csv_with_delim,
+        split_csv_with_flags, unterminated_quote, TokenizeError,
+    };
+    use memchr::memchr;
+    use std::borrow::Cow;
+
+    /// Every line used by the other tokenizer tests, plus quote edge cases
+    const CORPUS: &[&str] = &[
+        "",
+        ",",
+        ",,",
+        "a",
+        "a,b,c",
+        "a,b,",
+        "a,\"b,c\",d,,e",
+        ",leading,comma",
+        "trailing,comma,",
+        "quoted,\"\"\"q\"\"\"",
+        "\"a,b\",\"c\"\"d\"\"e\",f",
+        "a,\"b\",\"c,\"\"d\"\"\",",
+        "x,\"y\",,z",
+        "a,\"b,c\",,\"a\"\"b\"",
+        "\"unterminated,x",
+        "\"unterminated,",
+        "\"q\"junk,y",
+        "\"a\"\"\"",
+        "x,\"\",",
+        "a,\"b,c\",d,TYPE,rest",
+        "\"café\",\"🎉 \"\"ok\"\"\",naïve",
+    ];
+
+    /// The original extraction, which builds every field up to the target;
+    /// the reference for `test_extract_field_skip_parity`.
+    fn extract_field_materialized(line: &str, target_idx: usize, delim: u8) -> Option<String> {
+        let bytes = line.as_bytes();
+        let mut i = 0usize;
+        let n = bytes.len();
+        let mut idx = 0usize;
+
+        while idx <= target_idx && i <= n {
+            if i >= n {
+                if idx == target_idx {
+                    return Some(String::new());
+                } else {
+                    return None;
+                }
+            }
+            let mut field = String::new();
+            if bytes[i] == b'"' {
+                i += 1;
+                // Buffer raw bytes and decode once so multibyte UTF-8 survives intact
+                let mut buf: Vec<u8> = Vec::new();
+                while i < n {
+                    let b = bytes[i];
+                    if b == b'"' {
+                        if i + 1 < n && bytes[i + 1] == b'"' {
+                            buf.push(b'"');
+                            i += 2;
+                            continue;
+                        } else {
+                            i += 1;
+                            break;
+                        }
                     } else {
+                        buf.push(b);
                         i += 1;
-                        break;
                     }
-                } else {
-                    field.push(b as char);
+                }
+                field = String::from_utf8(buf).unwrap();
+                while i < n && bytes[i] != delim {
                     i += 1;
                 }
+            } else {
+                if let Some(pos) = memchr(delim, &bytes[i..]) {
+                    let end = i + pos;
+                    match std::str::from_utf8(&bytes[i..end]) {
+                        Ok(s) => field.push_str(s),
+                        Err(_) => field.extend(bytes[i..end].iter().map(|&b| b as char)),
+                    }
+                    i = end;
+                } else {
+                    match std::str::from_utf8(&bytes[i..]) {
+                        Ok(s) => field.push_str(s),
+                        Err(_) => field.extend(bytes[i..].iter().map(|&b| b as char)),
+                    }
+                    i = n;
+                }
             }
-            while i < n && bytes[i] != b',' {
+            if i < n && bytes[i] == delim {
                 i += 1;
             }
-        } else {
-            if let Some(pos) = memchr(b',', &bytes[i..]) {
-                let end = i + pos;
-                match std::str::from_utf8(&bytes[i..end]) {
-                    Ok(s) => field.push_str(s),
-                    Err(_) => field.extend((&bytes[i..end]).iter().map(|&b| b as char)),
-                }
-                i = end;
-            } else {
-                match std::str::from_utf8(&bytes[i..]) {
-                    Ok(s) => field.push_str(s),
-                    Err(_) => field.extend((&bytes[i..]).iter().map(|&b| b as char)),
-                }
-                i = n;
+            if idx == target_idx {
+                return Some(field);
             }
+            idx += 1;
         }
-        if i < n && bytes[i] == b',' {
-            i += 1;
-        }
-        out.push(field);
+        None
     }
 
-    out
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{extract_field_internal, split_csv_internal};
+    #[test]
+    fn test_extract_field_skip_parity() {
+        for &line in CORPUS {
+            let prefixes = line.char_indices().map(|(end, _)| &line[..end]);
+            for text in prefixes.chain([line]) {
+                for idx in 0..count_fields(text) + 2 {
+                    for delim in [b',', b'\t', b'|'] {
+                        assert_eq!(
+                            extract_field_with_delim(text, idx, delim),
+                            extract_field_materialized(text, idx, delim),
+                            "idx={} delim={:?} line={:?}",
+                            idx,
+                            delim as char,
+                            text
+                        );
+                    }
+                }
+            }
+        }
+    }
 
     #[test]
     fn test_split_csv_internal_basic_and_quotes() {
@@ -143,6 +482,47 @@ mod tests {
         assert_eq!(v.len(), 0);
     }
 
+    #[test]
+    fn test_split_csv_strict_rejects_unterminated_quote() {
+        // Balanced quotes split like the lenient variant
+        let line = "a,\"b,c\",\"d\"\"e\"";
+        assert_eq!(split_csv_strict(line).unwrap(), split_csv_internal(line));
+        // Trailing quoted field never closed
+        assert_eq!(
+            split_csv_strict("a,b,\"abc"),
+            Err(TokenizeError::UnterminatedQuote { offset: 4 })
+        );
+        assert_eq!(split_csv_internal("a,b,\"abc"), vec!["a", "b", "abc"]);
+        // An escaped quote at the end is content, not the closing quote
+        assert_eq!(
+            split_csv_strict("a,\"b\"\""),
+            Err(TokenizeError::UnterminatedQuote { offset: 2 })
+        );
+        assert_eq!(split_csv_strict("a,\"b\"\"\"").unwrap(), vec!["a", "b\""]);
+    }
+
+    #[test]
+    fn test_split_csv_spans() {
+        let line = "001,\"a,b\",,\"say \"\"hi\"\"\",x";
+        let spans = split_csv_spans(line);
+        let got: Vec<(usize, usize, &str)> =
+            spans.iter().map(|(s, e, v)| (*s, *e, v.as_str())).collect();
+        assert_eq!(
+            got,
+            [(0, 3, "001"), (4, 9, "a,b"), (10, 10, ""), (11, 23, "say \"hi\""), (24, 25, "x")]
+        );
+        let raw: Vec<&str> = spans.iter().map(|(s, e, _)| &line[*s..*e]).collect();
+        assert_eq!(raw, ["001", "\"a,b\"", "", "\"say \"\"hi\"\"\"", "x"]);
+
+        for line in ["a,b,", "", "\"open,x", "é,\"ü\"", ",", "\"q\"tail,z"] {
+            let spans = split_csv_spans(line);
+            let values: Vec<&str> = spans.iter().map(|(_, _, v)| v.as_str()).collect();
+            assert_eq!(values, split_csv_internal(line), "{:?}", line);
+            let raw: Vec<&str> = spans.iter().map(|(s, e, _)| &line[*s..*e]).collect();
+            assert_eq!(raw.join(","), line, "{:?}", line);
+        }
+    }
+
     #[test]
     fn test_extract_field_internal() {
         // Validate consistency with split_csv_internal for a variety of inputs
@@ -175,4 +555,159 @@ mod tests {
             assert_eq!(extract_field_internal(line, split.len() + 1), None);
         }
     }
+
+    #[test]
+    fn test_extract_field_ref_matches_owned() {
+        for &line in CORPUS {
+            for idx in 0..count_fields(line) + 2 {
+                let borrowed = extract_field_ref(line, idx);
+                assert_eq!(
+                    borrowed.as_deref(),
+                    extract_field_internal(line, idx).as_deref(),
+                    "idx={} line={:?}",
+                    idx,
+                    line
+                );
+            }
+        }
+        let line = "a,\"b,c\",d,TRAFFIC,rest";
+        assert!(matches!(extract_field_ref(line, 3), Some(Cow::Borrowed("TRAFFIC"))));
+        assert!(matches!(extract_field_ref(line, 1), Some(Cow::Owned(s)) if s == "b,c"));
+    }
+
+    #[test]
+    fn test_split_csv_with_flags() {
+        let got = split_csv_with_flags("a,\"b\",\"c,\"\"d\"\"\",");
+        assert_eq!(
+            got,
+            vec![
+                ("a".to_string(), false),
+                ("b".to_string(), true),
+                ("c,\"d\"".to_string(), true),
+                (String::new(), false),
+            ]
+        );
+        // Values always agree with split_csv_internal, and the flag with the
+        // field's first byte
+        for &line in CORPUS {
+            let (values, quoted): (Vec<String>, Vec<bool>) =
+                split_csv_with_flags(line).into_iter().unzip();
+            assert_eq!(values, split_csv_internal(line), "line={:?}", line);
+            let want: Vec<bool> = split_csv_spans(line)
+                .iter()
+                .map(|&(start, end, _)| start < end && line.as_bytes()[start] == b'"')
+                .collect();
+            assert_eq!(quoted, want, "line={:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_join_csv_round_trips() {
+        assert_eq!(join_csv(&["a", "b,c", "say \"hi\"", ""]), "a,\"b,c\",\"say \"\"hi\"\"\",");
+        assert_eq!(join_csv(&[""]), "\"\"");
+        assert_eq!(join_csv(&[] as &[&str]), "");
+
+        // Random field vectors over an alphabet heavy in CSV metacharacters
+        let alphabet = ['a', 'Z', ' ', ',', '"', '|', '\n', '\r', 'é', '9'];
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+        for _ in 0..5000 {
+            let fields: Vec<String> = (0..next(6))
+                .map(|_| (0..next(5)).map(|_| alphabet[next(alphabet.len())]).collect())
+                .collect();
+            assert_eq!(split_csv_internal(&join_csv(&fields)), fields, "{:?}", fields);
+            let piped = join_csv_with_delim(&fields, b'|');
+            assert_eq!(split_csv_with_delim(&piped, b'|'), fields, "{:?}", fields);
+        }
+    }
+
+    #[test]
+    fn test_split_with_delim_and_field_offset() {
+        assert_eq!(split_csv_with_delim("a|\"b|c\"|", b'|'), vec!["a", "b|c", ""]);
+        assert_eq!(split_csv_with_delim("a,b\tc", b'\t'), vec!["a,b", "c"]);
+        for (line, delim) in [("a|\"b|c\"||d|", b'|'), ("x\ty\t\"z\"\"\"\t", b'\t')] {
+            let split = split_csv_with_delim(line, delim);
+            for (idx, want) in split.iter().enumerate() {
+                assert_eq!(extract_field_with_delim(line, idx, delim).as_ref(), Some(want));
+            }
+            assert_eq!(extract_field_with_delim(line, split.len(), delim), None);
+        }
+
+        // A non-ASCII delimiter could split "é" (0xC3 0xA9); it is refused up front
+        let line = "café,x";
+        for delim in [0xC3, b'"'] {
+            let calls: [&dyn Fn(); 4] = [
+                &|| {
+                    let _ = split_csv_with_delim(line, delim);
+                },
+                &|| {
+                    let _ = extract_field_with_delim(line, 1, delim);
+                },
+                &|| {
+                    let _ = unterminated_quote(line, delim);
+                },
+                &|| {
+                    let _ = join_csv_with_delim(&[line], delim);
+                },
+            ];
+            for call in calls {
+                assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(call)).is_err());
+            }
+        }
+
+        let line = "a,\"b,c\",d,TYPE,rest";
+        assert_eq!(field_start_offset(line, 0), Some(0));
+        assert_eq!(&line[field_start_offset(line, 3).unwrap()..], "TYPE,rest");
+        assert_eq!(&line[field_start_offset(line, 4).unwrap()..], "rest");
+        assert_eq!(field_start_offset(line, 5), None);
+        assert_eq!(field_start_offset("a,b,", 2), Some(4));
+    }
+
+    #[test]
+    fn test_csv_fields_borrow_unless_unescaping() {
+        let fields: Vec<Cow<str>> = csv_fields("a,\"b,c\",,\"a\"\"b\"").collect();
+        assert_eq!(fields, vec!["a", "b,c", "", "a\"b"]);
+        assert!(matches!(fields[0], Cow::Borrowed("a")));
+        // Quoted without escapes still borrows the interior slice
+        assert!(matches!(fields[1], Cow::Borrowed("b,c")));
+        assert!(matches!(fields[2], Cow::Borrowed("")));
+        assert!(matches!(&fields[3], Cow::Owned(s) if s == "a\"b"));
+
+        // Same fields as split_csv_internal across edge cases
+        for line in ["", ",", "a,b,", "\"unterminated,x", "\"q\"junk,y", "\"a\"\"\"", "x,\"\","] {
+            let got: Vec<String> = csv_fields(line).map(Cow::into_owned).collect();
+            assert_eq!(got, split_csv_internal(line), "line={:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_count_fields_matches_split() {
+        for &line in CORPUS {
+            assert_eq!(count_fields(line), split_csv_internal(line).len(), "line={:?}", line);
+        }
+        // Every prefix of the corpus lines, to hit states cut mid-field or mid-quote
+        for &line in CORPUS {
+            for (end, _) in line.char_indices() {
+                let prefix = &line[..end];
+                assert_eq!(count_fields(prefix), split_csv_internal(prefix).len(), "{:?}", prefix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quoted_fields_keep_non_ascii() {
+        let line = "\"café\",\"🎉 \"\"ok\"\"\",naïve";
+        let want = vec!["café", "🎉 \"ok\"", "naïve"];
+        assert_eq!(split_csv_internal(line), want);
+        for (idx, w) in want.iter().enumerate() {
+            assert_eq!(extract_field_internal(line, idx).as_deref(), Some(*w));
+        }
+        let values: Vec<String> = split_csv_with_flags(line).into_iter().map(|(v, _)| v).collect();
+        assert_eq!(values, want);
+    }
 }
@@ -0,0 +1,61 @@
+// Allocation counting for the reusable LineParser. The counting allocator is
+// global, so it lives in its own test binary rather than the lib tests.
+use logparse_core::{parse_line_to_map, FieldMap, LineParser, LoadedSchema};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts allocations per thread, so tests running in parallel do not
+/// disturb each other's counts.
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|n| n.get())
+}
+
+#[test]
+fn test_line_parser_allocations_stay_flat() {
+    let mut schema = LoadedSchema::default();
+    let fields = ["serial", "time", "vsys", "type", "subtype", "src", "dst", "action"];
+    schema.type_to_fields.insert("TRAFFIC".to_string(), fields.map(String::from).to_vec());
+    let line = "1,2025/10/12 05:07:29,001,TRAFFIC,end,10.0.0.1,10.0.0.2,allow";
+    let mut parser = LineParser::new(&schema);
+    let mut map = FieldMap::new();
+    parser.parse_into(line, &mut map).unwrap();
+
+    let start = allocations();
+    for _ in 0..100_000 {
+        parser.parse_into(line, &mut map).unwrap();
+    }
+    assert_eq!(allocations() - start, 0);
+    assert_eq!(map, parse_line_to_map(line, &schema).unwrap());
+
+    // The one-shot parse allocates on every line
+    let start = allocations();
+    for _ in 0..10 {
+        parse_line_to_map(line, &schema).unwrap();
+    }
+    assert!(allocations() - start >= 10);
+}
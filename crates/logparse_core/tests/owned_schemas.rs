@@ -0,0 +1,42 @@
+// Schemas owned by the caller, parsed against without the global cache
+use logparse_core::{cached_schema_types, parse_line_to_map, LoadedSchema};
+
+const LINE: &str = "1,2025/10/12 05:07:29,001,TRAFFIC,10.0.0.1,10.0.0.2,allow";
+
+fn schema_json(fields: &[&str]) -> String {
+    let log_types = serde_json::json!({"traffic": {"type_value": "TRAFFIC", "fields": fields}});
+    serde_json::json!({ "log_types": log_types }).to_string()
+}
+
+#[test]
+fn test_independent_schemas_parse_concurrently() {
+    let by_src = ["serial", "time", "vsys", "type", "src", "dst", "action"];
+    let by_dst = ["serial", "time", "vsys", "type", "dst", "src", "verdict"];
+    let first = LoadedSchema::from_json(&schema_json(&by_src)).unwrap();
+
+    let path = std::env::temp_dir().join(format!("logparse_owned_{}.json", std::process::id()));
+    std::fs::write(&path, schema_json(&by_dst)).unwrap();
+    let second = LoadedSchema::from_path(path.to_str().unwrap()).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(second.path, path.to_str().unwrap());
+
+    // Each thread parses repeatedly and checks it always gets the same record
+    let parse_many = |schema: &LoadedSchema| {
+        let rec = parse_line_to_map(LINE, schema).unwrap();
+        assert!((0..200).all(|_| parse_line_to_map(LINE, schema).unwrap() == rec));
+        rec
+    };
+    std::thread::scope(|s| {
+        let a = s.spawn(|| parse_many(&first));
+        let b = s.spawn(|| parse_many(&second));
+        let (a, b) = (a.join().unwrap(), b.join().unwrap());
+        assert_eq!(a["src"].as_deref(), Some("10.0.0.1"));
+        assert_eq!(a["action"].as_deref(), Some("allow"));
+        assert_eq!(b["src"].as_deref(), Some("10.0.0.2"));
+        assert_eq!(b["verdict"].as_deref(), Some("allow"));
+        assert!(!b.contains_key("action"));
+    });
+
+    assert!(LoadedSchema::from_json("{").is_err());
+    assert!(cached_schema_types().is_empty());
+}